@@ -0,0 +1,334 @@
+use crate::error::{Error, Result};
+use colored::Colorize;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+use std::fs;
+
+/// Load an OpenAPI document from a URL such as `http://localhost:8080/openapi.json` or a file
+async fn load_spec(spec: &str) -> Result<Value> {
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        let response = reqwest::get(spec).await?.error_for_status()?;
+        Ok(response.json().await?)
+    } else {
+        Ok(serde_json::from_str(&fs::read_to_string(spec)?)?)
+    }
+}
+
+/// Generate a TypeScript client from the server's OpenAPI document
+pub async fn typescript(spec: &str, out: &str) -> Result<()> {
+    let doc = load_spec(spec).await?;
+    let source = render_typescript(&doc)?;
+    fs::write(out, source)?;
+
+    println!("{} {}", "Generated".green(), out);
+    Ok(())
+}
+
+fn ts_name(schema_ref: &str) -> &str {
+    schema_ref.rsplit('/').next().unwrap_or(schema_ref)
+}
+
+fn ts_scalar(ty: &str) -> &'static str {
+    match ty {
+        "integer" | "number" => "number",
+        "boolean" => "boolean",
+        "string" => "string",
+        "null" => "null",
+        _ => "unknown",
+    }
+}
+
+/// TypeScript type of a JSON Schema as emitted by the OpenAPI document
+fn ts_type(schema: &Value) -> String {
+    if let Some(schema_ref) = schema["$ref"].as_str() {
+        return ts_name(schema_ref).to_string();
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        return values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" | ");
+    }
+    match &schema["type"] {
+        Value::String(ty) if ty == "array" => format!("{}[]", ts_type(&schema["items"])),
+        Value::String(ty) if ty == "object" => match schema["properties"].as_object() {
+            Some(properties) => {
+                let required = required_fields(schema);
+                let fields: Vec<_> = properties
+                    .iter()
+                    .map(|(name, prop)| field(name, prop, required.contains(&name.as_str())))
+                    .collect();
+                format!("{{ {} }}", fields.join("; "))
+            }
+            None => "Record<string, unknown>".to_string(),
+        },
+        Value::String(ty) => ts_scalar(ty).to_string(),
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ts_scalar)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn required_fields(schema: &Value) -> Vec<&str> {
+    schema["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+fn field(name: &str, schema: &Value, required: bool) -> String {
+    format!("{}{}: {}", name, if required { "" } else { "?" }, ts_type(schema))
+}
+
+fn interface(out: &mut String, name: &str, schema: &Value, skip_read_only: bool) {
+    let required = required_fields(schema);
+    if let Some(description) = schema["description"].as_str() {
+        let _ = writeln!(out, "/** {} */", description);
+    }
+    let _ = writeln!(out, "export interface {} {{", name);
+    if let Some(properties) = schema["properties"].as_object() {
+        for (prop_name, prop) in properties {
+            if skip_read_only && prop["readOnly"] == true {
+                continue;
+            }
+            let _ = writeln!(out, "  {};", field(prop_name, prop, required.contains(&prop_name.as_str())));
+        }
+    }
+    let _ = writeln!(out, "}}\n");
+}
+
+/// `list_post_by_post_id` -> `listPostByPostId`
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' || c == '-' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Request bodies that reference a resource schema use its create DTO, without read-only fields
+fn body_type(schema: &Value, create_dtos: &[String]) -> String {
+    let ty = ts_type(schema);
+    let dto = format!("{}Create", ty);
+    if create_dtos.contains(&dto) { dto } else { ty }
+}
+
+fn response_type(operation: &Value) -> String {
+    let Some(responses) = operation["responses"].as_object() else {
+        return "void".to_string();
+    };
+    responses
+        .iter()
+        .filter(|(status, _)| status.starts_with('2'))
+        .find_map(|(_, response)| response["content"]["application/json"].get("schema"))
+        .map(ts_type)
+        .unwrap_or_else(|| "void".to_string())
+}
+
+fn method(out: &mut String, path: &str, http_method: &str, operation: &Value, create_dtos: &[String]) {
+    let Some(operation_id) = operation["operationId"].as_str() else {
+        return;
+    };
+    // Event streams, exports and imports carry other media types and are left to `EventSource` or `fetch`,
+    // as are `HEAD` counts, which answer in a header
+    let non_json = |content: &Value| content.as_object().is_some_and(|c| !c.contains_key("application/json"));
+    if http_method == "head"
+        || non_json(&operation["requestBody"]["content"])
+        || non_json(&operation["responses"]["200"]["content"])
+    {
+        return;
+    }
+    let name = camel_case(operation_id);
+    let params = operation["parameters"].as_array().cloned().unwrap_or_default();
+
+    let mut args = vec![];
+    let mut url = format!("`{}`", path);
+    for param in params.iter().filter(|p| p["in"] == "path") {
+        let param_name = param["name"].as_str().unwrap_or_default();
+        let arg = camel_case(param_name);
+        url = url.replace(&format!("{{{}}}", param_name), &format!("${{{}}}", arg));
+        args.push(format!("{}: {}", arg, ts_type(&param["schema"])));
+    }
+
+    let body = operation["requestBody"]["content"]["application/json"].get("schema");
+    if let Some(schema) = body {
+        args.push(format!("body: {}", body_type(schema, create_dtos)));
+    }
+
+    let query: Vec<_> = params
+        .iter()
+        .filter(|p| p["in"] == "query")
+        .map(|p| field(p["name"].as_str().unwrap_or_default(), &p["schema"], p["required"] == true))
+        .collect();
+    if !query.is_empty() {
+        args.push(format!("query: {{ {} }} = {{}}", query.join("; ")));
+    }
+
+    let returns = response_type(operation);
+    if let Some(summary) = operation["summary"].as_str() {
+        let _ = writeln!(out, "  /** {} */", summary);
+    }
+    let _ = writeln!(out, "  async {}({}): Promise<{}> {{", name, args.join(", "), returns);
+
+    let mut call_args = vec![format!("\"{}\"", http_method.to_uppercase()), url];
+    match (body.is_some(), query.is_empty()) {
+        (true, true) => call_args.push("body".to_string()),
+        (true, false) => call_args.extend(["body".to_string(), "query".to_string()]),
+        (false, false) => call_args.extend(["undefined".to_string(), "query".to_string()]),
+        (false, true) => {}
+    }
+    let call = format!("this.request<{}>({})", returns, call_args.join(", "));
+
+    if operation_id == "login" {
+        // Keep the token so later calls are authenticated
+        let _ = writeln!(out, "    const result = await {};", call);
+        let _ = writeln!(out, "    this.setToken(result.token);");
+        let _ = writeln!(out, "    return result;");
+    } else {
+        let _ = writeln!(out, "    return {};", call);
+    }
+    let _ = writeln!(out, "  }}\n");
+}
+
+const CLIENT_RUNTIME: &str = r#"export class ApiError extends Error {
+  constructor(public status: number, message: string) {
+    super(message || `HTTP ${status}`);
+  }
+}
+
+export class ApiClient {
+  constructor(public baseUrl: string = DEFAULT_BASE_URL, public token: string | null = null) {}
+
+  setToken(token: string | null): void {
+    this.token = token;
+  }
+
+  private async request<T>(
+    method: string,
+    path: string,
+    body?: unknown,
+    query: Record<string, string | number | boolean | undefined> = {},
+  ): Promise<T> {
+    const params = new URLSearchParams();
+    for (const [key, value] of Object.entries(query)) {
+      if (value !== undefined) params.set(key, String(value));
+    }
+    const search = params.toString();
+
+    const headers: Record<string, string> = {};
+    if (body !== undefined) headers["Content-Type"] = "application/json";
+    if (this.token) headers["Authorization"] = `Bearer ${this.token}`;
+
+    const response = await fetch(`${this.baseUrl}${path}${search ? `?${search}` : ""}`, {
+      method,
+      headers,
+      body: body === undefined ? undefined : JSON.stringify(body),
+    });
+    if (!response.ok) {
+      throw new ApiError(response.status, await response.text());
+    }
+    if (response.headers.get("content-type")?.includes("application/json")) {
+      return (await response.json()) as T;
+    }
+    return undefined as T;
+  }
+
+"#;
+
+/// Render the client: an interface per schema, a `XCreate` DTO per resource and
+/// an `ApiClient` method per operation
+pub fn render_typescript(doc: &Value) -> Result<String> {
+    let empty = Map::new();
+    let schemas = doc["components"]["schemas"].as_object().unwrap_or(&empty);
+    let paths = doc["paths"]
+        .as_object()
+        .ok_or_else(|| Error::Config("OpenAPI document has no paths".to_string()))?;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "// Generated by `vsr codegen ts` from {} {}. Do not edit by hand.\n",
+        doc["info"]["title"].as_str().unwrap_or("the API"),
+        doc["info"]["version"].as_str().unwrap_or_default()
+    );
+    let base_url = doc["servers"][0]["url"].as_str().unwrap_or("");
+    let _ = writeln!(out, "export const DEFAULT_BASE_URL = {};\n", Value::from(base_url.trim_end_matches('/')));
+
+    // Resources are the schemas with a `Partial` counterpart
+    let mut create_dtos = vec![];
+    for (name, schema) in schemas {
+        interface(&mut out, name, schema, false);
+        if schemas.contains_key(&format!("Partial{}", name)) {
+            let dto = format!("{}Create", name);
+            let _ = writeln!(out, "/** Body of create and update requests for {} */", name);
+            interface(&mut out, &dto, schema, true);
+            create_dtos.push(dto);
+        }
+    }
+
+    out.push_str(CLIENT_RUNTIME);
+    for (path, item) in paths {
+        let Some(item) = item.as_object() else { continue };
+        for (http_method, operation) in item {
+            method(&mut out, path, http_method, operation, &create_dtos);
+        }
+    }
+    out.push_str("}\n");
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_interfaces_and_methods() {
+        let doc = json!({
+            "info": { "title": "Test", "version": "1" },
+            "servers": [{ "url": "/api" }],
+            "paths": {
+                "/post/{id}": { "put": {
+                    "operationId": "update_post",
+                    "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" } }],
+                    "requestBody": { "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Post" } } } },
+                    "responses": { "200": { "description": "Updated" } }
+                }},
+                "/auth/login": { "post": {
+                    "operationId": "login",
+                    "responses": { "200": { "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Token" } } } } }
+                }}
+            },
+            "components": { "schemas": {
+                "Post": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": ["integer", "null"], "readOnly": true },
+                        "title": { "type": "string" }
+                    },
+                    "required": ["title"]
+                },
+                "PartialPost": { "type": "object", "properties": { "title": { "type": "string" } } },
+                "Token": { "type": "object", "properties": { "token": { "type": "string" } }, "required": ["token"] }
+            }}
+        });
+
+        let ts = render_typescript(&doc).unwrap();
+        assert!(ts.contains("export const DEFAULT_BASE_URL = \"/api\";"));
+        assert!(ts.contains("export interface Post {\n  id?: number | null;\n  title: string;\n}"));
+        assert!(ts.contains("export interface PostCreate {\n  title: string;\n}"));
+        assert!(ts.contains("async updatePost(id: number, body: PostCreate): Promise<void>"));
+        assert!(ts.contains("this.request<void>(\"PUT\", `/post/${id}`, body)"));
+        assert!(ts.contains("this.setToken(result.token);"));
+    }
+}
@@ -67,10 +67,8 @@ fn generate_random_string(length: usize) -> String {
     for _ in 0..length/4 {
         let pos = rng.random_range(0..length);
         let special_idx = rng.random_range(0..special_chars.len());
-        if let Some(special) = special_chars.chars().nth(special_idx) {
-            if let Some(c) = result.get_mut(pos) {
-                *c = special;
-            }
+        if let (Some(special), Some(c)) = (special_chars.chars().nth(special_idx), result.get_mut(pos)) {
+            *c = special;
         }
     }
     
@@ -0,0 +1,152 @@
+use crate::error::Result;
+use colored::Colorize;
+use rest_macro_core::migrate::{create_migration, Migration, Migrator};
+use sqlx::AnyPool;
+use std::path::Path;
+
+/// Connect to the database and point a migrator at the migrations directory
+async fn migrator(database_url: &str, dir: &str) -> Result<Migrator> {
+    sqlx::any::install_default_drivers();
+    let pool = AnyPool::connect(database_url).await?;
+    Ok(Migrator::new(pool, dir))
+}
+
+fn print_migrations(verb: &str, migrations: &[Migration]) {
+    for migration in migrations {
+        println!("{} {}_{}", verb.green(), migration.version, migration.name);
+    }
+}
+
+/// Create an empty migration pair to write by hand
+pub fn new_migration(dir: &str, name: &str) -> Result<()> {
+    let migration = create_migration(Path::new(dir), name)?;
+
+    println!("{} {}", "Created".green(), migration.up.display());
+    if let Some(down) = &migration.down {
+        println!("{} {}", "Created".green(), down.display());
+    }
+    Ok(())
+}
+
+/// Apply all pending migrations
+pub async fn up(database_url: &str, dir: &str) -> Result<()> {
+    let applied = migrator(database_url, dir).await?.run().await?;
+
+    if applied.is_empty() {
+        println!("{}", "Database is up to date".green());
+    } else {
+        print_migrations("Applied", &applied);
+    }
+    Ok(())
+}
+
+/// Revert the last `steps` applied migrations
+pub async fn down(database_url: &str, dir: &str, steps: usize) -> Result<()> {
+    let reverted = migrator(database_url, dir).await?.revert(steps).await?;
+
+    if reverted.is_empty() {
+        println!("{}", "No applied migrations to revert".yellow());
+    } else {
+        print_migrations("Reverted", &reverted);
+    }
+    Ok(())
+}
+
+/// Revert the last applied migration and apply it again
+pub async fn redo(database_url: &str, dir: &str) -> Result<()> {
+    match migrator(database_url, dir).await?.redo().await? {
+        Some(migration) => print_migrations("Redone", &[migration]),
+        None => println!("{}", "No applied migrations to redo".yellow()),
+    }
+    Ok(())
+}
+
+/// List migrations and whether they are applied
+pub async fn status(database_url: &str, dir: &str) -> Result<()> {
+    let migrator = migrator(database_url, dir).await?;
+    let status = migrator.status().await?;
+
+    if status.is_empty() {
+        println!("No migrations found in {}", dir);
+    }
+
+    for migration in &status {
+        let state = match &migration.applied_at {
+            Some(at) if migration.has_files => format!("applied {}", at).green(),
+            Some(at) => format!("applied {} (files missing)", at).yellow(),
+            None => "pending".yellow(),
+        };
+        println!("{}_{}  {}", migration.version, migration.name, state);
+    }
+
+    if let Some(holder) = migrator.lock_holder().await? {
+        println!("{}: migrations are locked by {}", "WARNING".yellow().bold(), holder);
+    }
+    Ok(())
+}
+
+/// Release a lock left behind by a migration process that died
+pub async fn unlock(database_url: &str, dir: &str) -> Result<()> {
+    migrator(database_url, dir).await?.force_unlock().await?;
+    println!("{}", "Migration lock released".green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rest_macro_core::migrate::LOCK_TABLE;
+    use std::fs;
+
+    async fn tables(url: &str) -> Vec<String> {
+        let pool = AnyPool::connect(url).await.unwrap();
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'note'")
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn subcommands_apply_revert_and_unlock() {
+        let root = std::env::temp_dir().join(format!("vsr-cli-{:x}", rand::random::<u64>()));
+        let dir = root.join("migrations");
+        let dir = dir.to_str().unwrap();
+        fs::create_dir_all(&root).unwrap();
+        let url = format!("sqlite:{}?mode=rwc", root.join("app.db").display());
+
+        sqlx::any::install_default_drivers();
+        new_migration(dir, "Create notes").unwrap();
+        let files = Migrator::new(AnyPool::connect(&url).await.unwrap(), dir).migrations().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "create_notes");
+        fs::write(&files[0].up, "CREATE TABLE note (id INTEGER PRIMARY KEY);").unwrap();
+        fs::write(files[0].down.as_ref().unwrap(), "DROP TABLE note;").unwrap();
+
+        up(&url, dir).await.unwrap();
+        assert_eq!(tables(&url).await, ["note"]);
+        status(&url, dir).await.unwrap();
+        redo(&url, dir).await.unwrap();
+        assert_eq!(tables(&url).await, ["note"]);
+        down(&url, dir, 1).await.unwrap();
+        assert!(tables(&url).await.is_empty());
+        // Nothing left to revert or redo
+        down(&url, dir, 1).await.unwrap();
+        redo(&url, dir).await.unwrap();
+
+        let pool = AnyPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("INSERT INTO {} (id, owner, locked_at) VALUES (1, 'pid 0', 'now')", LOCK_TABLE))
+            .execute(&pool)
+            .await
+            .unwrap();
+        unlock(&url, dir).await.unwrap();
+        let locks: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", LOCK_TABLE))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(locks, 0);
+        up(&url, dir).await.unwrap();
+        assert_eq!(tables(&url).await, ["note"]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -1,6 +1,8 @@
 pub mod admin;
+pub mod codegen;
 pub mod db;
 pub mod env;
 pub mod gen_env;
 pub mod init;
+pub mod migrate;
 pub mod setup; 
\ No newline at end of file
@@ -9,6 +9,15 @@ pub enum Error {
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
     
+    #[error("Migration error: {0}")]
+    Migration(#[from] rest_macro_core::migrate::MigrateError),
+    
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    
     #[error("Password hashing error: {0}")]
     Bcrypt(#[from] bcrypt::BcryptError),
     
@@ -76,6 +76,65 @@ enum Commands {
         #[arg(short, long)]
         path: Option<String>,
     },
+    
+    /// Manage schema migrations
+    Migrate {
+        /// Directory holding the migration files
+        #[arg(long, default_value = "migrations")]
+        dir: String,
+        
+        #[command(subcommand)]
+        command: MigrateCommands,
+    },
+    
+    /// Generate API clients from the server's OpenAPI document
+    Codegen {
+        #[command(subcommand)]
+        command: CodegenCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum CodegenCommands {
+    /// Generate a typed TypeScript client
+    Ts {
+        /// URL or path of the OpenAPI document
+        #[arg(short, long, default_value = "http://localhost:8080/openapi.json")]
+        spec: String,
+        
+        /// Output file
+        #[arg(short, long, default_value = "api.ts")]
+        out: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommands {
+    /// Create an empty migration to write by hand
+    New {
+        /// Migration name
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    
+    /// Apply all pending migrations
+    Up,
+    
+    /// Revert applied migrations, newest first
+    Down {
+        /// Number of migrations to revert
+        #[arg(short, long, default_value_t = 1)]
+        steps: usize,
+    },
+    
+    /// Show which migrations are applied
+    Status,
+    
+    /// Revert the last applied migration and apply it again
+    Redo,
+    
+    /// Release a lock left behind by a migration process that died
+    Unlock,
 }
 
 #[tokio::main]
@@ -103,7 +162,7 @@ async fn main() -> Result<()> {
             println!("{}", "Initializing new project...".green().bold());
             commands::init::create_project(
                 name,
-                description.clone().unwrap_or_else(|| format!("A REST API built with very_simple_rest")),
+                description.clone().unwrap_or_else(|| "A REST API built with very_simple_rest".to_string()),
                 author.clone().unwrap_or_else(|| "Anonymous".to_string()),
                 license,
                 output_dir.clone().unwrap_or_else(|| ".".to_string()),
@@ -152,6 +211,19 @@ async fn main() -> Result<()> {
             println!("{}", "Generating environment file...".green().bold());
             commands::gen_env::generate_env_file(path.clone())?;
         },
+        
+        Commands::Migrate { dir, command } => match command {
+            MigrateCommands::New { name } => commands::migrate::new_migration(dir, name)?,
+            MigrateCommands::Up => commands::migrate::up(&database_url, dir).await?,
+            MigrateCommands::Down { steps } => commands::migrate::down(&database_url, dir, *steps).await?,
+            MigrateCommands::Status => commands::migrate::status(&database_url, dir).await?,
+            MigrateCommands::Redo => commands::migrate::redo(&database_url, dir).await?,
+            MigrateCommands::Unlock => commands::migrate::unlock(&database_url, dir).await?,
+        },
+        
+        Commands::Codegen { command } => match command {
+            CodegenCommands::Ts { spec, out } => commands::codegen::typescript(spec, out).await?,
+        },
     }
     
     Ok(())
@@ -0,0 +1,704 @@
+//! Parsing and validation of the attributes accepted by `#[derive(RestApi)]`.
+//!
+//! Everything the derive understands is collected into a [`ResourceSpec`] here,
+//! so code generation in `lib.rs` never has to deal with malformed input. Every
+//! problem is reported as a `syn::Error` pointing at the offending tokens.
+
+use proc_macro2::Span;
+use std::collections::HashMap;
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, GenericArgument, Ident, LitInt, LitStr, PathArguments, Type};
+
+/// Operations generated by the derive, in the order they are documented
+pub const OPERATIONS: [&str; 6] = ["list", "get", "create", "update", "patch", "delete"];
+
+/// Database backends accepted by `#[rest_api(db = "...")]`
+const BACKENDS: [&str; 4] = ["sqlite", "postgres", "mysql", "any"];
+
+/// Roles required for one operation: any role from `any` and every role from `all`.
+/// A `public` operation needs no token at all.
+#[derive(Clone)]
+pub struct RoleSpec {
+    pub any: Vec<String>,
+    pub all: Vec<String>,
+    pub public: bool,
+    /// The key it was given under, such as `read` or `update`
+    key: String,
+    span: Span,
+}
+
+/// Permissions required for one operation: any permission from `any` and every permission from `all`
+#[derive(Clone)]
+pub struct PermissionSpec {
+    pub any: Vec<String>,
+    pub all: Vec<String>,
+    key: String,
+    span: Span,
+}
+
+/// Role name that opens an operation to anonymous callers
+const PUBLIC_ROLE: &str = "public";
+
+/// A `#[relation(references = "table.column")]` on a field
+pub struct RelationSpec {
+    pub parent_table: String,
+    pub parent_column: String,
+    /// `/{parent}/{parent_id}/{table}` is registered unless `nested_route = "false"`
+    pub nested_route: bool,
+}
+
+/// A `#[rest_api(file(max_size = "10MB", types = "image/*"))]` on a field
+pub struct FileSpec {
+    pub max_size: u64,
+    /// Accepted content types; empty accepts any
+    pub types: Vec<String>,
+}
+
+/// Upload limit when `file` has no `max_size`
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+pub struct FieldSpec {
+    pub ident: Ident,
+    pub ty: Type,
+    pub name: String,
+    pub sql_type: &'static str,
+    pub nullable: bool,
+    pub unique: bool,
+    /// Left out of exports, change events and webhook payloads, from `#[rest_api(hidden)]`
+    pub hidden: bool,
+    pub relation: Option<RelationSpec>,
+    pub file: Option<FileSpec>,
+}
+
+impl FieldSpec {
+    /// `created_at` and `updated_at` are filled in by the database
+    pub fn is_timestamp(&self) -> bool {
+        self.name == "created_at" || self.name == "updated_at"
+    }
+}
+
+pub struct ResourceSpec {
+    pub table: String,
+    pub id_field: String,
+    pub db: String,
+    pub operations: Vec<&'static str>,
+    pub roles: HashMap<&'static str, RoleSpec>,
+    pub permissions: HashMap<&'static str, PermissionSpec>,
+    pub fields: Vec<FieldSpec>,
+    /// Seconds `get_all` and `get_one` responses are cached, from `#[rest_api(cache(ttl = 60))]`
+    pub cache_ttl: Option<u64>,
+}
+
+impl ResourceSpec {
+    pub fn is_public(&self, op: &str) -> bool {
+        self.roles.get(op).is_some_and(|requirement| requirement.public)
+    }
+
+    pub fn from_derive_input(input: &DeriveInput) -> syn::Result<Self> {
+        let fields_named = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => fields,
+                Fields::Unnamed(_) => {
+                    return Err(syn::Error::new_spanned(
+                        &input.ident,
+                        "RestApi can only be derived for structs with named fields, not tuple structs",
+                    ));
+                }
+                Fields::Unit => {
+                    return Err(syn::Error::new_spanned(
+                        &input.ident,
+                        "RestApi can only be derived for structs with named fields, not unit structs",
+                    ));
+                }
+            },
+            Data::Enum(data) => {
+                return Err(syn::Error::new_spanned(
+                    data.enum_token,
+                    "RestApi can only be derived for structs",
+                ));
+            }
+            Data::Union(data) => {
+                return Err(syn::Error::new_spanned(
+                    data.union_token,
+                    "RestApi can only be derived for structs",
+                ));
+            }
+        };
+
+        let mut spec = ResourceSpec {
+            table: input.ident.to_string().to_lowercase(),
+            id_field: "id".to_string(),
+            db: "sqlite".to_string(),
+            operations: OPERATIONS.to_vec(),
+            roles: HashMap::new(),
+            permissions: HashMap::new(),
+            fields: vec![],
+            cache_ttl: None,
+        };
+        let mut errors = Errors::default();
+
+        for attr in &input.attrs {
+            if attr.path().is_ident("rest_api") {
+                errors.push(spec.parse_rest_api_attr(attr));
+            } else if attr.path().is_ident("require_role") {
+                errors.push(spec.parse_require_role_attr(attr));
+            } else if attr.path().is_ident("require_permission") {
+                errors.push(spec.parse_require_permission_attr(attr));
+            }
+        }
+
+        for field in &fields_named.named {
+            match parse_field(field) {
+                Ok(field) => spec.fields.push(field),
+                Err(err) => errors.push(Err(err)),
+            }
+        }
+        errors.finish()?;
+
+        // Requirements for operations that are not generated would never be checked. `read` only
+        // needs one of `list` and `get` to be generated
+        let read_enabled = spec.has_operation("list") || spec.has_operation("get");
+        let mut unused = Errors::default();
+        for op in OPERATIONS.into_iter().filter(|op| !spec.has_operation(op)) {
+            let roles = spec.roles.get(op).map(|r| (&r.key, r.span, "roles"));
+            let permissions = spec.permissions.get(op).map(|p| (&p.key, p.span, "permissions"));
+            for (key, span, kind) in roles.into_iter().chain(permissions) {
+                // A `read` that covers neither is reported once, for `list`
+                if key != "read" || (!read_enabled && op == "list") {
+                    unused.push(Err(syn::Error::new(
+                        span,
+                        format!("`{}` requires {} but is not generated; remove it or enable the operation", key, kind),
+                    )));
+                }
+            }
+        }
+        unused.finish()?;
+
+        // Create and patch fall back to the update requirement so older declarations stay protected.
+        // Inheriting `public` would open them up unnoticed, so that has to be spelled out
+        for op in ["create", "patch"] {
+            if !spec.has_operation(op) {
+                continue;
+            }
+            if !spec.roles.contains_key(op)
+                && let Some(requirement) = spec.roles.get("update").cloned()
+            {
+                if requirement.public {
+                    return Err(syn::Error::new(
+                        requirement.span,
+                        format!(
+                            "`{}` would inherit `update = \"public\"`; give it its own requirement, such as `{} = \"public\"`",
+                            op, op
+                        ),
+                    ));
+                }
+                spec.roles.insert(op, requirement);
+            }
+            if !spec.permissions.contains_key(op)
+                && let Some(requirement) = spec.permissions.get("update").cloned()
+            {
+                spec.permissions.insert(op, requirement);
+            }
+        }
+
+        // Permissions are checked against the token, which public operations do not require
+        for op in OPERATIONS {
+            if spec.is_public(op)
+                && let Some(requirement) = spec.permissions.get(op)
+            {
+                return Err(syn::Error::new(
+                    requirement.span,
+                    format!("`{}` is public and cannot also require permissions", op),
+                ));
+            }
+        }
+
+        match spec.fields.iter().find(|f| f.name == spec.id_field) {
+            None => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    format!(
+                        "missing primary key: expected a field named `{}`, or set `#[rest_api(id = \"...\")]`",
+                        spec.id_field
+                    ),
+                ));
+            }
+            Some(id) if id.sql_type != "INTEGER" => {
+                return Err(syn::Error::new_spanned(
+                    &id.ty,
+                    "the primary key must be an integer, such as `Option<i64>`",
+                ));
+            }
+            Some(id) if id.hidden => {
+                return Err(syn::Error::new_spanned(&id.ident, "the primary key cannot be `hidden`"));
+            }
+            Some(_) => {}
+        }
+
+        Ok(spec)
+    }
+
+    pub fn has_operation(&self, op: &str) -> bool {
+        self.operations.contains(&op)
+    }
+
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.iter().any(|f| f.name == name)
+    }
+
+    pub fn file_fields(&self) -> impl Iterator<Item = &FieldSpec> {
+        self.fields.iter().filter(|f| f.file.is_some())
+    }
+
+    fn parse_rest_api_attr(&mut self, attr: &syn::Attribute) -> syn::Result<()> {
+        attr.parse_nested_meta(|meta| {
+            let key = meta_key(&meta.path);
+            if key == "cache" {
+                self.cache_ttl = Some(parse_cache(&meta)?);
+                return Ok(());
+            }
+            let value = meta.value()?.parse::<LitStr>()?;
+            match key.as_str() {
+                "table" => self.table = parse_identifier(&value)?,
+                "id" => self.id_field = parse_identifier(&value)?,
+                "db" => {
+                    let db = value.value();
+                    if !BACKENDS.contains(&db.as_str()) {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!("unknown database `{}`, expected one of: {}", db, BACKENDS.join(", ")),
+                        ));
+                    }
+                    self.db = db;
+                }
+                "operations" => self.operations = parse_operations(&value)?,
+                "exclude" => {
+                    let excluded = parse_operations(&value)?;
+                    self.operations.retain(|op| !excluded.contains(op));
+                }
+                _ => {
+                    return Err(meta.error(format!(
+                        "unknown `rest_api` key `{}`, expected one of: table, id, db, operations, exclude, cache",
+                        key
+                    )));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn parse_require_role_attr(&mut self, attr: &syn::Attribute) -> syn::Result<()> {
+        attr.parse_nested_meta(|meta| {
+            let targets = operation_targets(&meta)?;
+            let key = meta_key(&meta.path);
+            let span = meta.path.span();
+
+            // `op = "a, b"` is any-of; `op(any = "a, b", all = "c, d")` spells both out
+            let requirement = if meta.input.peek(syn::Token![=]) {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                let roles = parse_names(&lit, "role")?;
+                if roles.iter().any(|role| role == PUBLIC_ROLE) {
+                    if roles.len() > 1 {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "`public` cannot be combined with other roles",
+                        ));
+                    }
+                    RoleSpec { any: vec![], all: vec![], public: true, key, span }
+                } else {
+                    RoleSpec { any: roles, all: vec![], public: false, key, span }
+                }
+            } else {
+                let mut requirement = RoleSpec { any: vec![], all: vec![], public: false, key, span };
+                meta.parse_nested_meta(|nested| {
+                    let lit = nested.value()?.parse::<LitStr>()?;
+                    let roles = parse_names(&lit, "role")?;
+                    if roles.iter().any(|role| role == PUBLIC_ROLE) {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "`public` must be given on its own, as in `read = \"public\"`",
+                        ));
+                    }
+                    if nested.path.is_ident("any") {
+                        requirement.any.extend(roles);
+                    } else if nested.path.is_ident("all") {
+                        requirement.all.extend(roles);
+                    } else {
+                        return Err(nested.error("expected `any` or `all`"));
+                    }
+                    Ok(())
+                })?;
+                requirement
+            };
+
+            for op in targets {
+                if self.roles.insert(op, requirement.clone()).is_some() {
+                    return Err(meta.error(format!(
+                        "role requirement for `{}` is specified more than once",
+                        op
+                    )));
+                }
+            }
+            Ok(())
+        })
+    }
+    fn parse_require_permission_attr(&mut self, attr: &syn::Attribute) -> syn::Result<()> {
+        attr.parse_nested_meta(|meta| {
+            let targets = operation_targets(&meta)?;
+            let key = meta_key(&meta.path);
+            let span = meta.path.span();
+
+            // Same shapes as `#[require_role]`: `op = "a, b"` or `op(any = "...", all = "...")`
+            let requirement = if meta.input.peek(syn::Token![=]) {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                PermissionSpec { any: parse_names(&lit, "permission")?, all: vec![], key, span }
+            } else {
+                let mut requirement = PermissionSpec { any: vec![], all: vec![], key, span };
+                meta.parse_nested_meta(|nested| {
+                    let lit = nested.value()?.parse::<LitStr>()?;
+                    let permissions = parse_names(&lit, "permission")?;
+                    if nested.path.is_ident("any") {
+                        requirement.any.extend(permissions);
+                    } else if nested.path.is_ident("all") {
+                        requirement.all.extend(permissions);
+                    } else {
+                        return Err(nested.error("expected `any` or `all`"));
+                    }
+                    Ok(())
+                })?;
+                requirement
+            };
+
+            for op in targets {
+                if self.permissions.insert(op, requirement.clone()).is_some() {
+                    return Err(meta.error(format!(
+                        "permission requirement for `{}` is specified more than once",
+                        op
+                    )));
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<FieldSpec> {
+    let ident = field.ident.clone().expect("named fields always have an ident");
+    let name = ident.to_string();
+    let sql_type = sql_type_of(&field.ty)?;
+
+    let mut spec = FieldSpec {
+        ident,
+        ty: field.ty.clone(),
+        name,
+        sql_type,
+        nullable: option_inner(&field.ty).is_some(),
+        unique: false,
+        hidden: false,
+        relation: None,
+        file: None,
+    };
+
+    for attr in &field.attrs {
+        if attr.path().is_ident("rest_api") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("unique") {
+                    spec.unique = true;
+                    Ok(())
+                } else if meta.path.is_ident("hidden") {
+                    spec.hidden = true;
+                    Ok(())
+                } else if meta.path.is_ident("file") {
+                    spec.file = Some(parse_file(&meta)?);
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown `rest_api` field option `{}`, expected `unique`, `hidden` or `file`",
+                        meta_key(&meta.path)
+                    )))
+                }
+            })?;
+        } else if attr.path().is_ident("relation") {
+            spec.relation = Some(parse_relation(attr, &spec.name)?);
+        }
+    }
+
+    if spec.file.is_some() {
+        if option_inner(&field.ty).is_none_or(|inner| type_name(inner) != "StoredFile") {
+            return Err(syn::Error::new_spanned(&field.ty, "file fields must be `Option<StoredFile>`"));
+        }
+        if spec.unique || spec.relation.is_some() {
+            return Err(syn::Error::new_spanned(
+                &spec.ident,
+                "file fields cannot also be `unique` or a `relation`",
+            ));
+        }
+    }
+
+    Ok(spec)
+}
+
+/// `file`, `file(max_size = "10MB", types = "image/*, application/pdf")`
+fn parse_file(meta: &ParseNestedMeta) -> syn::Result<FileSpec> {
+    let mut file = FileSpec { max_size: DEFAULT_MAX_FILE_SIZE, types: vec![] };
+    if meta.input.is_empty() || meta.input.peek(syn::Token![,]) {
+        return Ok(file);
+    }
+    meta.parse_nested_meta(|inner| {
+        let key = meta_key(&inner.path);
+        let lit = inner.value()?.parse::<LitStr>()?;
+        match key.as_str() {
+            "max_size" => file.max_size = parse_size(&lit)?,
+            "types" => {
+                file.types = parse_names(&lit, "content type")?;
+                if let Some(bad) = file.types.iter().find(|t| t.split_once('/').is_none_or(|(a, b)| a.is_empty() || b.is_empty())) {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        format!("malformed content type `{}`, expected `type/subtype` or `type/*`", bad),
+                    ));
+                }
+            }
+            _ => return Err(inner.error(format!("unknown `file` key `{}`, expected `max_size` or `types`", key))),
+        }
+        Ok(())
+    })?;
+    Ok(file)
+}
+
+/// `"512KB"`, `"10MB"` or `"1GB"` in binary units, or a plain number of bytes
+fn parse_size(lit: &LitStr) -> syn::Result<u64> {
+    let value = lit.value();
+    let text = value.trim().to_ascii_uppercase();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => text.split_at(at),
+        None => (text.as_str(), ""),
+    };
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        _ => 0,
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 && multiplier > 0 => Ok(n * multiplier),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            format!("malformed size `{}`, expected a number with an optional B, KB, MB or GB unit", value),
+        )),
+    }
+}
+
+fn parse_relation(attr: &syn::Attribute, field_name: &str) -> syn::Result<RelationSpec> {
+    let mut references = None;
+    let mut nested_route = true;
+
+    attr.parse_nested_meta(|meta| {
+        let key = meta_key(&meta.path);
+        let value = meta.value()?.parse::<LitStr>()?;
+        match key.as_str() {
+            "foreign_key" => {
+                if value.value() != field_name {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!("`foreign_key` must name the annotated field `{}`", field_name),
+                    ));
+                }
+            }
+            "references" => {
+                let refs = value.value();
+                match refs.split_once('.') {
+                    Some((table, column)) if is_identifier(table) && is_identifier(column) => {
+                        references = Some((table.to_string(), column.to_string()));
+                    }
+                    _ => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!("malformed reference `{}`, expected `table.column`", refs),
+                        ));
+                    }
+                }
+            }
+            "nested_route" => {
+                nested_route = match value.value().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(syn::Error::new(value.span(), "expected \"true\" or \"false\"")),
+                };
+            }
+            _ => {
+                return Err(meta.error(format!(
+                    "unknown `relation` key `{}`, expected one of: foreign_key, references, nested_route",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    })?;
+
+    let (parent_table, parent_column) = references.ok_or_else(|| {
+        syn::Error::new_spanned(attr, "`relation` requires `references = \"table.column\"`")
+    })?;
+
+    Ok(RelationSpec {
+        parent_table,
+        parent_column,
+        nested_route,
+    })
+}
+
+/// Map a field type to its SQL column type
+fn sql_type_of(ty: &Type) -> syn::Result<&'static str> {
+    if let Some(inner) = option_inner(ty) {
+        return sql_type_of(inner);
+    }
+
+    let sql_type = match type_name(ty).as_str() {
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" => "INTEGER",
+        "f32" | "f64" => "REAL",
+        "bool" => "BOOLEAN",
+        // File metadata is kept as JSON
+        "String" | "NaiveDate" | "NaiveDateTime" | "NaiveTime" | "DateTime" | "StoredFile" => "TEXT",
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "unsupported field type, expected an integer, float, bool, String, chrono date/time type or StoredFile, optionally wrapped in Option",
+            ));
+        }
+    };
+    Ok(sql_type)
+}
+
+/// Last path segment of a type, such as `String` for `std::string::String`
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// `Option<T>` -> `T`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The operations a `#[require_role]` or `#[require_permission]` key applies to
+fn operation_targets(meta: &ParseNestedMeta) -> syn::Result<Vec<&'static str>> {
+    let key = meta_key(&meta.path);
+    match key.as_str() {
+        "read" => Ok(vec!["list", "get"]),
+        _ => match OPERATIONS.iter().find(|op| **op == key) {
+            Some(op) => Ok(vec![*op]),
+            None => Err(meta.error(format!(
+                "unknown operation `{}`, expected one of: read, {}",
+                key,
+                OPERATIONS.join(", ")
+            ))),
+        },
+    }
+}
+
+/// Split a comma-separated list of role or permission names such as `"editor, admin"`
+fn parse_names(lit: &LitStr, kind: &str) -> syn::Result<Vec<String>> {
+    let names: Vec<String> = lit.value().split(',').map(|name| name.trim().to_string()).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(syn::Error::new(lit.span(), format!("{} names cannot be empty", kind)));
+    }
+    Ok(names)
+}
+
+/// Split a comma-separated operation list such as `"list,get,create"`
+fn parse_operations(lit: &LitStr) -> syn::Result<Vec<&'static str>> {
+    lit.value()
+        .split(',')
+        .map(|op| {
+            let op = op.trim();
+            OPERATIONS.iter().find(|known| **known == op).copied().ok_or_else(|| {
+                syn::Error::new(
+                    lit.span(),
+                    format!("unknown operation `{}`, expected one of: {}", op, OPERATIONS.join(", ")),
+                )
+            })
+        })
+        .collect()
+}
+
+/// `cache(ttl = 60)`: how many seconds responses are cached
+fn parse_cache(meta: &ParseNestedMeta) -> syn::Result<u64> {
+    let mut ttl = None;
+    meta.parse_nested_meta(|inner| {
+        if inner.path.is_ident("ttl") {
+            let lit = inner.value()?.parse::<LitInt>()?;
+            let seconds = lit.base10_parse::<u64>()?;
+            if seconds == 0 {
+                return Err(syn::Error::new(lit.span(), "`ttl` must be at least 1 second"));
+            }
+            ttl = Some(seconds);
+            Ok(())
+        } else {
+            Err(inner.error(format!("unknown `cache` key `{}`, expected `ttl`", meta_key(&inner.path))))
+        }
+    })?;
+    ttl.ok_or_else(|| meta.error("`cache` requires a `ttl` in seconds, such as `cache(ttl = 60)`"))
+}
+
+/// Table and column names end up in SQL, so only plain identifiers are accepted
+fn parse_identifier(lit: &LitStr) -> syn::Result<String> {
+    let value = lit.value();
+    if is_identifier(&value) {
+        Ok(value)
+    } else {
+        Err(syn::Error::new(lit.span(), format!("`{}` is not a valid SQL identifier", value)))
+    }
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn meta_key(path: &syn::Path) -> String {
+    path.get_ident()
+        .map(|ident| ident.to_string())
+        .unwrap_or_else(|| quote::quote!(#path).to_string().replace(' ', ""))
+}
+
+/// Collects errors so that every problem is reported in a single compile
+#[derive(Default)]
+struct Errors(Option<syn::Error>);
+
+impl Errors {
+    fn push(&mut self, result: syn::Result<()>) {
+        if let Err(err) = result {
+            match &mut self.0 {
+                Some(existing) => existing.combine(err),
+                None => self.0 = Some(err),
+            }
+        }
+    }
+
+    fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,246 +1,315 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use std::collections::HashSet;
-use syn::{parse_macro_input, DeriveInput, Lit};
-use sqlx::{SqlitePool, MySqlPool, PgPool, AnyPool};
+use syn::{parse_macro_input, DeriveInput};
 
-#[proc_macro_derive(RestApi, attributes(rest_api, require_role, relation))]
+mod attrs;
+
+use attrs::ResourceSpec;
+
+#[proc_macro_derive(RestApi, attributes(rest_api, require_role, require_permission, relation))]
 pub fn rest_api_macro(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let spec = ResourceSpec::from_derive_input(input)?;
+
     let struct_name = &input.ident;
     let lower_name = struct_name.to_string().to_lowercase();
     let module_ident = format_ident!("__rest_api_impl_for_{}", lower_name);
 
-    let mut field_defs = vec![];
-    let mut field_names = vec![];
-    let mut field_idents = vec![];
-    let mut bind_fields_insert = vec![];
-    let mut bind_fields_update = vec![];
-    let mut update_clauses = vec![];
-    let mut skip_insert_fields = HashSet::new();
-
-    let mut db_type = "sqlite".to_string(); // default
-
-    for attr in &input.attrs {
-        if attr.path().is_ident("rest_api") {
-            let _ = attr.parse_nested_meta(|meta| {
-                let ident = meta.path.get_ident().unwrap().to_string();
-                let value = meta.value()?.parse::<Lit>()?;
-                if ident == "db" {
-                    if let Lit::Str(litstr) = value {
-                        db_type = litstr.value();
-                    }
-                }
-                Ok(())
-            });
-        }
-    }
-
-    let pool_type = match db_type.as_str() {
+    let db_type = spec.db.as_str();
+    let pool_type = match db_type {
         "sqlite" => quote! { SqlitePool },
         "mysql" => quote! { MySqlPool },
         "postgres" => quote! { PgPool },
         _ => quote! { AnyPool },
     };
+    let database = match db_type {
+        "sqlite" => quote! { sqlx::Sqlite },
+        "mysql" => quote! { sqlx::MySql },
+        "postgres" => quote! { sqlx::Postgres },
+        _ => quote! { sqlx::Any },
+    };
+    let placeholder = |n: usize| {
+        if db_type == "postgres" {
+            format!("${}", n)
+        } else {
+            "?".to_string()
+        }
+    };
 
-    let table_name = lower_name.clone();
-    let id_field = "id";
-
-    // Track relations for nested routes
-    let mut relation_field = String::new();
-    let mut relation_parent_table = String::new();
-
-    // Default role requirements
-    let mut read_role = None;
-    let mut update_role = None;
-    let mut delete_role = None;
-
-    // Parse require_role attributes
-    for attr in &input.attrs {
-        if attr.path().is_ident("require_role") {
-            let _ = attr.parse_nested_meta(|meta| {
-                let path = meta.path.get_ident().unwrap().to_string();
-                let value = meta.value()?.parse::<syn::LitStr>()?.value();
-
-                if path == "read" {
-                    read_role = Some(value);
-                } else if path == "update" {
-                    update_role = Some(value);
-                } else if path == "delete" {
-                    delete_role = Some(value);
+    let table_name = spec.table.as_str();
+    let id_field = spec.id_field.as_str();
+
+    // Generate role and permission checks for each operation
+    let role_check = |op: &str| {
+        // Roles may be overridden at runtime by a `PolicyStore`, so every authenticated operation asks it
+        let roles = match spec.roles.get(op) {
+            Some(requirement) if requirement.public => quote! {},
+            requirement => {
+                let fallback = match requirement {
+                    Some(requirement) => {
+                        let any = &requirement.any;
+                        let all = &requirement.all;
+                        quote! { Some(RoleRequirement { any: &[#(#any),*], all: &[#(#all),*] }) }
+                    }
+                    None => quote! { None },
+                };
+                quote! {
+                    const REQUIRED_ROLES: Option<RoleRequirement> = #fallback;
+                    match policy::authorize(&req, &user, #table_name, #op, REQUIRED_ROLES).await {
+                        Ok(true) => {}
+                        Ok(false) => return HttpResponse::Forbidden().body("Insufficient privileges"),
+                        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                    }
+                }
+            }
+        };
+        let permissions = match spec.permissions.get(op) {
+            Some(requirement) => {
+                let any = &requirement.any;
+                let all = &requirement.all;
+                quote! {
+                    const REQUIRED_PERMISSIONS: PermissionRequirement = PermissionRequirement {
+                        any: &[#(#any),*],
+                        all: &[#(#all),*],
+                    };
+                    if !REQUIRED_PERMISSIONS.is_satisfied_by(
+                        &user,
+                        PermissionMap::from_request(&req),
+                        RoleHierarchy::from_request(&req),
+                    ) {
+                        return HttpResponse::Forbidden().body("Insufficient privileges");
+                    }
                 }
+            }
+            None => quote! {},
+        };
+        quote! { #roles #permissions }
+    };
+    let list_check = role_check("list");
+    let get_check = role_check("get");
+    let create_check = role_check("create");
+    let update_check = role_check("update");
+    let patch_check = role_check("patch");
+    let delete_check = role_check("delete");
+    // Write handlers begin their transaction once the checks pass, so rejected callers never hold a connection
+    let begin_tx = quote! {
+        let mut tx = match Tx::begin(db.get_ref()).await {
+            Ok(tx) => tx,
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        };
+    };
 
-                Ok(())
-            });
+    // Public operations accept anonymous callers but still receive the user when a token is sent.
+    // The others also take the request, which carries the role hierarchy, permission map and policy
+    let user_param = |op: &str| {
+        if spec.is_public(op) {
+            quote! { user: OptionalUserContext }
+        } else {
+            quote! { req: HttpRequest, user: UserContext }
         }
-    }
-
-    // Generate role check for read operations
-    let read_check = if let Some(role) = &read_role {
-        quote! {
-            // Admin role always has access
-            if !user.roles.contains(&String::from("admin")) && !user.roles.contains(&String::from(#role)) {
-                return HttpResponse::Forbidden().body("Insufficient privileges");
-            }
+    };
+    // Handlers that read the request themselves, such as the event streams, take it even when public
+    let request_user = |op: &str| {
+        if spec.is_public(op) {
+            quote! { req: HttpRequest, user: OptionalUserContext }
+        } else {
+            user_param(op)
         }
-    } else {
-        quote! {}
     };
-
-    // Generate role check for update operations
-    let update_check = if let Some(role) = &update_role {
-        quote! {
-            // Admin role always has access
-            if !user.roles.contains(&String::from("admin")) && !user.roles.contains(&String::from(#role)) {
-                return HttpResponse::Forbidden().body("Insufficient privileges");
-            }
+    // Cached resources and those with file fields need the request to find the `ResponseCache` or `FileStorage`
+    let has_files = spec.file_fields().next().is_some();
+    let handler_user = |op: &str| {
+        if spec.cache_ttl.is_some() || has_files {
+            request_user(op)
+        } else {
+            user_param(op)
         }
-    } else {
-        quote! {}
     };
-
-    // Generate role check for delete operations
-    let delete_check = if let Some(role) = &delete_role {
-        quote! {
-            // Admin role always has access
-            if !user.roles.contains(&String::from("admin")) && !user.roles.contains(&String::from(#role)) {
-                return HttpResponse::Forbidden().body("Insufficient privileges");
+    let list_user = handler_user("list");
+    let events_user = request_user("list");
+    let get_user = handler_user("get");
+    let update_user = handler_user("update");
+    let patch_user = handler_user("patch");
+    let delete_user = handler_user("delete");
+
+    // Cache lookups before the query and stores after it, for `#[rest_api(cache(ttl = ...))]`
+    let cache_lookup = |op: &str, key: proc_macro2::TokenStream| match spec.cache_ttl {
+        Some(_) => {
+            let caller = if spec.is_public(op) {
+                quote! { user.0.as_ref() }
+            } else {
+                quote! { Some(&user) }
+            };
+            quote! {
+                let response_cache = ResponseCache::from_request(&req);
+                let cache_key = cache::key(#key, codec, #caller, req.query_string());
+                if let Some(body) = response_cache.get(#table_name, &cache_key).await {
+                    return cache::hit(codec, body);
+                }
             }
         }
-    } else {
-        quote! {}
+        None => quote! {},
+    };
+    let cache_respond = |value: proc_macro2::TokenStream| match spec.cache_ttl {
+        Some(ttl) => quote! {
+            response_cache
+                .store(#table_name, &cache_key, std::time::Duration::from_secs(#ttl), codec, &#value)
+                .await
+        },
+        None => quote! { codec.ok(&#value) },
+    };
+    let cache_invalidate = match spec.cache_ttl {
+        Some(_) => quote! { ResponseCache::from_request(&req).invalidate(#table_name).await; },
+        None => quote! {},
     };
 
-    if let syn::Data::Struct(data_struct) = &input.data {
-        if let syn::Fields::Named(fields_named) = &data_struct.fields {
-            for field in &fields_named.named {
-                let name = field.ident.as_ref().unwrap().to_string();
-                let ident = field.ident.as_ref().unwrap();
-
-                // Check for relation attribute
-                for attr in &field.attrs {
-                    if attr.path().is_ident("relation") {
-                        let mut foreign_key = None;
-                        let mut references = None;
-                        let mut nested_route = false;
-
-                        let _ = attr.parse_nested_meta(|meta| {
-                            let path = meta.path.get_ident().unwrap().to_string();
-
-                            if path == "foreign_key" {
-                                foreign_key = Some(meta.value()?.parse::<syn::LitStr>()?.value());
-                            } else if path == "references" {
-                                references = Some(meta.value()?.parse::<syn::LitStr>()?.value());
-                            } else if path == "nested_route" {
-                                let value = meta.value()?.parse::<syn::LitStr>()?.value();
-                                nested_route = value == "true";
-                            }
-
-                            Ok(())
-                        });
-
-                        if let (Some(_), Some(refs)) = (foreign_key, references) {
-                            let parts: Vec<&str> = refs.split('.').collect();
-                            if parts.len() == 2 {
-                                let parent_table = parts[0];
-                                relation_field = name.clone();
-                                relation_parent_table = parent_table.to_string();
-                            }
-                        }
-                    }
-                }
+    let mut schema_columns = vec![];
+    let mut insert_fields = vec![];
+    let mut bind_fields_insert = vec![];
+    let mut bind_fields_update = vec![];
+    let mut update_clauses = vec![];
 
-                if name == "created_at" || name == "updated_at" {
-                    field_defs.push(format!("{} TEXT DEFAULT CURRENT_TIMESTAMP", name));
-                    skip_insert_fields.insert(name.clone());
-                    if name == "updated_at" {
-                        update_clauses.push("updated_at = CURRENT_TIMESTAMP".to_string());
-                    }
-                    continue;
-                }
+    for field in &spec.fields {
+        let name = &field.name;
+        let ident = &field.ident;
 
-                let ty_str = quote!(#field.ty).to_string();
-                let sql_type = if ty_str.contains("i32") || ty_str.contains("i64") {
-                    "INTEGER"
-                } else if ty_str.contains("f32") || ty_str.contains("f64") {
-                    "REAL"
-                } else {
-                    "TEXT"
-                };
+        let column_type = match field.sql_type {
+            "INTEGER" => quote! { ColumnType::Integer },
+            "REAL" => quote! { ColumnType::Real },
+            "BOOLEAN" => quote! { ColumnType::Boolean },
+            _ => quote! { ColumnType::Text },
+        };
+        let primary_key = name == id_field;
+        let nullable = field.nullable;
+        let unique = field.unique;
+        let default_now = field.is_timestamp();
+        let references = match &field.relation {
+            Some(relation) => {
+                let table = &relation.parent_table;
+                let column = &relation.parent_column;
+                quote! { Some(ForeignKey { table: #table, column: #column }) }
+            }
+            None => quote! { None },
+        };
+        schema_columns.push(quote! {
+            ColumnDef {
+                name: #name,
+                column_type: #column_type,
+                primary_key: #primary_key,
+                nullable: #nullable,
+                unique: #unique,
+                default_now: #default_now,
+                references: #references,
+            }
+        });
 
-                let is_id = name == id_field;
-                if is_id {
-                    field_defs.push(format!("{} INTEGER PRIMARY KEY AUTOINCREMENT", name));
-                    skip_insert_fields.insert(name.clone());
-                } else {
-                    field_defs.push(format!("{} {}", name, sql_type));
-                }
+        if field.is_timestamp() {
+            if name == "updated_at" {
+                update_clauses.push("updated_at = CURRENT_TIMESTAMP".to_string());
+            }
+            continue;
+        }
 
-                field_names.push(name.clone());
-                field_idents.push(ident.clone());
+        if name == id_field {
+            continue;
+        }
 
-                if !skip_insert_fields.contains(&name) {
-                    bind_fields_insert.push(quote! { q = q.bind(&item.#ident); });
-                }
-                if !is_id && name != "created_at" && name != "updated_at" {
-                    bind_fields_update.push(quote! { q = q.bind(&item.#ident); });
-                    let clause = if db_type == "postgres" {
-                        format!("{} = ${}", name, update_clauses.len() + 1)
-                    } else {
-                        format!("{} = ?", name)
-                    };
-                    update_clauses.push(clause);
-                }
-            }
+        insert_fields.push(name.clone());
+        bind_fields_insert.push(quote! { q = q.bind(&item.#ident); });
+        bind_fields_update.push(quote! { q = q.bind(&item.#ident); });
+        // A file field keeps its file unless a new one was uploaded
+        let value = placeholder(bind_fields_update.len());
+        if field.file.is_some() {
+            update_clauses.push(format!("{} = COALESCE({}, {})", name, value, name));
+        } else {
+            update_clauses.push(format!("{} = {}", name, value));
         }
     }
-    // let insert_fields: Vec<String> = field_names.iter().cloned().filter(|f| !skip_insert_fields.contains(f)).collect();
 
-    let insert_fields: Vec<String> = field_names
-        .iter()
-        .filter(|&f| !skip_insert_fields.contains(f))
-        .cloned()
-        .collect();
-    let insert_placeholders = insert_fields
-        .iter()
-        .map(|_| "?")
+    let insert_placeholders = (1..=insert_fields.len())
+        .map(placeholder)
         .collect::<Vec<_>>()
         .join(", ");
     let update_sql = update_clauses.join(", ");
     let insert_fields_csv = insert_fields.join(", ");
-    let field_defs_sql = field_defs.join(", ");
-
-    // Generate partial_struct_name and partial_fields for PATCH
-    let (partial_struct_name, partial_fields) = if let syn::Data::Struct(data_struct) = &input.data {
-        if let syn::Fields::Named(fields_named) = &data_struct.fields {
-            let fields: Vec<_> = fields_named.named
-                .iter()
-                .filter(|f| f.ident.as_ref().unwrap() != "id")  // Skip primary key field
-                .map(|f| {
-                    let ident = &f.ident;
-                    let ty = &f.ty;
-                    quote! { #ident: Option<#ty> }
-                })
-                .collect();
-            let name = format_ident!("Partial{}", struct_name);
-            (name, fields)
-        } else {
-            (format_ident!("Partial{}", struct_name), vec![])
+    let id_placeholder = placeholder(bind_fields_update.len() + 1);
+
+    // Upsert statements keyed on each unique column, used by `POST /{table}?on_conflict=<field>`
+    let base_insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table_name, insert_fields_csv, insert_placeholders
+    );
+    // An upsert can overwrite an existing row, so it is only offered when updates are enabled
+    let upsert_fields: Vec<&String> = if spec.has_operation("update") {
+        spec.fields.iter().filter(|f| f.unique).map(|f| &f.name).collect()
+    } else {
+        vec![]
+    };
+    // ...and it needs the `update` checks on top of the `create` ones
+    let upsert_check = if upsert_fields.is_empty() || spec.is_public("update") {
+        quote! {}
+    } else if spec.is_public("create") {
+        quote! {
+            if params.on_conflict.is_some() {
+                let Some(user) = user.0.as_ref() else {
+                    return HttpResponse::Unauthorized().body("Missing token");
+                };
+                #update_check
+            }
         }
     } else {
-        (format_ident!("Partial{}", struct_name), vec![])
+        quote! {
+            if params.on_conflict.is_some() {
+                #update_check
+            }
+        }
+    };
+    // A public `create` still reads the request when upserts need the `update` checks
+    let create_user = if spec.is_public("create") && !upsert_fields.is_empty() && !spec.is_public("update") {
+        request_user("create")
+    } else {
+        handler_user("create")
     };
+    let upsert_arms: Vec<_> = upsert_fields
+        .iter()
+        .map(|conflict_field| {
+            let upsert_sql = upsert_sql(&spec, &base_insert_sql, &insert_fields, conflict_field);
+            quote! { Some(#conflict_field) => #upsert_sql, }
+        })
+        .collect();
+
+    // Generate partial_struct_name and partial_fields for PATCH
+    let partial_struct_name = format_ident!("Partial{}", struct_name);
+    let partial_fields: Vec<_> = spec
+        .fields
+        .iter()
+        .filter(|f| f.name != id_field && f.file.is_none()) // Skip primary key and file fields
+        .map(|f| {
+            let ident = &f.ident;
+            let ty = &f.ty;
+            quote! {
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                pub #ident: Option<#ty>
+            }
+        })
+        .collect();
 
     // Example:
     //
     // pub struct PartialPost {
-    //     title: Option<String>,
-    //     content: Option<String>,
-    //     created_at: Option<String>,
-    //     updated_at: Option<String>,
+    //     pub title: Option<String>,
+    //     pub content: Option<String>,
+    //     pub created_at: Option<String>,
+    //     pub updated_at: Option<String>,
     // }
     let expanded_partial = quote! {
-        #[derive(serde::Deserialize)]
+        #[derive(Default, serde::Deserialize, serde::Serialize)]
         pub struct #partial_struct_name {
             #(#partial_fields),*
         }
@@ -251,13 +320,13 @@ pub fn rest_api_macro(input: TokenStream) -> TokenStream {
         let mut set_tokens = Vec::new();
         let mut bind_tokens = Vec::new();
 
-        for ident in &field_idents {
-            let name = ident.to_string();
-            if name == "id" || name == "created_at" || name == "updated_at" {
+        for field in &spec.fields {
+            if field.name == id_field || field.is_timestamp() || field.file.is_some() {
                 continue;
             }
 
-            let name_lit = syn::LitStr::new(&name, ident.span());
+            let ident = &field.ident;
+            let name_lit = syn::LitStr::new(&field.name, ident.span());
 
             // Example generated code:
             //
@@ -288,7 +357,7 @@ pub fn rest_api_macro(input: TokenStream) -> TokenStream {
             });
         }
 
-        let updated_at_code = if field_names.contains(&"updated_at".to_string()) {
+        let updated_at_code = if spec.has_field("updated_at") {
             quote! {
                 if !first {
                     sql.push_str(", updated_at = CURRENT_TIMESTAMP");
@@ -300,16 +369,17 @@ pub fn rest_api_macro(input: TokenStream) -> TokenStream {
         } else {
             quote! {}
         };
+        let where_clause = format!(" WHERE {} = ?", id_field);
 
         quote! {
             impl #partial_struct_name {
                 pub async fn patch(
                     path: web::Path<i64>,
-                    json: web::Json<Self>,
-                    user: UserContext,
-                    db: web::Data<AnyPool>,
+                    json: Payload<Self>,
+                    #patch_user,
+                    db: web::Data<#pool_type>,
                 ) -> impl Responder {
-                    #update_check
+                    #patch_check
 
                     let id = path.into_inner();
                     let partial = json.into_inner();    // Instance of PartialStruct
@@ -328,21 +398,24 @@ pub fn rest_api_macro(input: TokenStream) -> TokenStream {
                         return HttpResponse::Ok().finish();
                     }
 
-                    sql.push_str(" WHERE id = ?");
+                    sql.push_str(#where_clause);
+                    #begin_tx
                     let mut query = sqlx::query(&sql);
 
                     // Bind values for fields that are Some
                     #(#bind_tokens)*
                     query = query.bind(id);
 
-                    match query.execute(db.get_ref()).await {
-                        Ok(res) => {
-                            if res.rows_affected() > 0 {
-                                HttpResponse::Ok().finish()
-                            } else {
-                                HttpResponse::NotFound().finish()
+                    match query.execute(&mut *tx).await {
+                        Ok(res) if res.rows_affected() > 0 => {
+                            if let Err(e) = tx.commit().await {
+                                return HttpResponse::InternalServerError().body(e.to_string());
                             }
+                            #cache_invalidate
+                            events::publish_with(#table_name, "patch", Some(id), &partial);
+                            HttpResponse::Ok().finish()
                         }
+                        Ok(_) => HttpResponse::NotFound().finish(),
                         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
                     }
                 }
@@ -350,154 +423,1016 @@ pub fn rest_api_macro(input: TokenStream) -> TokenStream {
         }
     };
 
-    // Conditional get_by_parent_id method
-    let get_by_parent_id_impl = if !relation_field.is_empty() {
-        let field_lit = syn::LitStr::new(&relation_field, struct_name.span());
-        
-        quote! {
-            async fn get_by_parent_id(
-                path: web::Path<i64>,
-                user: UserContext,
-                db: web::Data<AnyPool>,
-            ) -> impl Responder {
-                #read_check
+    // Nested routes such as `/post/{parent_id}/comment` for every relation without `nested_route = "false"`
+    let mut nested_route_impls = vec![];
+    let mut nested_route_registrations = vec![];
+    if spec.has_operation("list") {
+        for field in &spec.fields {
+            let Some(relation) = field.relation.as_ref().filter(|r| r.nested_route) else {
+                continue;
+            };
+            let handler = format_ident!("get_by_{}", field.ident);
+            let sql = format!(
+                "SELECT * FROM {} WHERE {} = {}",
+                table_name,
+                field.name,
+                placeholder(1)
+            );
+            let parent_table = &relation.parent_table;
 
-                let parent_id = path.into_inner();
-                let sql = format!("SELECT * FROM {} WHERE {} = ?", #table_name, #field_lit);
-                match sqlx::query_as::<_, Self>(&sql)
-                    .bind(parent_id)
-                    .fetch_all(db.get_ref())
-                    .await
-                {
-                    Ok(items) => HttpResponse::Ok().json(items),
-                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            nested_route_impls.push(quote! {
+                async fn #handler(
+                    path: web::Path<i64>,
+                    #list_user,
+                    codec: Codec,
+                    db: web::Data<#pool_type>,
+                ) -> impl Responder {
+                    #list_check
+
+                    let parent_id = path.into_inner();
+                    match sqlx::query_as::<_, Self>(#sql)
+                        .bind(parent_id)
+                        .fetch_all(db.get_ref())
+                        .await
+                    {
+                        Ok(items) => codec.ok(&items),
+                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                    }
                 }
+            });
+            nested_route_registrations.push(quote! {
+                cfg.service(
+                    web::resource(format!("/{}/{{parent_id}}/{}", #parent_table, #table_name))
+                        .route(web::get().to(Self::#handler))
+                );
+            });
+        }
+    }
+
+    // Route registration for the enabled operations only
+    let mut collection_routes = vec![];
+    let mut item_routes = vec![];
+    if spec.has_operation("list") {
+        collection_routes.push(quote! { .route(web::get().to(Self::get_all)) });
+        collection_routes.push(quote! { .route(web::head().to(Self::count_head)) });
+    }
+    if spec.has_operation("create") {
+        collection_routes.push(quote! { .route(web::post().to(Self::create)) });
+    }
+    if spec.has_operation("get") {
+        item_routes.push(quote! { .route(web::get().to(Self::get_one)) });
+    }
+    if spec.has_operation("update") {
+        item_routes.push(quote! { .route(web::put().to(Self::update)) });
+    }
+    if spec.has_operation("patch") {
+        item_routes.push(quote! { .route(web::patch().to(#partial_struct_name::patch)) });
+    }
+    if spec.has_operation("delete") {
+        item_routes.push(quote! { .route(web::delete().to(Self::delete)) });
+    }
+
+    let collection_registration = if collection_routes.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            cfg.service(
+                web::resource(format!("/{}", #table_name))
+                    #(#collection_routes)*
+            );
+        }
+    };
+    // `/{table}/_schema` goes before `/{table}/{id}`, which would otherwise match it
+    let has_payloads = ["create", "update", "patch"].iter().any(|op| spec.has_operation(op));
+    // It describes the payloads, so it is open to whoever passes the checks of at least one
+    // payload operation, and public when one of them is
+    let payload_ops: Vec<&str> = ["create", "update", "patch"].into_iter().filter(|op| spec.has_operation(op)).collect();
+    let payload_schemas = quote! {
+        HttpResponse::Ok().json(very_simple_rest::core::json_schema::payload_schemas(Self::metadata()))
+    };
+    let json_schema_impl = if !has_payloads {
+        quote! {}
+    } else if payload_ops.iter().any(|op| spec.is_public(op)) {
+        quote! {
+            /// JSON Schema for the create, update and patch payloads
+            async fn json_schema() -> impl Responder {
+                #payload_schemas
             }
         }
+    } else {
+        let checks = payload_ops.iter().map(|op| role_check(op));
+        quote! {
+            /// JSON Schema for the create, update and patch payloads
+            async fn json_schema(req: HttpRequest, user: UserContext) -> impl Responder {
+                #(
+                    let response = async { #checks HttpResponse::Ok().finish() }.await;
+                    if response.status().is_success() {
+                        return #payload_schemas;
+                    }
+                )*
+                response
+            }
+        }
+    };
+    let schema_registration = if has_payloads {
+        quote! {
+            cfg.route(&format!("/{}/_schema", #table_name), web::get().to(Self::json_schema));
+        }
     } else {
         quote! {}
     };
-
-    // Conditional nested route registration
-    let nested_route_registration = if !relation_field.is_empty() {
+    // `/{table}/events` likewise, for list readers only
+    let events_registration = if spec.has_operation("list") {
+        quote! {
+            cfg.route(&format!("/{}/events", #table_name), web::get().to(Self::events));
+            cfg.route(&format!("/{}/events/ws", #table_name), web::get().to(Self::events_ws));
+        }
+    } else {
+        quote! {}
+    };
+    // And the bulk transfer, aggregate and count routes
+    let mut transfer_registration = vec![];
+    if spec.has_operation("list") {
+        transfer_registration.push(quote! {
+            cfg.route(&format!("/{}/export", #table_name), web::get().to(Self::export));
+        });
+    }
+    if spec.has_operation("list") {
+        transfer_registration.push(quote! {
+            cfg.route(&format!("/{}/aggregate", #table_name), web::get().to(Self::aggregate));
+            cfg.route(&format!("/{}/count", #table_name), web::get().to(Self::count));
+        });
+    }
+    if spec.has_operation("create") {
+        transfer_registration.push(quote! {
+            cfg.service(
+                web::resource(format!("/{}/import", #table_name))
+                    .app_data(web::PayloadConfig::new(bulk::MAX_IMPORT_SIZE))
+                    .route(web::post().to(Self::import)),
+            );
+        });
+    }
+    let item_registration = if item_routes.is_empty() {
+        quote! {}
+    } else {
         quote! {
             cfg.service(
-                web::resource(format!("/{}/{{parent_id}}/{}", #relation_parent_table, #table_name))
-                    .route(web::get().to(Self::get_by_parent_id))
+                web::resource(format!("/{}/{{id}}", #table_name))
+                    #(#item_routes)*
             );
         }
+    };
+
+    let select_one_sql = format!("SELECT * FROM {} WHERE {} = {}", table_name, id_field, placeholder(1));
+    let update_one_sql = format!("UPDATE {} SET {} WHERE {} = {}", table_name, update_sql, id_field, id_placeholder);
+    let delete_one_sql = format!("DELETE FROM {} WHERE {} = {}", table_name, id_field, placeholder(1));
+
+    let list_lookup = cache_lookup("list", quote! { "list" });
+    let list_respond = cache_respond(quote! { data });
+    let get_lookup = cache_lookup("get", quote! { &format!("get:{}", id) });
+    let get_respond = cache_respond(quote! { item });
+
+    // The SQL dialect for queries built at runtime; `AnyPool` asks the connection
+    let backend = match db_type {
+        "sqlite" => quote! { Backend::Sqlite },
+        "mysql" => quote! { Backend::Mysql },
+        "postgres" => quote! { Backend::Postgres },
+        _ => quote! {
+            match Backend::of_pool(db.get_ref()).await {
+                Ok(backend) => backend,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        },
+    };
+    // `?column=value` filters shared by the list-like handlers, less the handler's own parameters
+    let parse_filters = |reserved: &[&str]| {
+        quote! {
+            let query = match ListQuery::parse(Self::metadata(), &filters, &[#(#reserved),*]) {
+                Ok(query) => query,
+                Err(e) => return HttpResponse::BadRequest().body(e),
+            };
+        }
+    };
+    let filters_param = quote! { filters: web::Query<std::collections::BTreeMap<String, String>> };
+
+    // CSV and NDJSON export (with `list`) and import (with `create`)
+    let export_filters = parse_filters(&["format"]);
+    let export_impl = if spec.has_operation("list") {
+        quote! {
+            /// Every matching row as CSV or NDJSON, streamed a page at a time
+            async fn export(
+                params: web::Query<bulk::TransferParams>,
+                #filters_param,
+                #list_user,
+                db: web::Data<#pool_type>,
+            ) -> impl Responder {
+                #list_check
+                #export_filters
+
+                let format = match bulk::Format::for_export(&params) {
+                    Ok(format) => format,
+                    Err(e) => return HttpResponse::BadRequest().body(e),
+                };
+                let sql = query.page_sql(#backend);
+                bulk::export_response(Self::metadata(), format, move |after| {
+                    let db = db.clone();
+                    let arguments = query.page_arguments::<#database>(after, bulk::PAGE_SIZE);
+                    let sql = sql.clone();
+                    async move {
+                        let arguments = arguments.map_err(sqlx::Error::Encode)?;
+                        sqlx::query_as_with::<_, Self, _>(&sql, arguments).fetch_all(db.get_ref()).await
+                    }
+                })
+            }
+        }
     } else {
         quote! {}
     };
+    // Counts, sums and averages, with the `list` checks
+    let aggregate_filters = parse_filters(&["count", "sum", "avg", "group_by"]);
+    let aggregate_impl = if spec.has_operation("list") {
+        quote! {
+            /// `COUNT`, `SUM` and `AVG` over the table, optionally grouped
+            async fn aggregate(
+                params: web::Query<aggregate::AggregateParams>,
+                #filters_param,
+                #list_user,
+                codec: Codec,
+                db: web::Data<#pool_type>,
+            ) -> impl Responder {
+                #list_check
+                #aggregate_filters
 
-    // FINAL EXPANDED OUTPUT
-    let expanded = quote! {
-        #expanded_partial
+                let aggregate = match aggregate::Aggregate::parse(Self::metadata(), &params) {
+                    Ok(aggregate) => aggregate,
+                    Err(e) => return HttpResponse::BadRequest().body(e),
+                };
+                let arguments = match query.arguments::<#database>() {
+                    Ok(arguments) => arguments,
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                };
+                let sql = aggregate.sql(#backend, &query);
+                let rows = match sqlx::query_with(&sql, arguments).fetch_all(db.get_ref()).await {
+                    Ok(rows) => rows,
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                };
+                match aggregate.rows(&rows) {
+                    Ok(rows) => codec.ok(&rows),
+                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let import_user = request_user("create");
+    let insert_columns = &insert_fields;
+    let numbered_placeholders = db_type == "postgres";
+    let import_impl = if spec.has_operation("create") {
+        quote! {
+            /// Validate every line, then insert them in batches within one transaction
+            async fn import(
+                body: web::Bytes,
+                params: web::Query<bulk::TransferParams>,
+                #import_user,
+                db: web::Data<#pool_type>,
+            ) -> impl Responder {
+                #create_check
 
-        mod #module_ident {
-            use super::*;
-            use actix_web::{web, HttpResponse, Responder};
-            use sqlx::{SqlitePool, MySqlPool, PgPool, AnyPool};
-            // Access UserContext through the core module which is re-exported in rest_api
-            use very_simple_rest::core::auth::UserContext;
+                let format = match bulk::Format::for_import(&req, &params) {
+                    Ok(format) => format,
+                    Err(e) => return HttpResponse::BadRequest().body(e),
+                };
+                let rows = match bulk::parse::<Self>(Self::metadata(), format, &body) {
+                    Ok(rows) => rows,
+                    Err(report) => return report.response(),
+                };
 
-            impl #struct_name {
-                pub fn configure(cfg: &mut web::ServiceConfig, db: #pool_type) {
-                    let db = web::Data::new(db);
-                    cfg.app_data(db.clone());
-                    actix_web::rt::spawn(Self::create_table_if_not_exists(db.clone()));
-
-                    cfg.service(
-                        web::resource(format!("/{}", #table_name))
-                            .route(web::get().to(Self::get_all))
-                            .route(web::post().to(Self::create))
-                    )
-                    .service(
-                        web::resource(format!("/{}/{{id}}", #table_name))
-                            .route(web::get().to(Self::get_one))
-                            .route(web::put().to(Self::update))
-                            .route(web::patch().to(#partial_struct_name::patch))
-                            .route(web::delete().to(Self::delete))
-                    );
-
-                    #nested_route_registration
+                const COLUMNS: &[&str] = &[#(#insert_columns),*];
+                #begin_tx
+                for batch in rows.chunks(bulk::batch_size(COLUMNS.len())) {
+                    let sql = bulk::insert_sql(#table_name, COLUMNS, batch.len(), #numbered_placeholders);
+                    let mut q = sqlx::query(&sql);
+                    for (_, item) in batch {
+                        #(#bind_fields_insert)*
+                    }
+                    if let Err(e) = q.execute(&mut *tx).await {
+                        return bulk::ImportReport::batch_failed(batch, e).response();
+                    }
+                }
+                if let Err(e) = tx.commit().await {
+                    return HttpResponse::InternalServerError().body(e.to_string());
                 }
 
-                async fn create_table_if_not_exists(db: web::Data<#pool_type>) {
-                    let sql = format!("CREATE TABLE IF NOT EXISTS {} ({})", #table_name, #field_defs_sql);
-                    let _ = sqlx::query(&sql).execute(db.get_ref()).await;
+                #cache_invalidate
+                // Multi-row inserts don't report their ids
+                for (_, item) in &rows {
+                    events::publish_with(#table_name, "create", None, item);
                 }
+                bulk::ImportReport { inserted: rows.len(), errors: vec![] }.response()
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-                async fn get_all(user: UserContext, db: web::Data<#pool_type>) -> impl Responder {
-                    #read_check
+    let list_filters = parse_filters(&[]);
+    let get_all_impl = if spec.has_operation("list") {
+        quote! {
+            /// The rows matching the query string's filters, in primary key order
+            async fn get_all(#filters_param, #list_user, codec: Codec, db: web::Data<#pool_type>) -> impl Responder {
+                #list_check
+                #list_filters
+                #list_lookup
+
+                let arguments = match query.arguments::<#database>() {
+                    Ok(arguments) => arguments,
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                };
+                match sqlx::query_as_with::<_, Self, _>(&query.select_sql(#backend), arguments).fetch_all(db.get_ref()).await {
+                    Ok(data) => #list_respond,
+                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            }
 
-                    let sql = format!("SELECT * FROM {}", #table_name);
-                    match sqlx::query_as::<_, Self>(&sql).fetch_all(db.get_ref()).await {
-                        Ok(data) => HttpResponse::Ok().json(data),
-                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
-                    }
+            /// The number of rows `get_all` returns for the same query
+            async fn count_rows(query: &ListQuery, backend: Backend, db: &#pool_type) -> Result<i64, sqlx::Error> {
+                let arguments = query.arguments::<#database>().map_err(sqlx::Error::Encode)?;
+                sqlx::query_scalar_with::<_, i64, _>(&query.count_sql(backend), arguments).fetch_one(db).await
+            }
+
+            /// `{ "count": n }` for `GET /{table}/count`
+            async fn count(#filters_param, #list_user, codec: Codec, db: web::Data<#pool_type>) -> impl Responder {
+                #list_check
+                #list_filters
+
+                match Self::count_rows(&query, #backend, db.get_ref()).await {
+                    Ok(count) => codec.ok(&aggregate::Count { count }),
+                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
                 }
+            }
+
+            /// The count in `X-Total-Count` for `HEAD /{table}`
+            async fn count_head(#filters_param, #list_user, db: web::Data<#pool_type>) -> impl Responder {
+                #list_check
+                #list_filters
+
+                match Self::count_rows(&query, #backend, db.get_ref()).await {
+                    Ok(count) => HttpResponse::Ok()
+                        .insert_header((aggregate::TOTAL_COUNT_HEADER, count.to_string()))
+                        .finish(),
+                    Err(_) => HttpResponse::InternalServerError().finish(),
+                }
+            }
+
+            #(#nested_route_impls)*
+
+            /// Server-Sent Events for changes to this resource
+            async fn events(#events_user) -> impl Responder {
+                #list_check
+
+                events::sse_response(&req, #table_name)
+            }
+
+            /// The same events as JSON messages over a WebSocket
+            async fn events_ws(body: web::Payload, #events_user) -> impl Responder {
+                #list_check
 
-                async fn get_one(path: web::Path<i64>, user: UserContext, db: web::Data<#pool_type>) -> impl Responder {
-                    #read_check
+                match events::websocket_response(&req, body, #table_name) {
+                    Ok(response) => response,
+                    Err(e) => e.error_response(),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-                    let sql = format!("SELECT * FROM {} WHERE {} = ?", #table_name, #id_field);
-                    match sqlx::query_as::<_, Self>(&sql)
+    // `GET /{table}/{id}/{field}` for each file field, with the `get` checks
+    let download_user = request_user("get");
+    let mut download_impls = vec![];
+    let mut download_registrations = vec![];
+    if spec.has_operation("get") {
+        for field in spec.file_fields() {
+            let ident = &field.ident;
+            let name = &field.name;
+            let handler = format_ident!("download_{}", ident);
+            download_impls.push(quote! {
+                /// The uploaded file, as an attachment
+                async fn #handler(path: web::Path<i64>, #download_user, db: web::Data<#pool_type>) -> impl Responder {
+                    #get_check
+
+                    match sqlx::query_as::<_, Self>(#select_one_sql)
                         .bind(path.into_inner())
                         .fetch_optional(db.get_ref())
                         .await
                     {
-                        Ok(Some(item)) => HttpResponse::Ok().json(item),
+                        Ok(Some(item)) => FileStorage::from_request(&req).download(item.#ident.as_ref()).await,
                         Ok(None) => HttpResponse::NotFound().finish(),
                         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
                     }
                 }
+            });
+            download_registrations.push(quote! {
+                cfg.route(&format!("/{}/{{id}}/{}", #table_name, #name), web::get().to(Self::#handler));
+            });
+        }
+    }
 
-                async fn create(item: web::Json<Self>, user: UserContext, db: web::Data<#pool_type>) -> impl Responder {
-                    #update_check
+    let get_one_impl = if spec.has_operation("get") {
+        quote! {
+            async fn get_one(path: web::Path<i64>, #get_user, codec: Codec, db: web::Data<#pool_type>) -> impl Responder {
+                #get_check
 
-                    let sql = format!("INSERT INTO {} ({}) VALUES ({})", #table_name, #insert_fields_csv, #insert_placeholders);
-                    let mut q = sqlx::query(&sql);
-                    #(#bind_fields_insert)*
-                    match q.execute(db.get_ref()).await {
-                        Ok(_) => HttpResponse::Created().finish(),
-                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                let id = path.into_inner();
+                #get_lookup
+
+                match sqlx::query_as::<_, Self>(#select_one_sql)
+                    .bind(id)
+                    .fetch_optional(db.get_ref())
+                    .await
+                {
+                    Ok(Some(item)) => #get_respond,
+                    Ok(None) => HttpResponse::NotFound().finish(),
+                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Key of the inserted row for the `create` event; Postgres only reports it through RETURNING
+    let inserted_id = match db_type {
+        "sqlite" => quote! { Some(res.last_insert_rowid()) },
+        "mysql" => quote! { Some(res.last_insert_id() as i64) },
+        "postgres" => quote! { { let _ = res; None } },
+        _ => quote! { res.last_insert_id() },
+    };
+
+    // File fields: payloads may be multipart, uploads are stored once the checks pass, and
+    // files that are replaced or whose row is deleted are removed from the `FileStorage`
+    let file_idents: Vec<_> = spec.file_fields().map(|f| &f.ident).collect();
+    let file_names: Vec<_> = spec.file_fields().map(|f| &f.name).collect();
+    let (payload_param, payload_intake, discard_uploads) = if has_files {
+        (
+            quote! { upload: Upload<Self> },
+            quote! {
+                let (mut item, files) = upload.into_parts();
+                let storage = FileStorage::from_request(&req);
+                // File fields are only ever set by an upload, never from the payload
+                #(item.#file_idents = None;)*
+                match storage.store(#table_name, files).await {
+                    Ok(stored) => {
+                        for (field, file) in stored {
+                            match field {
+                                #(#file_names => item.#file_idents = Some(file),)*
+                                _ => {}
+                            }
+                        }
                     }
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            },
+            quote! { storage.remove([#(item.#file_idents.as_ref()),*].into_iter().flatten()).await; },
+        )
+    } else {
+        (quote! { item: Payload<Self> }, quote! { let item = item.into_inner(); }, quote! {})
+    };
+    // An upsert may replace the files of the row it overwrites, so that row is read first, like update does
+    let fetch_overwritten = if has_files {
+        let arms = upsert_fields.iter().map(|conflict_field| {
+            let field = spec.fields.iter().find(|f| f.name == **conflict_field).unwrap();
+            let ident = &field.ident;
+            let sql = format!("SELECT * FROM {} WHERE {} = {}", table_name, conflict_field, placeholder(1));
+            quote! {
+                Some(#conflict_field) => sqlx::query_as::<_, Self>(#sql).bind(&item.#ident).fetch_optional(&mut *tx).await,
+            }
+        });
+        quote! {
+            let previous: Result<Option<Self>, sqlx::Error> = match params.on_conflict.as_deref() {
+                #(#arms)*
+                _ => Ok(None),
+            };
+            let previous = match previous {
+                Ok(previous) => previous,
+                Err(e) => {
+                    #discard_uploads
+                    return HttpResponse::InternalServerError().body(e.to_string());
                 }
+            };
+        }
+    } else {
+        quote! {}
+    };
+    let (fetch_previous, release_replaced, release_deleted) = if has_files {
+        (
+            quote! {
+                let previous = match sqlx::query_as::<_, Self>(#select_one_sql)
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                {
+                    Ok(previous) => previous,
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                };
+            },
+            quote! {
+                if let Some(previous) = &previous {
+                    let replaced = [#(previous.#file_idents.as_ref().filter(|_| item.#file_idents.is_some())),*];
+                    storage.remove(replaced.into_iter().flatten()).await;
+                }
+            },
+            quote! {
+                if let Some(previous) = &previous {
+                    FileStorage::from_request(&req)
+                        .remove([#(previous.#file_idents.as_ref()),*].into_iter().flatten())
+                        .await;
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {})
+    };
 
-                async fn update(path: web::Path<i64>, item: web::Json<Self>, user: UserContext, db: web::Data<#pool_type>) -> impl Responder {
-                    #update_check
+    // Writes run in one `Tx`; events, cache eviction and file removal wait for the commit
+    let commit_or_discard = quote! {
+        if let Err(e) = tx.commit().await {
+            #discard_uploads
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
 
-                    let sql = format!("UPDATE {} SET {} WHERE {} = ?", #table_name, #update_sql, #id_field);
-                    let mut q = sqlx::query(&sql);
-                    #(#bind_fields_update)*
-                    q = q.bind(path.into_inner());
-                    match q.execute(db.get_ref()).await {
-                        Ok(_) => HttpResponse::Ok().finish(),
-                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    let create_impl = if spec.has_operation("create") {
+        quote! {
+            async fn create(
+                #payload_param,
+                params: web::Query<CreateParams>,
+                #create_user,
+                db: web::Data<#pool_type>,
+            ) -> impl Responder {
+                #create_check
+                #upsert_check
+                #payload_intake
+
+                // `?on_conflict=<field>` turns the insert into an upsert keyed on a unique column
+                let sql = match params.on_conflict.as_deref() {
+                    None => #base_insert_sql,
+                    #(#upsert_arms)*
+                    Some(field) => {
+                        return HttpResponse::BadRequest()
+                            .body(format!("Cannot upsert on '{}'", field));
+                    }
+                };
+                let mut tx = match Tx::begin(db.get_ref()).await {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        #discard_uploads
+                        return HttpResponse::InternalServerError().body(e.to_string());
+                    }
+                };
+                #fetch_overwritten
+                let mut q = sqlx::query(sql);
+                #(#bind_fields_insert)*
+                let res = match q.execute(&mut *tx).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        #discard_uploads
+                        return HttpResponse::InternalServerError().body(e.to_string());
                     }
+                };
+                #commit_or_discard
+                #release_replaced
+
+                #cache_invalidate
+                if params.on_conflict.is_some() {
+                    events::publish_with(#table_name, "upsert", None, &item);
+                    HttpResponse::Ok().finish()
+                } else {
+                    events::publish_with(#table_name, "create", #inserted_id, &item);
+                    HttpResponse::Created().finish()
                 }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-                async fn delete(path: web::Path<i64>, user: UserContext, db: web::Data<#pool_type>) -> impl Responder {
-                    #delete_check
+    let update_impl = if spec.has_operation("update") {
+        quote! {
+            async fn update(
+                path: web::Path<i64>,
+                #payload_param,
+                #update_user,
+                db: web::Data<#pool_type>,
+            ) -> impl Responder {
+                #update_check
+
+                let id = path.into_inner();
+                #begin_tx
+                #fetch_previous
+                #payload_intake
+                let mut q = sqlx::query(#update_one_sql);
+                #(#bind_fields_update)*
+                q = q.bind(id);
+                match q.execute(&mut *tx).await {
+                    Ok(res) if res.rows_affected() > 0 => {
+                        #commit_or_discard
+                        #release_replaced
+                        #cache_invalidate
+                        events::publish_with(#table_name, "update", Some(id), &item);
+                        HttpResponse::Ok().finish()
+                    }
+                    Ok(_) => {
+                        #discard_uploads
+                        HttpResponse::Ok().finish()
+                    }
+                    Err(e) => {
+                        #discard_uploads
+                        HttpResponse::InternalServerError().body(e.to_string())
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-                    let sql = format!("DELETE FROM {} WHERE {} = ?", #table_name, #id_field);
-                    match sqlx::query(&sql)
-                        .bind(path.into_inner())
-                        .execute(db.get_ref())
-                        .await
-                    {
-                        Ok(_) => HttpResponse::Ok().finish(),
-                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    let delete_impl = if spec.has_operation("delete") {
+        quote! {
+            async fn delete(path: web::Path<i64>, #delete_user, db: web::Data<#pool_type>) -> impl Responder {
+                #delete_check
+
+                let id = path.into_inner();
+                #begin_tx
+                #fetch_previous
+                match sqlx::query(#delete_one_sql)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                {
+                    Ok(res) if res.rows_affected() > 0 => {
+                        if let Err(e) = tx.commit().await {
+                            return HttpResponse::InternalServerError().body(e.to_string());
+                        }
+                        #release_deleted
+                        #cache_invalidate
+                        events::publish(#table_name, "delete", Some(id));
+                        HttpResponse::Ok().finish()
                     }
+                    Ok(_) => HttpResponse::Ok().finish(),
+                    Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let patch_impl = if spec.has_operation("patch") {
+        patch_impl
+    } else {
+        quote! {}
+    };
+
+    // Static metadata for `Resource`, describing what was generated above
+    let struct_name_str = struct_name.to_string();
+    let collection_path = format!("/{}", table_name);
+    let relation_metas = spec.fields.iter().filter_map(|field| {
+        let relation = field.relation.as_ref()?;
+        let name = &field.name;
+        let parent_table = &relation.parent_table;
+        let parent_column = &relation.parent_column;
+        let nested_path = if relation.nested_route && spec.has_operation("list") {
+            let path = format!("/{}/{{parent_id}}/{}", parent_table, table_name);
+            quote! { Some(#path) }
+        } else {
+            quote! { None }
+        };
+        Some(quote! {
+            RelationMeta {
+                field: #name,
+                parent_table: #parent_table,
+                parent_column: #parent_column,
+                nested_path: #nested_path,
+            }
+        })
+    });
+    let hidden_fields = spec.fields.iter().filter(|f| f.hidden).map(|f| &f.name);
+    let file_metas = spec.file_fields().map(|field| {
+        let name = &field.name;
+        let file = field.file.as_ref().expect("file_fields only yields file fields");
+        let max_size = file.max_size;
+        let types = &file.types;
+        quote! {
+            FileMeta {
+                field: #name,
+                max_size: #max_size,
+                types: &[#(#types),*],
+            }
+        }
+    });
+    let operation_metas = attrs::OPERATIONS.iter().filter(|op| spec.has_operation(op)).map(|op| {
+        let public = spec.is_public(op);
+        let roles = match spec.roles.get(op) {
+            Some(requirement) if !requirement.public => {
+                let any = &requirement.any;
+                let all = &requirement.all;
+                quote! { Some(RoleRequirement { any: &[#(#any),*], all: &[#(#all),*] }) }
+            }
+            _ => quote! { None },
+        };
+        let permissions = match spec.permissions.get(op) {
+            Some(requirement) => {
+                let any = &requirement.any;
+                let all = &requirement.all;
+                quote! { Some(PermissionRequirement { any: &[#(#any),*], all: &[#(#all),*] }) }
+            }
+            None => quote! { None },
+        };
+        quote! {
+            OperationMeta {
+                name: #op,
+                public: #public,
+                roles: #roles,
+                permissions: #permissions,
+            }
+        }
+    });
+
+    let expanded_client = if cfg!(feature = "client") {
+        client(struct_name, &partial_struct_name, &spec)
+    } else {
+        quote! {}
+    };
+
+    // FINAL EXPANDED OUTPUT
+    Ok(quote! {
+        #expanded_partial
+        #expanded_client
+
+        mod #module_ident {
+            use super::*;
+            use actix_web::{web, HttpRequest, HttpResponse, Responder};
+            use sqlx::{SqlitePool, MySqlPool, PgPool, AnyPool};
+            // Access UserContext through the core module which is re-exported in rest_api
+            use very_simple_rest::core::aggregate;
+            use very_simple_rest::core::auth::{OptionalUserContext, UserContext};
+            use very_simple_rest::core::bulk;
+            use very_simple_rest::core::cache::{self, ResponseCache};
+            use very_simple_rest::core::codec::{Codec, Payload};
+            use very_simple_rest::core::events;
+            use very_simple_rest::core::files::{FileStorage, Upload};
+            use very_simple_rest::core::migrate::{Backend, ColumnDef, ColumnType, ForeignKey, TableSchema};
+            use very_simple_rest::core::permissions::{PermissionMap, PermissionRequirement};
+            use very_simple_rest::core::policy;
+            use very_simple_rest::core::query::ListQuery;
+            use very_simple_rest::core::resource::{FileMeta, OperationMeta, RelationMeta, Resource, ResourceMeta};
+            use very_simple_rest::core::roles::{RoleHierarchy, RoleRequirement};
+            use very_simple_rest::core::tx::Tx;
+
+            impl Resource for #struct_name {
+                fn metadata() -> &'static ResourceMeta {
+                    static META: ResourceMeta = ResourceMeta {
+                        name: #struct_name_str,
+                        table: #table_name,
+                        path: #collection_path,
+                        primary_key: #id_field,
+                        schema: #struct_name::SCHEMA,
+                        relations: &[#(#relation_metas),*],
+                        files: &[#(#file_metas),*],
+                        hidden: &[#(#hidden_fields),*],
+                        operations: &[#(#operation_metas),*],
+                    };
+                    &META
                 }
+            }
 
-                #get_by_parent_id_impl
+            #[derive(serde::Deserialize)]
+            pub struct CreateParams {
+                on_conflict: Option<String>,
+            }
+
+            impl #struct_name {
+                pub fn configure(cfg: &mut web::ServiceConfig, db: #pool_type) {
+                    let db = web::Data::new(db);
+                    cfg.app_data(db.clone());
+                    very_simple_rest::core::resource::register(Self::metadata());
+
+                    #collection_registration
+                    #schema_registration
+                    #events_registration
+                    #(#transfer_registration)*
+                    #item_registration
+                    #(#download_registrations)*
+                    #(#nested_route_registrations)*
+                }
+
+                /// The table this resource expects, for generating migrations
+                pub const SCHEMA: TableSchema = TableSchema {
+                    name: #table_name,
+                    columns: &[#(#schema_columns),*],
+                    unique: &[],
+                };
+
+                #json_schema_impl
+
+                #get_all_impl
+                #get_one_impl
+                #(#download_impls)*
+                #create_impl
+                #export_impl
+                #aggregate_impl
+                #import_impl
+                #update_impl
+                #delete_impl
             }
 
             #patch_impl
         }
-    };
+    })
+}
+
+/// `insert_sql` turned into an upsert keyed on the unique `conflict_field`: `ON CONFLICT ... DO UPDATE`,
+/// or `ON DUPLICATE KEY UPDATE` on MySQL. File fields keep their file unless a new one was uploaded.
+fn upsert_sql(spec: &ResourceSpec, insert_sql: &str, insert_fields: &[String], conflict_field: &str) -> String {
+    let mysql = spec.db == "mysql";
+    let mut assignments: Vec<String> = insert_fields
+        .iter()
+        .filter(|f| *f != conflict_field)
+        .map(|f| {
+            let incoming = if mysql { format!("VALUES({})", f) } else { format!("excluded.{}", f) };
+            if spec.fields.iter().any(|field| field.name == *f && field.file.is_some()) {
+                format!("{} = COALESCE({}, {}.{})", f, incoming, spec.table, f)
+            } else {
+                format!("{} = {}", f, incoming)
+            }
+        })
+        .collect();
+    if spec.has_field("updated_at") {
+        assignments.push("updated_at = CURRENT_TIMESTAMP".to_string());
+    }
+    // MySQL has no DO NOTHING equivalent, so a no-op assignment keeps the statement valid
+    if assignments.is_empty() {
+        assignments.push(format!("{} = {}", conflict_field, conflict_field));
+    }
 
-    TokenStream::from(expanded)
+    if mysql {
+        format!("{} ON DUPLICATE KEY UPDATE {}", insert_sql, assignments.join(", "))
+    } else {
+        format!("{} ON CONFLICT ({}) DO UPDATE SET {}", insert_sql, conflict_field, assignments.join(", "))
+    }
+}
+
+/// `PostClient`, calling the routes `configure` registers for the enabled operations
+fn client(struct_name: &syn::Ident, partial_struct_name: &syn::Ident, spec: &ResourceSpec) -> proc_macro2::TokenStream {
+    let client_name = format_ident!("{}Client", struct_name);
+    let doc = format!("Typed HTTP client for the `{}` routes", struct_name);
+    let collection_path = format!("/{}", spec.table);
+    let item_path = format!("/{}/{{}}", spec.table);
+
+    let mut methods = vec![];
+    if spec.has_operation("list") {
+        methods.push(quote! {
+            pub async fn list(&self) -> Result<Vec<#struct_name>, ClientError> {
+                self.api.get_json(#collection_path).await
+            }
+        });
+    }
+    if spec.has_operation("get") {
+        methods.push(quote! {
+            pub async fn get(&self, id: i64) -> Result<#struct_name, ClientError> {
+                self.api.get_json(&format!(#item_path, id)).await
+            }
+        });
+    }
+    if spec.has_operation("create") {
+        methods.push(quote! {
+            pub async fn create(&self, item: &#struct_name) -> Result<(), ClientError> {
+                self.api.send_json(Method::POST, #collection_path, item).await
+            }
+        });
+    }
+    if spec.has_operation("update") {
+        methods.push(quote! {
+            pub async fn update(&self, id: i64, item: &#struct_name) -> Result<(), ClientError> {
+                self.api.send_json(Method::PUT, &format!(#item_path, id), item).await
+            }
+        });
+    }
+    if spec.has_operation("patch") {
+        methods.push(quote! {
+            pub async fn patch(&self, id: i64, changes: &#partial_struct_name) -> Result<(), ClientError> {
+                self.api.send_json(Method::PATCH, &format!(#item_path, id), changes).await
+            }
+        });
+    }
+    if spec.has_operation("delete") {
+        methods.push(quote! {
+            pub async fn delete(&self, id: i64) -> Result<(), ClientError> {
+                self.api.delete(&format!(#item_path, id)).await
+            }
+        });
+    }
+    if spec.has_operation("list") {
+        for field in &spec.fields {
+            let Some(relation) = field.relation.as_ref().filter(|r| r.nested_route) else {
+                continue;
+            };
+            let method = format_ident!("list_by_{}", field.name);
+            let path = format!("/{}/{{}}/{}", relation.parent_table, spec.table);
+            methods.push(quote! {
+                pub async fn #method(&self, parent_id: i64) -> Result<Vec<#struct_name>, ClientError> {
+                    self.api.get_json(&format!(#path, parent_id)).await
+                }
+            });
+        }
+    }
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        pub struct #client_name {
+            api: very_simple_rest::core::client::ApiClient,
+        }
+
+        const _: () = {
+            use very_simple_rest::core::client::{ApiClient, ClientError, Method};
+
+            impl #client_name {
+                pub fn new(api: ApiClient) -> Self {
+                    Self { api }
+                }
+
+                #(#methods)*
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(input: DeriveInput) -> ResourceSpec {
+        ResourceSpec::from_derive_input(&input).unwrap()
+    }
+
+    #[test]
+    fn upserts_in_each_dialect() {
+        let fields = ["email".to_string(), "name".to_string()];
+        let sqlite = spec(syn::parse_quote! {
+            struct Account {
+                id: Option<i64>,
+                #[rest_api(unique)]
+                email: String,
+                name: String,
+                updated_at: Option<String>,
+            }
+        });
+        assert_eq!(
+            upsert_sql(&sqlite, "INSERT INTO account (email, name) VALUES (?, ?)", &fields, "email"),
+            "INSERT INTO account (email, name) VALUES (?, ?) ON CONFLICT (email) DO UPDATE SET \
+             name = excluded.name, updated_at = CURRENT_TIMESTAMP"
+        );
+
+        let mysql = spec(syn::parse_quote! {
+            #[rest_api(db = "mysql")]
+            struct Account {
+                id: Option<i64>,
+                #[rest_api(unique)]
+                email: String,
+                name: String,
+            }
+        });
+        assert_eq!(
+            upsert_sql(&mysql, "INSERT INTO account (email, name) VALUES (?, ?)", &fields, "email"),
+            "INSERT INTO account (email, name) VALUES (?, ?) ON DUPLICATE KEY UPDATE name = VALUES(name)"
+        );
+        // Nothing else to update still needs an assignment on MySQL
+        assert_eq!(
+            upsert_sql(&mysql, "INSERT INTO account (email) VALUES (?)", &fields[..1], "email"),
+            "INSERT INTO account (email) VALUES (?) ON DUPLICATE KEY UPDATE email = email"
+        );
+    }
+
+    #[test]
+    fn upserts_keep_files_without_uploads() {
+        let document = spec(syn::parse_quote! {
+            #[rest_api(db = "postgres")]
+            struct Document {
+                id: Option<i64>,
+                #[rest_api(unique)]
+                slug: String,
+                #[rest_api(file)]
+                scan: Option<StoredFile>,
+            }
+        });
+        let fields = ["slug".to_string(), "scan".to_string()];
+        assert_eq!(
+            upsert_sql(&document, "INSERT INTO document (slug, scan) VALUES ($1, $2)", &fields, "slug"),
+            "INSERT INTO document (slug, scan) VALUES ($1, $2) ON CONFLICT (slug) DO UPDATE SET \
+             scan = COALESCE(excluded.scan, document.scan)"
+        );
+    }
 }
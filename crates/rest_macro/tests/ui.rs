@@ -0,0 +1,8 @@
+//! Compile errors reported by `#[derive(RestApi)]`, checked against the
+//! `.stderr` snapshot next to each case in `tests/ui`. After changing a
+//! message, run with `TRYBUILD=overwrite` and review the new snapshots.
+
+#[test]
+fn attribute_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
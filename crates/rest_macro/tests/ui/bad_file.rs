@@ -0,0 +1,22 @@
+use rest_macro::RestApi;
+
+struct StoredFile;
+
+#[derive(RestApi)]
+struct Post {
+    id: Option<i64>,
+    #[rest_api(file(max_size = "10XB"))]
+    cover: Option<StoredFile>,
+    #[rest_api(file(max_size = "0"))]
+    thumbnail: Option<StoredFile>,
+    #[rest_api(file(types = "image, application/pdf"))]
+    attachment: Option<StoredFile>,
+    #[rest_api(file(maxsize = "1MB"))]
+    banner: Option<StoredFile>,
+    #[rest_api(file)]
+    notes: String,
+    #[rest_api(unique, file)]
+    signature: Option<StoredFile>,
+}
+
+fn main() {}
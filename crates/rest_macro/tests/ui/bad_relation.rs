@@ -0,0 +1,18 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+struct Comment {
+    id: Option<i64>,
+    #[relation(references = "post")]
+    post_id: i64,
+    #[relation(foreign_key = "post_id", references = "post.id")]
+    parent_id: i64,
+    #[relation(references = "user.id", nested_route = "yes")]
+    author_id: i64,
+    #[relation(nested_route = "true")]
+    thread_id: i64,
+    #[relation(refs = "post.id")]
+    reply_to: i64,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+#[rest_api(db = "oracle")]
+#[rest_api(table = "blog-post")]
+#[rest_api(exclude = "delete, purge")]
+#[rest_api(cache(ttl = 0))]
+struct Post {
+    id: Option<i64>,
+    title: String,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+#[require_role(read = "public, user")]
+#[require_role(update(any = "editor", most = "admin"))]
+#[require_role(create = "editor,")]
+#[require_role(delete = "admin")]
+#[require_role(delete = "owner")]
+struct Post {
+    id: Option<i64>,
+    title: String,
+}
+
+fn main() {}
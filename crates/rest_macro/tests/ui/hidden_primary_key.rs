@@ -0,0 +1,10 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+struct User {
+    #[rest_api(hidden)]
+    id: Option<i64>,
+    email: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+#[rest_api(id = "post_id")]
+struct Post {
+    id: Option<i64>,
+    title: String,
+}
+
+fn main() {}
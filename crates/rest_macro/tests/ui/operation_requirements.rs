@@ -0,0 +1,29 @@
+use rest_macro::RestApi;
+
+// Requirements for operations that `operations` or `exclude` leave out
+#[derive(RestApi)]
+#[rest_api(operations = "list, create")]
+#[require_role(read = "user", delete = "admin")]
+#[require_permission(patch = "post:write")]
+struct Post {
+    id: Option<i64>,
+    title: String,
+}
+
+#[derive(RestApi)]
+#[rest_api(exclude = "list, get")]
+#[require_role(read = "user")]
+struct Draft {
+    id: Option<i64>,
+    title: String,
+}
+
+// `create` and `patch` would silently become public
+#[derive(RestApi)]
+#[require_role(read = "user", update = "public")]
+struct Note {
+    id: Option<i64>,
+    title: String,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+#[require_role(read = "public", update = "user")]
+#[require_permission(read = "post:read")]
+struct Post {
+    id: Option<i64>,
+    title: String,
+}
+
+fn main() {}
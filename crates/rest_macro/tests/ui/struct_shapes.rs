@@ -0,0 +1,25 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+struct Pair(i64, String);
+
+#[derive(RestApi)]
+struct Marker;
+
+#[derive(RestApi)]
+enum Status {
+    Draft,
+}
+
+#[derive(RestApi)]
+struct Post {
+    id: Option<i64>,
+    tags: Vec<String>,
+}
+
+#[derive(RestApi)]
+struct Tag {
+    id: String,
+}
+
+fn main() {}
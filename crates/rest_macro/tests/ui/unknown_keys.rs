@@ -0,0 +1,13 @@
+use rest_macro::RestApi;
+
+#[derive(RestApi)]
+#[rest_api(tabel = "post")]
+#[rest_api(cache(ttl = 60, size = 10))]
+#[require_role(raed = "user")]
+struct Post {
+    id: Option<i64>,
+    #[rest_api(uniq)]
+    title: String,
+}
+
+fn main() {}
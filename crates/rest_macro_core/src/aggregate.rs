@@ -0,0 +1,227 @@
+//! Counts, sums and averages over a resource, generated as
+//! `GET /{table}/aggregate` for resources with `list` enabled. Plain row
+//! counts are also served by `GET /{table}/count` as a [`Count`], and by
+//! `HEAD /{table}` in an `X-Total-Count` header.
+//!
+//! ```text
+//! GET /comment/aggregate?count=*&group_by=post_id
+//! [{ "post_id": 1, "count": 12 }, { "post_id": 2, "count": 3 }]
+//! ```
+//!
+//! `count`, `sum`, `avg` and `group_by` each take a comma-separated list of
+//! columns; `count` also takes `*` for every row. Any other parameters are
+//! [`query`](crate::query) filters, as for `list`. Only integer and real
+//! columns can be summed or averaged. Each result row has the group columns
+//! under their own names, then `count` (for `*`), `count_<column>`,
+//! `sum_<column>` and `avg_<column>`. Groups are ordered by the group columns.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{ColumnIndex, Decode, Row, Type};
+
+use crate::migrate::{Backend, ColumnDef, ColumnType};
+use crate::query::ListQuery;
+use crate::resource::ResourceMeta;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AggregateParams {
+    pub count: Option<String>,
+    pub sum: Option<String>,
+    pub avg: Option<String>,
+    pub group_by: Option<String>,
+}
+
+/// The body of `GET /{table}/count`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Count {
+    pub count: i64,
+}
+
+/// The header `HEAD /{table}` reports the count in
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// How a result column is decoded
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    Group(&'static ColumnDef),
+    Count,
+    Number,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Count,
+    Sum,
+    Avg,
+}
+
+/// A validated aggregate request
+#[derive(Debug)]
+pub struct Aggregate {
+    table: &'static str,
+    group_by: Vec<&'static str>,
+    functions: Vec<(Function, Option<&'static str>)>,
+    outputs: Vec<(String, Output)>,
+}
+
+impl Aggregate {
+    /// Check every requested column against the resource
+    pub fn parse(meta: &'static ResourceMeta, params: &AggregateParams) -> Result<Self, String> {
+        let mut aggregate = Aggregate { table: meta.table, group_by: vec![], functions: vec![], outputs: vec![] };
+
+        for name in names(&params.group_by) {
+            let column = column(meta, name)?;
+            if meta.file(name).is_some() {
+                return Err(format!("cannot group by the file field `{}`", name));
+            }
+            if !aggregate.group_by.contains(&column.name) {
+                aggregate.group_by.push(column.name);
+                aggregate.outputs.push((column.name.to_string(), Output::Group(column)));
+            }
+        }
+
+        for name in names(&params.count) {
+            let (alias, column) = match name {
+                "*" => ("count".to_string(), None),
+                name => {
+                    let column = column(meta, name)?;
+                    (format!("count_{}", column.name), Some(column.name))
+                }
+            };
+            aggregate.push(alias, Function::Count, column, Output::Count);
+        }
+
+        for (function, prefix, requested) in [(Function::Sum, "sum", &params.sum), (Function::Avg, "avg", &params.avg)] {
+            for name in names(requested) {
+                let column = column(meta, name)?;
+                if !matches!(column.column_type, ColumnType::Integer | ColumnType::Real) {
+                    return Err(format!("`{}` is not numeric and cannot be used with {}", name, prefix));
+                }
+                aggregate.push(format!("{}_{}", prefix, column.name), function, Some(column.name), Output::Number);
+            }
+        }
+
+        if aggregate.functions.is_empty() {
+            return Err("nothing to aggregate; pass count, sum or avg".to_string());
+        }
+        Ok(aggregate)
+    }
+
+    fn push(&mut self, alias: String, function: Function, column: Option<&'static str>, output: Output) {
+        if !self.outputs.iter().any(|(name, _)| *name == alias) {
+            self.functions.push((function, column));
+            self.outputs.push((alias, output));
+        }
+    }
+
+    /// The query over the rows `filters` match, with sums and averages cast to
+    /// a double so every backend returns the same type. Bind
+    /// [`ListQuery::arguments`] to it.
+    pub fn sql(&self, backend: Backend, filters: &ListQuery) -> String {
+        let double = match backend {
+            Backend::Sqlite => "REAL",
+            Backend::Postgres => "DOUBLE PRECISION",
+            Backend::Mysql => "DOUBLE",
+        };
+
+        let mut select: Vec<String> = self.group_by.iter().map(|c| c.to_string()).collect();
+        let aliases = self.outputs.iter().skip(self.group_by.len()).map(|(alias, _)| alias);
+        for ((function, column), alias) in self.functions.iter().zip(aliases) {
+            let expression = match (function, column) {
+                (Function::Count, None) => "COUNT(*)".to_string(),
+                (Function::Count, Some(column)) => format!("COUNT({})", column),
+                (Function::Sum, Some(column)) => format!("CAST(SUM({}) AS {})", column, double),
+                (Function::Avg, Some(column)) => format!("CAST(AVG({}) AS {})", column, double),
+                (_, None) => unreachable!("sum and avg always name a column"),
+            };
+            select.push(format!("{} AS {}", expression, alias));
+        }
+
+        let mut sql = format!("SELECT {} FROM {}{}", select.join(", "), self.table, filters.where_sql(backend));
+        if !self.group_by.is_empty() {
+            let columns = self.group_by.join(", ");
+            sql.push_str(&format!(" GROUP BY {} ORDER BY {}", columns, columns));
+        }
+        sql
+    }
+
+    /// Result rows as JSON objects
+    pub fn rows<R>(&self, rows: &[R]) -> Result<Vec<Value>, sqlx::Error>
+    where
+        R: Row,
+        usize: ColumnIndex<R>,
+        for<'r> i64: Decode<'r, R::Database> + Type<R::Database>,
+        for<'r> f64: Decode<'r, R::Database> + Type<R::Database>,
+        for<'r> bool: Decode<'r, R::Database> + Type<R::Database>,
+        for<'r> String: Decode<'r, R::Database> + Type<R::Database>,
+    {
+        rows.iter()
+            .map(|row| {
+                let mut object = Map::new();
+                for (index, (name, output)) in self.outputs.iter().enumerate() {
+                    let value = match output {
+                        Output::Count => Value::from(row.try_get::<i64, _>(index)?),
+                        Output::Number => row.try_get::<Option<f64>, _>(index)?.map_or(Value::Null, Value::from),
+                        Output::Group(column) => match column.column_type {
+                            ColumnType::Integer => row.try_get::<Option<i64>, _>(index)?.map_or(Value::Null, Value::from),
+                            ColumnType::Real => row.try_get::<Option<f64>, _>(index)?.map_or(Value::Null, Value::from),
+                            // SQLite stores booleans as integers
+                            ColumnType::Boolean => row
+                                .try_get::<Option<bool>, _>(index)
+                                .or_else(|_| row.try_get::<Option<i64>, _>(index).map(|v| v.map(|v| v != 0)))?
+                                .map_or(Value::Null, Value::from),
+                            ColumnType::Text => row.try_get::<Option<String>, _>(index)?.map_or(Value::Null, Value::from),
+                        },
+                    };
+                    object.insert(name.clone(), value);
+                }
+                Ok(Value::Object(object))
+            })
+            .collect()
+    }
+}
+
+fn names(list: &Option<String>) -> impl Iterator<Item = &str> {
+    list.as_deref().unwrap_or_default().split(',').map(str::trim).filter(|name| !name.is_empty())
+}
+
+/// Hidden fields are treated as unknown, so their values cannot be probed
+fn column(meta: &ResourceMeta, name: &str) -> Result<&'static ColumnDef, String> {
+    meta.column(name).filter(|c| !meta.is_hidden(c.name)).ok_or_else(|| format!("unknown column `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::COMMENT;
+    use std::collections::BTreeMap;
+
+    fn params(count: &str, sum: &str, avg: &str, group_by: &str) -> AggregateParams {
+        let some = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        AggregateParams { count: some(count), sum: some(sum), avg: some(avg), group_by: some(group_by) }
+    }
+
+    #[test]
+    fn builds_grouped_queries() {
+        let aggregate = Aggregate::parse(&COMMENT, &params("*, title", "rating", "rating", "post_id")).unwrap();
+        let filters = BTreeMap::from([("title_contains".to_string(), "great".to_string())]);
+        let filters = ListQuery::parse(&COMMENT, &filters, &[]).unwrap();
+        assert_eq!(
+            aggregate.sql(Backend::Postgres, &filters),
+            "SELECT post_id, COUNT(*) AS count, COUNT(title) AS count_title, \
+             CAST(SUM(rating) AS DOUBLE PRECISION) AS sum_rating, \
+             CAST(AVG(rating) AS DOUBLE PRECISION) AS avg_rating \
+             FROM comment WHERE title LIKE $1 GROUP BY post_id ORDER BY post_id"
+        );
+
+        let total = Aggregate::parse(&COMMENT, &params("*", "", "", "")).unwrap();
+        assert_eq!(total.sql(Backend::Sqlite, &ListQuery::all(&COMMENT)), "SELECT COUNT(*) AS count FROM comment");
+    }
+
+    #[test]
+    fn rejects_unknown_and_non_numeric_columns() {
+        assert!(Aggregate::parse(&COMMENT, &params("", "title", "", "")).unwrap_err().contains("not numeric"));
+        assert!(Aggregate::parse(&COMMENT, &params("*", "", "", "author")).unwrap_err().contains("unknown column"));
+        assert!(Aggregate::parse(&COMMENT, &params("", "", "", "post_id")).is_err());
+    }
+}
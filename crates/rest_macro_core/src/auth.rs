@@ -53,13 +53,76 @@ fn get_jwt_secret() -> &'static [u8] {
 struct Claims {
     sub: i64,
     roles: Vec<String>,
+    /// Fine-grained grants such as `post:write`; absent in tokens issued before permissions existed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    permissions: Vec<String>,
     exp: usize,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserContext {
     pub id: i64,
     pub roles: Vec<String>,
+    /// Permissions granted directly by the token, without those implied by roles
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+impl UserContext {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+/// Sign a token for `user_id` that is valid for `ttl`.
+///
+/// `login` issues tokens with the user's role only. Use this to hand out
+/// narrower tokens, such as ones carrying just `post:read` for a partner
+/// integration.
+pub fn issue_token(
+    user_id: i64,
+    roles: Vec<String>,
+    permissions: Vec<String>,
+    ttl: Duration,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims {
+        sub: user_id,
+        roles,
+        permissions,
+        exp: (Utc::now() + ttl).timestamp() as usize,
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(get_jwt_secret()))
+}
+
+/// Decode the bearer token of a request, if it has one
+fn user_from_request(req: &HttpRequest) -> Result<Option<UserContext>, actix_web::Error> {
+    use actix_web::{error::ErrorUnauthorized, http::header};
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) => match decode::<Claims>(token, &DecodingKey::from_secret(get_jwt_secret()), &Validation::default()) {
+            Ok(data) => {
+                let claims = data.claims;
+                Ok(Some(UserContext {
+                    id: claims.sub,
+                    roles: claims.roles,
+                    permissions: claims.permissions,
+                }))
+            }
+            Err(_) => Err(ErrorUnauthorized("Invalid token")),
+        },
+        None => Ok(None),
+    }
 }
 
 impl FromRequest for UserContext {
@@ -67,29 +130,37 @@ impl FromRequest for UserContext {
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        use actix_web::{error::ErrorUnauthorized, http::header};
-
-        let token = req
-            .headers()
-            .get(header::AUTHORIZATION)
-            .and_then(|h| h.to_str().ok())
-            .and_then(|v| v.strip_prefix("Bearer "))
-            .map(|s| s.to_string());
-
-        if let Some(token) = token {
-            match decode::<Claims>(&token, &DecodingKey::from_secret(get_jwt_secret()), &Validation::default()) {
-                Ok(data) => {
-                    let claims = data.claims;
-                    return ready(Ok(UserContext {
-                        id: claims.sub,
-                        roles: claims.roles,
-                    }));
-                }
-                Err(_) => return ready(Err(ErrorUnauthorized("Invalid token"))),
-            }
-        }
+        ready(user_from_request(req).and_then(|user| {
+            user.ok_or_else(|| actix_web::error::ErrorUnauthorized("Missing token"))
+        }))
+    }
+}
+
+/// The authenticated user, if the request carries a token.
+///
+/// Used by operations that are open to anonymous callers. Requests without an
+/// `Authorization` header get `None`, while an invalid token is still rejected
+/// with `401 Unauthorized` rather than being treated as anonymous.
+#[derive(Clone)]
+pub struct OptionalUserContext(pub Option<UserContext>);
+
+impl FromRequest for OptionalUserContext {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(user_from_request(req).map(OptionalUserContext))
+    }
+}
 
-        ready(Err(ErrorUnauthorized("Missing token")))
+/// `403 Forbidden` unless `user` holds a superuser role of the app's
+/// [`RoleHierarchy`](crate::roles::RoleHierarchy), as the policy and webhook
+/// admin routes require
+pub fn require_superuser(req: &HttpRequest, user: &UserContext) -> Option<HttpResponse> {
+    if crate::roles::RoleHierarchy::from_request(req).is_superuser(&user.roles) {
+        None
+    } else {
+        Some(HttpResponse::Forbidden().body("Insufficient privileges"))
     }
 }
 
@@ -144,13 +215,7 @@ pub async fn login(input: web::Json<LoginInput>, db: web::Data<AnyPool>) -> impl
     };
 
     if verify(&input.password, &user.password_hash).unwrap_or(false) {
-        let claims = Claims {
-            sub: user.id.unwrap(),
-            roles: vec![user.role.clone()],
-            exp: (Utc::now() + Duration::hours(24)).timestamp() as usize,
-        };
-
-        match encode(&Header::default(), &claims, &EncodingKey::from_secret(get_jwt_secret())) {
+        match issue_token(user.id.unwrap(), vec![user.role.clone()], vec![], Duration::hours(24)) {
             Ok(token) => HttpResponse::Ok().json(serde_json::json!({ "token": token })),
             Err(_) => HttpResponse::InternalServerError().body("Token generation failed"),
         }
@@ -0,0 +1,391 @@
+//! CSV and NDJSON export and import, generated as `GET /{table}/export` and
+//! `POST /{table}/import`.
+//!
+//! Exports stream the rows `list` returns, taking the same
+//! [`query`](crate::query) filters, a page at a time in primary key order, so
+//! large tables never sit in memory. Hidden fields are left out. CSV has a
+//! header row with the column names, and NULL is written as an empty cell.
+//!
+//! Imports check every line before writing anything. If any line is invalid,
+//! nothing is inserted and the response lists each bad line. Otherwise the rows
+//! are inserted in batches inside one transaction, which is rolled back if a
+//! batch fails. Ids, timestamps and file fields in the file are ignored, so
+//! every line becomes a new row. Uploads may be up to [`MAX_IMPORT_SIZE`], and
+//! the change events of imported rows carry no `record_id`.
+
+use std::future::Future;
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::stream;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::migrate::ColumnType;
+use crate::resource::ResourceMeta;
+
+/// Rows fetched per query while exporting
+pub const PAGE_SIZE: i64 = 500;
+
+/// Largest import upload, above actix's default payload limit of 256 KiB
+pub const MAX_IMPORT_SIZE: usize = 32 * 1024 * 1024;
+
+/// Bound parameters per insert statement, below SQLite's historical limit of 999
+const MAX_PARAMETERS: usize = 900;
+
+#[derive(Debug, Deserialize)]
+pub struct TransferParams {
+    /// `csv` or `ndjson`
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Ndjson,
+}
+
+impl Format {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "ndjson" => Ok(Format::Ndjson),
+            other => Err(format!("unknown format `{}`, expected csv or ndjson", other)),
+        }
+    }
+
+    /// `?format=`, defaulting to CSV
+    pub fn for_export(params: &TransferParams) -> Result<Self, String> {
+        params.format.as_deref().map_or(Ok(Format::Csv), Self::parse)
+    }
+
+    /// `?format=`, or else the `Content-Type` (`application/x-ndjson` or `text/csv`), defaulting to CSV
+    pub fn for_import(req: &HttpRequest, params: &TransferParams) -> Result<Self, String> {
+        if let Some(format) = &params.format {
+            return Self::parse(format);
+        }
+        let content_type = req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("");
+        if content_type.contains("ndjson") || content_type.contains("jsonlines") {
+            Ok(Format::Ndjson)
+        } else {
+            Ok(Format::Csv)
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Stream every row of a resource in `format`.
+///
+/// `fetch_page(after)` returns up to [`PAGE_SIZE`] rows whose primary key is
+/// greater than `after`, in primary key order.
+pub fn export_response<T, F, Fut>(meta: &'static ResourceMeta, format: Format, fetch_page: F) -> HttpResponse
+where
+    T: Serialize + 'static,
+    F: FnMut(i64) -> Fut + 'static,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>> + 'static,
+{
+    let columns = || meta.columns().iter().filter(|c| !meta.is_hidden(c.name));
+    let header_row = match format {
+        Format::Csv => Some(csv_line(columns().map(|c| c.name.to_string()))),
+        Format::Ndjson => None,
+    };
+
+    // `None` once the last page has been sent
+    let state = (fetch_page, Some(i64::MIN), header_row);
+    let body = stream::unfold(state, move |(mut fetch_page, after, header_row)| async move {
+        if let Some(header_row) = header_row {
+            return Some((Ok(Bytes::from(header_row)), (fetch_page, after, None)));
+        }
+        let rows = match fetch_page(after?).await {
+            Ok(rows) => rows,
+            Err(e) => return Some((Err(actix_web::error::ErrorInternalServerError(e)), (fetch_page, None, None))),
+        };
+
+        let mut chunk = String::new();
+        let mut last_id = None;
+        for row in &rows {
+            let value = serde_json::to_value(row).unwrap_or(Value::Null);
+            last_id = value.get(meta.primary_key).and_then(Value::as_i64);
+            match format {
+                Format::Csv => chunk.push_str(&csv_line(columns().map(|c| cell(&value[c.name])))),
+                // Written field by field to keep the struct's field order
+                Format::Ndjson => {
+                    let fields: Vec<_> = columns()
+                        .map(|c| format!("\"{}\":{}", c.name, serde_json::to_string(&value[c.name]).unwrap_or_default()))
+                        .collect();
+                    chunk.push_str(&format!("{{{}}}\n", fields.join(",")));
+                }
+            }
+        }
+
+        let next = if rows.len() < PAGE_SIZE as usize { None } else { last_id };
+        if chunk.is_empty() {
+            return None;
+        }
+        Some((Ok(Bytes::from(chunk)), (fetch_page, next, None)))
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.{}\"", meta.table, format.extension()),
+        ))
+        .streaming(body)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_line(cells: impl IntoIterator<Item = String>) -> String {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let _ = writer.write_record(cells);
+    String::from_utf8(writer.into_inner().unwrap_or_default()).unwrap_or_default()
+}
+
+/// A problem with one line of an import
+#[derive(Debug, Serialize)]
+pub struct LineError {
+    /// 1-based line in the uploaded file; the CSV header is line 1
+    pub line: usize,
+    pub error: String,
+}
+
+/// Response body of `POST /{table}/import`
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub errors: Vec<LineError>,
+}
+
+impl ImportReport {
+    pub fn response(&self) -> HttpResponse {
+        if self.errors.is_empty() {
+            HttpResponse::Ok().json(self)
+        } else {
+            HttpResponse::UnprocessableEntity().json(self)
+        }
+    }
+
+    /// A batch insert failed, so the transaction was rolled back
+    pub fn batch_failed(lines: &[(usize, impl Sized)], error: sqlx::Error) -> Self {
+        let first = lines.first().map(|(line, _)| *line).unwrap_or_default();
+        let last = lines.last().map(|(line, _)| *line).unwrap_or_default();
+        Self {
+            inserted: 0,
+            errors: vec![LineError { line: first, error: format!("insert of lines {}-{} failed: {}", first, last, error) }],
+        }
+    }
+}
+
+/// Parse an upload into rows tagged with their line numbers, or report every invalid line
+pub fn parse<T: DeserializeOwned>(meta: &ResourceMeta, format: Format, body: &[u8]) -> Result<Vec<(usize, T)>, ImportReport> {
+    let mut rows = vec![];
+    let mut errors = vec![];
+
+    match format {
+        Format::Ndjson => {
+            for (index, line) in String::from_utf8_lossy(body).lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match parse_line::<T>(meta, line) {
+                    Ok(row) => rows.push((index + 1, row)),
+                    // Each line is its own document, so serde's line number is always 1
+                    Err(e) => errors.push(LineError {
+                        line: index + 1,
+                        error: e.to_string().replace(" at line 1 column ", " at column "),
+                    }),
+                }
+            }
+        }
+        Format::Csv => {
+            let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body);
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(e) => return Err(ImportReport { inserted: 0, errors: vec![LineError { line: 1, error: e.to_string() }] }),
+            };
+            let columns: Vec<_> = headers.iter().map(|name| meta.column(name).ok_or(name)).collect();
+            for unknown in columns.iter().filter_map(|c| c.err()) {
+                errors.push(LineError { line: 1, error: format!("unknown column `{}`", unknown) });
+            }
+            if !errors.is_empty() {
+                return Err(ImportReport { inserted: 0, errors });
+            }
+
+            for record in reader.records() {
+                let record = match record {
+                    Ok(record) => record,
+                    Err(e) => {
+                        let line = e.position().map(|p| p.line() as usize).unwrap_or_default();
+                        errors.push(LineError { line, error: e.to_string() });
+                        continue;
+                    }
+                };
+                let line = record.position().map(|p| p.line() as usize).unwrap_or_default();
+
+                let mut object = Map::new();
+                let mut line_errors = vec![];
+                for (column, text) in columns.iter().flatten().zip(record.iter()) {
+                    if meta.file(column.name).is_some() {
+                        continue;
+                    }
+                    match text_value(column.column_type, text) {
+                        Ok(value) => {
+                            object.insert(column.name.to_string(), value);
+                        }
+                        Err(expected) => line_errors.push(format!("`{}` must be {}, got `{}`", column.name, expected, text)),
+                    }
+                }
+                if !line_errors.is_empty() {
+                    errors.push(LineError { line, error: line_errors.join("; ") });
+                    continue;
+                }
+                match serde_json::from_value::<T>(Value::Object(object)) {
+                    Ok(row) => rows.push((line, row)),
+                    Err(e) => errors.push(LineError { line, error: e.to_string() }),
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() { Ok(rows) } else { Err(ImportReport { inserted: 0, errors }) }
+}
+
+/// An NDJSON line, without its file fields
+fn parse_line<T: DeserializeOwned>(meta: &ResourceMeta, line: &str) -> serde_json::Result<T> {
+    if meta.files.is_empty() {
+        return serde_json::from_str(line);
+    }
+    let mut value: Value = serde_json::from_str(line)?;
+    if let Some(object) = value.as_object_mut() {
+        for file in meta.files {
+            object.remove(file.field);
+        }
+    }
+    serde_json::from_value(value)
+}
+
+/// A CSV cell or form field as the JSON value its column expects; empty text is NULL
+pub(crate) fn text_value(column_type: ColumnType, text: &str) -> Result<Value, &'static str> {
+    if text.is_empty() {
+        return Ok(Value::Null);
+    }
+    match column_type {
+        ColumnType::Integer => text.parse::<i64>().map(Value::from).map_err(|_| "an integer"),
+        ColumnType::Real => text.parse::<f64>().map(Value::from).map_err(|_| "a number"),
+        ColumnType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(Value::Bool(true)),
+            "false" | "0" => Ok(Value::Bool(false)),
+            _ => Err("true or false"),
+        },
+        ColumnType::Text => Ok(Value::String(text.to_string())),
+    }
+}
+
+/// Rows per insert statement for a table with `columns` inserted columns
+pub fn batch_size(columns: usize) -> usize {
+    (MAX_PARAMETERS / columns.max(1)).clamp(1, 100)
+}
+
+/// `INSERT INTO table (a, b) VALUES (?, ?), (?, ?)` for `rows` rows, with
+/// `$1`-style placeholders when `numbered`
+pub fn insert_sql(table: &str, columns: &[&str], rows: usize, numbered: bool) -> String {
+    let mut n = 0;
+    let values: Vec<String> = (0..rows)
+        .map(|_| {
+            let placeholders: Vec<String> = columns
+                .iter()
+                .map(|_| {
+                    n += 1;
+                    if numbered { format!("${}", n) } else { "?".to_string() }
+                })
+                .collect();
+            format!("({})", placeholders.join(", "))
+        })
+        .collect();
+    format!("INSERT INTO {} ({}) VALUES {}", table, columns.join(", "), values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::POST;
+
+    #[derive(Debug, Deserialize)]
+    struct Post {
+        title: String,
+        score: Option<f64>,
+    }
+
+    #[test]
+    fn parses_csv_and_reports_bad_lines() {
+        let csv = "id,title,score\n,\"Hello, world\",1.5\n2,Second,\n3,Third,high\n";
+        let report = parse::<Post>(&POST, Format::Csv, csv.as_bytes()).unwrap_err();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 4);
+        assert!(report.errors[0].error.contains("`score` must be a number"));
+
+        let valid = "id,title,score\n,\"Hello, world\",1.5\n2,Second,\n";
+        let rows = parse::<Post>(&POST, Format::Csv, valid.as_bytes()).unwrap();
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(rows[0].1.title, "Hello, world");
+        assert_eq!(rows[1].1.score, None);
+
+        let ndjson = "{\"title\":\"a\"}\n\n{\"score\":1}\n";
+        let report = parse::<Post>(&POST, Format::Ndjson, ndjson.as_bytes()).unwrap_err();
+        assert_eq!(report.errors[0].line, 3);
+    }
+
+    #[test]
+    fn builds_batched_inserts() {
+        assert_eq!(insert_sql("post", &["a", "b"], 2, false), "INSERT INTO post (a, b) VALUES (?, ?), (?, ?)");
+        assert_eq!(insert_sql("post", &["a"], 2, true), "INSERT INTO post (a) VALUES ($1), ($2)");
+        assert_eq!(batch_size(20), 45);
+    }
+
+    #[actix_web::test]
+    async fn exports_pages_without_hidden_fields() {
+        let rows: Vec<_> = (1..=PAGE_SIZE + 1)
+            .map(|id| serde_json::json!({ "id": id, "slug": format!("p{}", id), "title": "T", "score": null, "edit_token": "secret" }))
+            .collect();
+        let export = |format| {
+            let rows = rows.clone();
+            export_response(&POST, format, move |after| {
+                let page: Vec<_> = rows.iter().filter(|r| r["id"].as_i64() > Some(after)).take(PAGE_SIZE as usize).cloned().collect();
+                async move { Ok(page) }
+            })
+        };
+
+        let csv = actix_web::body::to_bytes(export(Format::Csv).into_body()).await.unwrap();
+        let csv = String::from_utf8(csv.to_vec()).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len() as i64, PAGE_SIZE + 2);
+        assert_eq!(lines[0], "id,slug,title,score");
+        assert_eq!(lines[1], "1,p1,T,");
+
+        let ndjson = actix_web::body::to_bytes(export(Format::Ndjson).into_body()).await.unwrap();
+        let first = String::from_utf8(ndjson.to_vec()).unwrap().lines().next().unwrap().to_string();
+        assert_eq!(first, r#"{"id":1,"slug":"p1","title":"T","score":null}"#);
+    }
+}
@@ -0,0 +1,213 @@
+//! Response cache for resources declared with `#[rest_api(cache(ttl = 60))]`.
+//!
+//! The generated `get_all` and `get_one` handlers store their encoded responses
+//! here, keyed by the query, the wire format and the caller's authorization
+//! scope, and every create, update, patch and delete of the resource evicts
+//! its entries. The cache sits after the role and permission checks, so a hit
+//! is only served to callers who may read the resource.
+//!
+//! Without a [`ResponseCache`] registered as app data, a process-wide in-memory
+//! LRU of 1000 entries is used. Other stores, such as Redis, plug in through
+//! [`CacheBackend`]:
+//!
+//! ```rust,ignore
+//! App::new().app_data(web::Data::new(ResponseCache::new(MyRedisCache::new(client))))
+//! ```
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::future::BoxFuture;
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::auth::UserContext;
+use crate::codec::Codec;
+
+/// Entries kept by the default in-memory cache
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Storage for cached responses.
+///
+/// Entries belong to a resource (its table name), and [`invalidate`](Self::invalidate)
+/// must make every entry of that resource unreachable, for example by bumping
+/// a per-resource version that is part of the stored key.
+pub trait CacheBackend: Send + Sync {
+    fn get<'a>(&'a self, resource: &'a str, key: &'a str) -> BoxFuture<'a, Option<Bytes>>;
+
+    fn set<'a>(&'a self, resource: &'a str, key: &'a str, value: Bytes, ttl: Duration) -> BoxFuture<'a, ()>;
+
+    fn invalidate<'a>(&'a self, resource: &'a str) -> BoxFuture<'a, ()>;
+}
+
+struct MemoryEntries {
+    entries: LruCache<(String, u64, String), (Instant, Bytes)>,
+    /// Bumped by `invalidate`, so older entries are never read again and age out of the LRU
+    generations: HashMap<String, u64>,
+}
+
+/// In-memory LRU cache, local to the process
+pub struct MemoryCache {
+    inner: Mutex<MemoryEntries>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            inner: Mutex::new(MemoryEntries { entries: LruCache::new(capacity), generations: HashMap::new() }),
+        }
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl MemoryEntries {
+    fn key(&self, resource: &str, key: &str) -> (String, u64, String) {
+        let generation = self.generations.get(resource).copied().unwrap_or_default();
+        (resource.to_string(), generation, key.to_string())
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get<'a>(&'a self, resource: &'a str, key: &'a str) -> BoxFuture<'a, Option<Bytes>> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = inner.key(resource, key);
+        let value = match inner.entries.get(&key) {
+            Some((expires, value)) if *expires > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                inner.entries.pop(&key);
+                None
+            }
+            None => None,
+        };
+        Box::pin(async move { value })
+    }
+
+    fn set<'a>(&'a self, resource: &'a str, key: &'a str, value: Bytes, ttl: Duration) -> BoxFuture<'a, ()> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = inner.key(resource, key);
+        inner.entries.put(key, (Instant::now() + ttl, value));
+        Box::pin(async {})
+    }
+
+    fn invalidate<'a>(&'a self, resource: &'a str) -> BoxFuture<'a, ()> {
+        *self.inner.lock().unwrap().generations.entry(resource.to_string()).or_default() += 1;
+        Box::pin(async {})
+    }
+}
+
+/// The cache used by generated handlers, registered as `web::Data<ResponseCache>`
+#[derive(Clone)]
+pub struct ResponseCache {
+    backend: Arc<dyn CacheBackend>,
+}
+
+impl ResponseCache {
+    pub fn new(backend: impl CacheBackend + 'static) -> Self {
+        Self { backend: Arc::new(backend) }
+    }
+
+    /// An in-memory LRU holding up to `capacity` responses
+    pub fn memory(capacity: usize) -> Self {
+        Self::new(MemoryCache::new(capacity))
+    }
+
+    /// The cache that applies to a request
+    pub fn from_request(req: &HttpRequest) -> &ResponseCache {
+        match req.app_data::<web::Data<ResponseCache>>() {
+            Some(cache) => cache.get_ref(),
+            None => Self::process_default(),
+        }
+    }
+
+    /// The in-memory cache used when no `ResponseCache` is registered
+    pub fn process_default() -> &'static ResponseCache {
+        static DEFAULT: OnceLock<ResponseCache> = OnceLock::new();
+        DEFAULT.get_or_init(|| Self::memory(DEFAULT_CAPACITY))
+    }
+
+    pub async fn get(&self, resource: &str, key: &str) -> Option<Bytes> {
+        self.backend.get(resource, key).await
+    }
+
+    /// Encode `value`, cache it for `ttl` and return it as the response
+    pub async fn store<T: Serialize>(&self, resource: &str, key: &str, ttl: Duration, codec: Codec, value: &T) -> HttpResponse {
+        match codec.encode(value) {
+            Ok(body) => {
+                let body = Bytes::from(body);
+                self.backend.set(resource, key, body.clone(), ttl).await;
+                cached_response(codec, body, "MISS")
+            }
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    }
+
+    /// Evict every cached response of `resource`
+    pub async fn invalidate(&self, resource: &str) {
+        self.backend.invalidate(resource).await;
+    }
+}
+
+/// Response for a cache hit
+pub fn hit(codec: Codec, body: Bytes) -> HttpResponse {
+    cached_response(codec, body, "HIT")
+}
+
+fn cached_response(codec: Codec, body: Bytes, status: &'static str) -> HttpResponse {
+    let mut response = codec.response(body);
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static("x-cache"), HeaderValue::from_static(status));
+    response
+}
+
+/// Cache key for an operation: `list` or `get:<id>`, the response format, the
+/// caller's roles and permissions, and the query string.
+///
+/// Callers with the same roles and permissions share entries, and anonymous
+/// callers share the `public` scope.
+pub fn key(operation: &str, codec: Codec, user: Option<&UserContext>, query: &str) -> String {
+    let scope = match user {
+        None => "public".to_string(),
+        Some(user) => {
+            let mut roles = user.roles.clone();
+            roles.sort();
+            let mut permissions = user.permissions.clone();
+            permissions.sort();
+            format!("{}|{}", roles.join(","), permissions.join(","))
+        }
+    };
+    format!("{} {} {} ?{}", operation, codec.media_type(), scope, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn expires_evicts_and_invalidates() {
+        let cache = MemoryCache::new(2);
+        let ttl = Duration::from_secs(60);
+
+        cache.set("post", "a", Bytes::from_static(b"1"), ttl).await;
+        cache.set("comment", "a", Bytes::from_static(b"2"), ttl).await;
+        assert_eq!(cache.get("post", "a").await, Some(Bytes::from_static(b"1")));
+
+        cache.invalidate("post").await;
+        assert_eq!(cache.get("post", "a").await, None);
+        assert_eq!(cache.get("comment", "a").await, Some(Bytes::from_static(b"2")));
+
+        cache.set("post", "b", Bytes::from_static(b"3"), Duration::ZERO).await;
+        assert_eq!(cache.get("post", "b").await, None);
+    }
+}
@@ -0,0 +1,178 @@
+//! HTTP client for calling a very_simple_rest API from Rust, enabled by the
+//! `client` feature.
+//!
+//! With the feature on, `#[derive(RestApi)]` also generates a `PostClient` for
+//! each resource. Every client shares one [`ApiClient`], so the token stored by
+//! [`AuthClient::login`] is sent by all of them.
+//!
+//! ```rust,ignore
+//! let api = ApiClient::new("http://localhost:8080/api");
+//! AuthClient::new(api.clone()).login("admin@example.com", "secret").await?;
+//!
+//! let posts = PostClient::new(api.clone());
+//! for post in posts.list().await? {
+//!     println!("{}", post.title);
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{LoginInput, RegisterInput, UserContext};
+
+pub use reqwest::Method;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or the response could not be decoded
+    Http(reqwest::Error),
+    /// The server answered with a non-success status
+    Status { status: u16, message: String },
+}
+
+impl ClientError {
+    /// Status code of a rejected request
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Http(e) => e.status().map(|s| s.as_u16()),
+            ClientError::Status { status, .. } => Some(*status),
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "HTTP error: {}", e),
+            ClientError::Status { status, message } if message.is_empty() => {
+                write!(f, "Request failed with status {}", status)
+            }
+            ClientError::Status { status, message } => {
+                write!(f, "Request failed with status {}: {}", status, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+/// Base URL, HTTP connection pool and bearer token shared by the typed clients
+#[derive(Clone)]
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: Arc<str>,
+    token: Arc<RwLock<Option<String>>>,
+}
+
+impl ApiClient {
+    /// `base_url` is the scope the resources are mounted in, such as `http://localhost:8080/api`
+    pub fn new(base_url: impl AsRef<str>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured `reqwest::Client`, for timeouts or custom TLS
+    pub fn with_http_client(base_url: impl AsRef<str>, http: reqwest::Client) -> Self {
+        Self {
+            http,
+            base_url: base_url.as_ref().trim_end_matches('/').into(),
+            token: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    pub fn set_token(&self, token: Option<String>) {
+        *self.token.write().unwrap() = token;
+    }
+
+    /// Start a request to `path` under the base URL, with the bearer token if one is set
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.http.request(method, format!("{}{}", self.base_url, path));
+        match self.token() {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    /// Send a request, turning non-success statuses into [`ClientError::Status`]
+    pub async fn send(&self, builder: RequestBuilder) -> Result<Response, ClientError> {
+        let response = builder.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(response)
+        } else {
+            let message = response.text().await.unwrap_or_default();
+            Err(ClientError::Status { status: status.as_u16(), message })
+        }
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        Ok(self.send(self.request(Method::GET, path)).await?.json().await?)
+    }
+
+    /// Send a JSON body and ignore the response body
+    pub async fn send_json<B: Serialize + ?Sized>(&self, method: Method, path: &str, body: &B) -> Result<(), ClientError> {
+        self.send(self.request(method, path).json(body)).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<(), ClientError> {
+        self.send(self.request(Method::DELETE, path)).await?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+/// Client for the routes registered by `auth_routes`
+#[derive(Clone)]
+pub struct AuthClient {
+    api: ApiClient,
+}
+
+impl AuthClient {
+    pub fn new(api: ApiClient) -> Self {
+        Self { api }
+    }
+
+    pub async fn register(&self, email: &str, password: &str) -> Result<(), ClientError> {
+        let input = RegisterInput { email: email.to_string(), password: password.to_string() };
+        self.api.send_json(Method::POST, "/auth/register", &input).await
+    }
+
+    /// Log in and store the token on the shared [`ApiClient`]
+    pub async fn login(&self, email: &str, password: &str) -> Result<String, ClientError> {
+        let input = LoginInput { email: email.to_string(), password: password.to_string() };
+        let response = self.api.send(self.api.request(Method::POST, "/auth/login").json(&input)).await?;
+        let TokenResponse { token } = response.json().await?;
+        self.api.set_token(Some(token.clone()));
+        Ok(token)
+    }
+
+    pub fn logout(&self) {
+        self.api.set_token(None);
+    }
+
+    pub async fn me(&self) -> Result<UserContext, ClientError> {
+        self.api.get_json("/auth/me").await
+    }
+}
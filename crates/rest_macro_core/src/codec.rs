@@ -0,0 +1,223 @@
+//! Wire formats for the generated handlers, chosen per request.
+//!
+//! Rows are read with the [`Codec`] picked from the `Accept` header, and
+//! payloads are decoded by [`Payload`] according to their `Content-Type`.
+//! JSON is always available and is the default. MessagePack
+//! (`application/msgpack`) and CBOR (`application/cbor`) are added by the
+//! `msgpack` and `cbor` features.
+//!
+//! A request that accepts nothing supported still gets JSON, and a body in a
+//! format that is not compiled in is rejected by `web::Json` as before.
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::ops::Deref;
+
+use actix_web::dev::Payload as RequestPayload;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A wire format for rows and payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Codec {
+    /// The codecs compiled in, JSON first
+    pub const ALL: &'static [Codec] = &[
+        Codec::Json,
+        #[cfg(feature = "msgpack")]
+        Codec::MessagePack,
+        #[cfg(feature = "cbor")]
+        Codec::Cbor,
+    ];
+
+    /// The media type sent in `Content-Type`
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Codec::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => "application/msgpack",
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => "application/cbor",
+        }
+    }
+
+    /// The codec for a media type without parameters, including common aliases
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" => Some(Codec::Json),
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Codec::MessagePack),
+            #[cfg(feature = "cbor")]
+            "application/cbor" => Some(Codec::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The preferred codec in an `Accept` header, or JSON when none is acceptable
+    pub fn from_accept(accept: Option<&HeaderValue>) -> Self {
+        let Some(accept) = accept.and_then(|v| v.to_str().ok()) else {
+            return Codec::Json;
+        };
+
+        let mut best = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let media_type = parts.next().unwrap_or("").trim();
+            let quality = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality <= 0.0 {
+                continue;
+            }
+            // Wildcards take the default
+            let codec = match media_type {
+                "*/*" | "application/*" => Some(Codec::Json),
+                other => Self::from_media_type(other),
+            };
+            let Some(codec) = codec else { continue };
+            // Highest quality wins, then the earliest listed
+            if best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, codec));
+            }
+        }
+        best.map_or(Codec::Json, |(_, codec)| codec)
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            // Named fields keep maps keyed like the JSON objects
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => {
+                let mut body = Vec::new();
+                ciborium::into_writer(value, &mut body).map_err(|e| e.to_string())?;
+                Ok(body)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, String> {
+        match self {
+            Codec::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => rmp_serde::from_slice(body).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => ciborium::from_reader(body).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// `200 OK` with `value` in this format
+    pub fn ok<T: Serialize + ?Sized>(self, value: &T) -> HttpResponse {
+        match self.encode(value) {
+            Ok(body) => self.response(body.into()),
+            Err(e) => HttpResponse::InternalServerError().body(e),
+        }
+    }
+
+    /// `200 OK` with a body already in this format, as stored by the response cache
+    pub fn response(self, body: web::Bytes) -> HttpResponse {
+        HttpResponse::Ok()
+            .content_type(self.media_type())
+            .insert_header((header::VARY, "Accept"))
+            .body(body)
+    }
+}
+
+/// The codec for the response, from the `Accept` header
+impl FromRequest for Codec {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut RequestPayload) -> Self::Future {
+        ready(Ok(Codec::from_accept(req.headers().get(header::ACCEPT))))
+    }
+}
+
+/// A request body in any compiled-in format, used by the generated handlers
+/// in place of `web::Json`.
+///
+/// JSON bodies go through `web::Json`, so a `JsonConfig` registered as app
+/// data still applies. Other formats are read with the `PayloadConfig` limit.
+pub struct Payload<T>(pub T);
+
+impl<T> Payload<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Payload<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Payload<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut RequestPayload) -> Self::Future {
+        let codec = req.mime_type().ok().flatten().and_then(|mime| Codec::from_media_type(mime.essence_str()));
+        match codec {
+            Some(codec) if codec != Codec::Json => {
+                let body = web::Bytes::from_request(req, payload);
+                Box::pin(async move {
+                    let body = body.await?;
+                    codec.decode(&body).map(Payload).map_err(actix_web::error::ErrorBadRequest)
+                })
+            }
+            _ => {
+                let json = web::Json::<T>::from_request(req, payload);
+                Box::pin(async move { json.await.map(|json| Payload(json.into_inner())) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &'static str) -> Codec {
+        Codec::from_accept(Some(&HeaderValue::from_static(value)))
+    }
+
+    #[test]
+    fn negotiates_accept() {
+        assert_eq!(Codec::from_accept(None), Codec::Json);
+        assert_eq!(accept("*/*"), Codec::Json);
+        assert_eq!(accept("text/html, application/xml;q=0.9"), Codec::Json);
+        assert_eq!(accept("application/json;q=0"), Codec::Json);
+
+        #[cfg(feature = "msgpack")]
+        {
+            assert_eq!(accept("application/msgpack"), Codec::MessagePack);
+            assert_eq!(accept("application/json;q=0.5, application/x-msgpack"), Codec::MessagePack);
+            assert_eq!(accept("application/json, application/msgpack"), Codec::Json);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let value = serde_json::json!({ "id": 1, "title": "Hello", "rating": 4.5, "draft": null });
+        for codec in Codec::ALL {
+            let body = codec.encode(&value).unwrap();
+            assert_eq!(codec.decode::<serde_json::Value>(&body).unwrap(), value);
+        }
+    }
+}
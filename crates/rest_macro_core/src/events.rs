@@ -0,0 +1,336 @@
+//! Change events emitted by the generated write handlers.
+//!
+//! Every successful create, update, patch and delete is published to a
+//! process-wide bus. Each resource with `list` enabled streams them at
+//! `GET /{table}/events` (Server-Sent Events) and `GET /{table}/events/ws`
+//! (WebSocket), behind the same checks as `list`.
+//!
+//! The last [`HISTORY`] events are kept, so a reconnecting client that sends
+//! `Last-Event-ID` (or `?last_event_id=` on the WebSocket) receives what it
+//! missed. When the gap is larger than that, or the id is not one this process
+//! issued, it receives a `reset` event and should reload the collection. Ids
+//! start at the process's start time in microseconds, so the ids of an earlier
+//! run always fall before the history of a restarted server.
+//!
+//! Fields a resource marks `#[rest_api(hidden)]` are removed from event data.
+//!
+//! Consumers that must see every event, such as webhook dispatch, [`listen`]
+//! instead: their queue is unbounded rather than limited to the history.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::rt::time::timeout;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::future::{select, Either};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::resource;
+
+/// Number of past events kept for clients resuming with `Last-Event-ID`
+pub const HISTORY: usize = 1024;
+
+/// Idle time after which a comment is sent to keep proxies from closing the stream
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// One change to a resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Increases by one with every event in the process
+    pub id: u64,
+    /// Table of the changed resource
+    pub resource: String,
+    /// `create`, `upsert`, `update`, `patch` or `delete`
+    pub action: String,
+    /// Primary key of the changed row, when known
+    pub record_id: Option<i64>,
+    /// The submitted payload: the full row for create and update, the changed
+    /// fields for patch. Hidden fields are left out.
+    pub data: Option<serde_json::Value>,
+}
+
+struct EventBus {
+    sender: broadcast::Sender<Arc<ChangeEvent>>,
+    history: Mutex<VecDeque<Arc<ChangeEvent>>>,
+    listeners: Mutex<Vec<mpsc::UnboundedSender<Arc<ChangeEvent>>>>,
+    next_id: AtomicU64,
+}
+
+fn bus() -> &'static EventBus {
+    static BUS: OnceLock<EventBus> = OnceLock::new();
+    BUS.get_or_init(|| EventBus {
+        sender: broadcast::channel(HISTORY).0,
+        history: Mutex::new(VecDeque::with_capacity(HISTORY)),
+        listeners: Mutex::new(vec![]),
+        next_id: AtomicU64::new(first_id()),
+    })
+}
+
+/// The process start time in microseconds, so ids never repeat across restarts
+fn first_id() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |since| since.as_micros() as u64)
+}
+
+/// Publish a change without a payload, as for deletes
+pub fn publish(resource: &str, action: &str, record_id: Option<i64>) {
+    emit(resource, action, record_id, None);
+}
+
+/// Publish a change with the payload that caused it
+pub fn publish_with<T: Serialize + ?Sized>(resource: &str, action: &str, record_id: Option<i64>, data: &T) {
+    let mut data = serde_json::to_value(data).ok();
+    if let Some(meta) = resource::lookup(resource)
+        && let Some(data) = &mut data
+    {
+        meta.redact(data);
+    }
+    emit(resource, action, record_id, data);
+}
+
+fn emit(resource: &str, action: &str, record_id: Option<i64>, data: Option<serde_json::Value>) {
+    let bus = bus();
+    // Holding the history lock keeps ids, history and broadcast order in step
+    let mut history = bus.history.lock().unwrap();
+    let event = Arc::new(ChangeEvent {
+        id: bus.next_id.fetch_add(1, Ordering::Relaxed),
+        resource: resource.to_string(),
+        action: action.to_string(),
+        record_id,
+        data,
+    });
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(event.clone());
+    // Dropped listeners are forgotten
+    bus.listeners.lock().unwrap().retain(|listener| listener.send(event.clone()).is_ok());
+    // No receivers is not an error
+    let _ = bus.sender.send(event);
+}
+
+/// What a subscriber receives next
+#[derive(Debug, Clone)]
+pub enum Update {
+    Event(Arc<ChangeEvent>),
+    /// Events were missed; reload the collection
+    Reset,
+}
+
+/// A stream of events for one resource
+pub struct Subscription {
+    resource: String,
+    backlog: VecDeque<Update>,
+    receiver: broadcast::Receiver<Arc<ChangeEvent>>,
+}
+
+/// Subscribe to `resource`, first replaying the events after `last_event_id`
+pub fn subscribe(resource: &str, last_event_id: Option<u64>) -> Subscription {
+    let bus = bus();
+    let history = bus.history.lock().unwrap();
+    let receiver = bus.sender.subscribe();
+
+    let mut backlog = VecDeque::new();
+    if let Some(last) = last_event_id {
+        let next_id = bus.next_id.load(Ordering::Relaxed);
+        let oldest = history.front().map(|e| e.id).unwrap_or(next_id);
+        // Too old to replay, or issued by another process
+        if last.saturating_add(1) < oldest || last >= next_id {
+            backlog.push_back(Update::Reset);
+        }
+        backlog.extend(
+            history
+                .iter()
+                .filter(|e| e.id > last && e.resource == resource)
+                .cloned()
+                .map(Update::Event),
+        );
+    }
+
+    Subscription { resource: resource.to_string(), backlog, receiver }
+}
+
+/// Receive every resource's events from now on, without ever missing one, as
+/// webhook dispatch does. Events queue up until they are received.
+pub fn listen() -> mpsc::UnboundedReceiver<Arc<ChangeEvent>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    bus().listeners.lock().unwrap().push(sender);
+    receiver
+}
+
+impl Subscription {
+    /// The next update, or `None` once the bus is gone
+    pub async fn next(&mut self) -> Option<Update> {
+        if let Some(update) = self.backlog.pop_front() {
+            return Some(update);
+        }
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.resource == self.resource => {
+                    return Some(Update::Event(event));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => return Some(Update::Reset),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+fn sse_frame(update: &Update) -> Bytes {
+    match update {
+        Update::Event(event) => Bytes::from(format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            event.id,
+            event.action,
+            serde_json::to_string(event.as_ref()).unwrap_or_default()
+        )),
+        Update::Reset => Bytes::from_static(b"event: reset\ndata: {}\n\n"),
+    }
+}
+
+fn ws_message(update: &Update) -> String {
+    match update {
+        Update::Event(event) => serde_json::to_string(event.as_ref()).unwrap_or_default(),
+        Update::Reset => r#"{"action":"reset"}"#.to_string(),
+    }
+}
+
+/// `Last-Event-ID` header, or the `last_event_id` query parameter for clients that cannot set headers
+fn last_event_id(req: &HttpRequest) -> Option<u64> {
+    let header = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    header.or_else(|| {
+        web::Query::<LastEventQuery>::from_query(req.query_string())
+            .ok()
+            .and_then(|q| q.last_event_id)
+    })
+}
+
+#[derive(Deserialize)]
+struct LastEventQuery {
+    last_event_id: Option<u64>,
+}
+
+/// Stream `resource` events as Server-Sent Events. Used by the generated `/{table}/events`.
+pub fn sse_response(req: &HttpRequest, resource: &str) -> HttpResponse {
+    let subscription = subscribe(resource, last_event_id(req));
+
+    let body = stream::unfold(subscription, |mut subscription| async move {
+        let frame = match timeout(KEEP_ALIVE, subscription.next()).await {
+            Ok(Some(update)) => sse_frame(&update),
+            Ok(None) => return None,
+            Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((Ok::<_, actix_web::Error>(frame), subscription))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body)
+}
+
+/// Stream `resource` events as JSON text messages over a WebSocket. Used by the
+/// generated `/{table}/events/ws`.
+pub fn websocket_response(req: &HttpRequest, body: web::Payload, resource: &str) -> Result<HttpResponse, actix_web::Error> {
+    let (response, mut session, mut messages) = actix_ws::handle(req, body)?;
+    let mut subscription = subscribe(resource, last_event_id(req));
+
+    actix_web::rt::spawn(async move {
+        loop {
+            let next_update = std::pin::pin!(subscription.next());
+            let next_message = std::pin::pin!(messages.recv());
+            match select(next_update, next_message).await {
+                Either::Left((Some(update), _)) => {
+                    if session.text(ws_message(&update)).await.is_err() {
+                        return;
+                    }
+                }
+                Either::Left((None, _)) => break,
+                Either::Right((Some(Ok(actix_ws::Message::Ping(bytes))), _)) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Either::Right((Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None, _)) => break,
+                // Subscribers only listen
+                Either::Right((Some(Ok(_)), _)) => {}
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::POST;
+
+    #[actix_web::test]
+    async fn replays_after_last_event_id() {
+        publish("replay_test", "create", Some(1));
+        let first = bus().next_id.load(Ordering::Relaxed) - 1;
+        publish("replay_other", "create", Some(1));
+        publish_with("replay_test", "patch", Some(1), &serde_json::json!({ "title": "x" }));
+
+        let mut subscription = subscribe("replay_test", Some(first));
+        let Some(Update::Event(event)) = subscription.next().await else {
+            panic!("expected a replayed event");
+        };
+        assert_eq!(event.action, "patch");
+        assert_eq!(event.data, Some(serde_json::json!({ "title": "x" })));
+
+        publish("replay_test", "delete", Some(1));
+        let Some(Update::Event(event)) = subscription.next().await else {
+            panic!("expected a live event");
+        };
+        assert_eq!(event.action, "delete");
+    }
+
+    #[actix_web::test]
+    async fn resets_ids_from_other_processes() {
+        let next_id = bus().next_id.load(Ordering::Relaxed);
+        assert!(matches!(subscribe("reset_test", Some(next_id + 100)).next().await, Some(Update::Reset)));
+        // Ids from a run that started earlier predate the history
+        assert!(matches!(subscribe("reset_test", Some(1)).next().await, Some(Update::Reset)));
+    }
+
+    #[actix_web::test]
+    async fn leaves_out_hidden_fields() {
+        resource::register(&POST);
+        let after = bus().next_id.load(Ordering::Relaxed) - 1;
+        publish_with("post", "create", Some(1), &serde_json::json!({ "title": "x", "edit_token": "secret" }));
+
+        let Some(Update::Event(event)) = subscribe("post", Some(after)).next().await else {
+            panic!("expected a replayed event");
+        };
+        assert_eq!(event.data, Some(serde_json::json!({ "title": "x" })));
+    }
+
+    #[actix_web::test]
+    async fn listeners_receive_more_than_the_history() {
+        let mut listener = listen();
+        for id in 0..HISTORY as i64 + 10 {
+            publish("listen_test", "create", Some(id));
+        }
+
+        let mut received = 0;
+        while let Ok(event) = listener.try_recv() {
+            if event.resource == "listen_test" {
+                assert_eq!(event.record_id, Some(received));
+                received += 1;
+            }
+        }
+        assert_eq!(received, HISTORY as i64 + 10);
+    }
+}
@@ -0,0 +1,376 @@
+//! File fields declared with `#[rest_api(file(max_size = "10MB", types = "image/*"))]`.
+//!
+//! A file field has the type `Option<StoredFile>`. Its column holds the file's
+//! metadata as JSON, and the content lives in a [`BlobStore`]. The generated
+//! `create` and `update` also accept `multipart/form-data`: file parts are
+//! checked against the field's size and type rules, stored once the caller is
+//! authorized, and their metadata is written to the row. Downloads are served
+//! at `GET /{table}/{id}/{field}` behind the `get` checks.
+//!
+//! Without a [`FileStorage`] registered as app data, files are kept under
+//! `./uploads`. Other stores, such as S3, plug in through [`BlobStore`]:
+//!
+//! ```rust,ignore
+//! App::new().app_data(web::Data::new(FileStorage::local("/var/lib/blog/files")))
+//! ```
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use actix_multipart::{Field, Multipart};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{dev, error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::{BoxFuture, LocalBoxFuture};
+use futures_util::TryStreamExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::{Database, Decode, Encode, Type};
+
+use crate::bulk;
+use crate::codec::Payload;
+use crate::resource::{FileMeta, Resource, ResourceMeta};
+
+/// Directory used when no `FileStorage` is registered
+pub const DEFAULT_DIR: &str = "uploads";
+
+/// Largest accepted text part of a multipart payload
+const TEXT_LIMIT: usize = 64 * 1024;
+
+/// Metadata of an uploaded file, kept in the row as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredFile {
+    /// Where the content is in the `BlobStore`
+    pub key: String,
+    /// Name the client uploaded it as
+    pub filename: String,
+    pub content_type: String,
+    /// Size in bytes
+    pub size: i64,
+}
+
+// Stored as a JSON string in a TEXT column on every backend
+impl<DB: Database> Type<DB> for StoredFile
+where
+    String: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for StoredFile
+where
+    String: Decode<'r, DB>,
+{
+    fn decode(value: DB::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        let text = <String as Decode<DB>>::decode(value)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for StoredFile
+where
+    String: Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut DB::ArgumentBuffer<'q>) -> Result<IsNull, BoxDynError> {
+        <String as Encode<DB>>::encode(serde_json::to_string(self)?, buf)
+    }
+}
+
+/// Storage for file contents, addressed by keys such as `post/attachment/3f2a...`
+pub trait BlobStore: Send + Sync {
+    fn put<'a>(&'a self, key: &'a str, body: Bytes) -> BoxFuture<'a, io::Result<()>>;
+
+    /// The content, or `None` if nothing is stored under `key`
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<Bytes>>>;
+
+    /// Remove the content; deleting a missing key is not an error
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>>;
+}
+
+/// Files in a directory on the local filesystem
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Keys are relative paths that must stay inside the root
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid blob key `{}`", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put<'a>(&'a self, key: &'a str, body: Bytes) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let path = self.path(key)?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            // Written aside and renamed, so readers never see a partial file
+            let partial = path.with_extension("partial");
+            tokio::fs::write(&partial, &body).await?;
+            tokio::fs::rename(&partial, &path).await
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<Bytes>>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.path(key)?).await {
+                Ok(body) => Ok(Some(body.into())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.path(key)?).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+/// The blob store used by generated handlers, registered as `web::Data<FileStorage>`
+#[derive(Clone)]
+pub struct FileStorage {
+    store: Arc<dyn BlobStore>,
+}
+
+impl FileStorage {
+    pub fn new(store: impl BlobStore + 'static) -> Self {
+        Self { store: Arc::new(store) }
+    }
+
+    /// Files under `root` on the local filesystem
+    pub fn local(root: impl Into<PathBuf>) -> Self {
+        Self::new(LocalBlobStore::new(root))
+    }
+
+    /// The storage that applies to a request
+    pub fn from_request(req: &HttpRequest) -> &FileStorage {
+        match req.app_data::<web::Data<FileStorage>>() {
+            Some(storage) => storage.get_ref(),
+            None => Self::process_default(),
+        }
+    }
+
+    /// Local storage in [`DEFAULT_DIR`], used when no `FileStorage` is registered
+    pub fn process_default() -> &'static FileStorage {
+        static DEFAULT: OnceLock<FileStorage> = OnceLock::new();
+        DEFAULT.get_or_init(|| Self::local(DEFAULT_DIR))
+    }
+
+    /// Store uploaded files of `resource`, returning each field with its metadata.
+    /// If one fails, those already stored are removed again.
+    pub async fn store(&self, resource: &str, files: Vec<PendingFile>) -> io::Result<Vec<(&'static str, StoredFile)>> {
+        let mut stored = vec![];
+        for file in files {
+            let key = format!("{}/{}/{:032x}", resource, file.field, rand::random::<u128>());
+            if let Err(e) = self.store.put(&key, file.body.clone()).await {
+                self.remove(stored.iter().map(|(_, f)| f)).await;
+                return Err(e);
+            }
+            stored.push((
+                file.field,
+                StoredFile { key, filename: file.filename, content_type: file.content_type, size: file.body.len() as i64 },
+            ));
+        }
+        Ok(stored)
+    }
+
+    /// Remove the contents of files that are no longer referenced. Failures are
+    /// logged rather than returned, since the row change they follow has happened.
+    pub async fn remove<'a>(&self, files: impl IntoIterator<Item = &'a StoredFile>) {
+        for file in files {
+            if let Err(e) = self.store.delete(&file.key).await {
+                log::warn!("Could not delete blob {}: {}", file.key, e);
+            }
+        }
+    }
+
+    /// Serve a file as an attachment, or `404 Not Found` if the field is empty
+    pub async fn download(&self, file: Option<&StoredFile>) -> HttpResponse {
+        let Some(file) = file else {
+            return HttpResponse::NotFound().finish();
+        };
+        match self.store.get(&file.key).await {
+            Ok(Some(body)) => HttpResponse::Ok()
+                .content_type(file.content_type.as_str())
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(file.filename.clone())],
+                })
+                .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+                .body(body),
+            Ok(None) => HttpResponse::NotFound().finish(),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    }
+}
+
+/// A file part of a multipart payload, read and checked but not yet stored
+pub struct PendingFile {
+    pub field: &'static str,
+    pub filename: String,
+    pub content_type: String,
+    pub body: Bytes,
+}
+
+/// A create or update payload of a resource with file fields: a body in any
+/// [`Codec`](crate::codec::Codec), or `multipart/form-data` with a part per
+/// field.
+///
+/// Multipart text parts are parsed by column type like CSV cells. File parts
+/// are checked against their field's `max_size` and `types` and held in
+/// memory until the handler stores them.
+pub struct Upload<T> {
+    item: T,
+    files: Vec<PendingFile>,
+}
+
+impl<T> Upload<T> {
+    pub fn into_parts(self) -> (T, Vec<PendingFile>) {
+        (self.item, self.files)
+    }
+}
+
+impl<T: Resource + DeserializeOwned + 'static> FromRequest for Upload<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let multipart = req.mime_type().ok().flatten().is_some_and(|mime| mime.essence_str() == "multipart/form-data");
+        if multipart {
+            let parts = Multipart::new(req.headers(), payload.take());
+            Box::pin(read_multipart(T::metadata(), parts))
+        } else {
+            let body = Payload::<T>::from_request(req, payload);
+            Box::pin(async move { Ok(Upload { item: body.await?.into_inner(), files: vec![] }) })
+        }
+    }
+}
+
+async fn read_multipart<T: DeserializeOwned>(meta: &'static ResourceMeta, mut parts: Multipart) -> Result<Upload<T>, actix_web::Error> {
+    let mut object = Map::new();
+    let mut files: Vec<PendingFile> = vec![];
+
+    while let Some(mut part) = parts.try_next().await? {
+        let name = part.name().unwrap_or_default().to_string();
+        if let Some(rule) = meta.file(&name) {
+            if files.iter().any(|f| f.field == rule.field) {
+                return Err(error::ErrorBadRequest(format!("`{}` was sent more than once", name)));
+            }
+            files.push(read_file(rule, &mut part).await?);
+        } else if let Some(column) = meta.column(&name) {
+            let body = read_limited(&mut part, TEXT_LIMIT as u64)
+                .await?
+                .ok_or_else(|| error::ErrorPayloadTooLarge(format!("`{}` is longer than {} bytes", name, TEXT_LIMIT)))?;
+            let text = std::str::from_utf8(&body).map_err(|_| error::ErrorBadRequest(format!("`{}` is not UTF-8", name)))?;
+            let value = bulk::text_value(column.column_type, text.trim())
+                .map_err(|expected| error::ErrorBadRequest(format!("`{}` must be {}, got `{}`", name, expected, text)))?;
+            object.insert(name, value);
+        } else {
+            return Err(error::ErrorBadRequest(format!("unknown field `{}`", name)));
+        }
+    }
+
+    let item = serde_json::from_value(Value::Object(object)).map_err(error::ErrorBadRequest)?;
+    Ok(Upload { item, files })
+}
+
+async fn read_file(rule: &'static FileMeta, part: &mut Field) -> Result<PendingFile, actix_web::Error> {
+    let content_type = part
+        .content_type()
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    if !rule.accepts(&content_type) {
+        return Err(error::ErrorUnsupportedMediaType(format!(
+            "`{}` accepts {}, not {}",
+            rule.field,
+            rule.types.join(", "),
+            content_type
+        )));
+    }
+    let filename = part
+        .content_disposition()
+        .and_then(|cd| cd.get_filename())
+        .map(file_name)
+        .unwrap_or_default();
+
+    let body = read_limited(part, rule.max_size)
+        .await?
+        .ok_or_else(|| error::ErrorPayloadTooLarge(format!("`{}` is larger than {} bytes", rule.field, rule.max_size)))?;
+    let filename = if filename.is_empty() { rule.field.to_string() } else { filename };
+    Ok(PendingFile { field: rule.field, filename, content_type, body })
+}
+
+/// The whole part, or `None` once it grows past `limit` bytes
+async fn read_limited(part: &mut Field, limit: u64) -> Result<Option<Bytes>, actix_web::Error> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = part.try_next().await? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Some(body.freeze()))
+}
+
+/// The last segment of an uploaded file name, without control characters
+fn file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    base.chars().filter(|c| !c.is_control()).collect::<String>().trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_content_types() {
+        let rule = FileMeta { field: "attachment", max_size: 10, types: &["image/*", "application/pdf"] };
+        assert!(rule.accepts("image/png"));
+        assert!(rule.accepts("Application/PDF"));
+        assert!(!rule.accepts("text/html"));
+        assert!(!rule.accepts("imagex/png"));
+        assert!(FileMeta { field: "any", max_size: 10, types: &[] }.accepts("text/html"));
+    }
+
+    #[actix_web::test]
+    async fn local_store_keeps_keys_inside_root() {
+        let root = std::env::temp_dir().join(format!("vsr-blobs-{:x}", rand::random::<u64>()));
+        let store = LocalBlobStore::new(&root);
+
+        store.put("post/attachment/a", Bytes::from_static(b"hello")).await.unwrap();
+        assert_eq!(store.get("post/attachment/a").await.unwrap(), Some(Bytes::from_static(b"hello")));
+        store.delete("post/attachment/a").await.unwrap();
+        assert_eq!(store.get("post/attachment/a").await.unwrap(), None);
+        store.delete("post/attachment/a").await.unwrap();
+
+        assert!(store.put("../escape", Bytes::new()).await.is_err());
+        assert!(store.get("/etc/passwd").await.is_err());
+        assert_eq!(file_name("C:\\Users\\me\\photo.png"), "photo.png");
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,695 @@
+//! GraphQL endpoint over every resource mounted through `configure`, enabled by
+//! the `graphql` feature.
+//!
+//! The schema is built from the [`resource`](crate::resource) registry on the
+//! first request. Each resource `Post` on table `post` gets:
+//!
+//! - queries `post(id)` and `post_list(filter, limit, offset, order_by, desc)`
+//! - mutations `create_post`, `update_post`, `patch_post` and `delete_post`
+//! - a field for each `#[relation]`: `Comment.post` for the parent row and
+//!   `Post.comment_list` for the children
+//!
+//! Only enabled operations appear, and every resolver applies the same role,
+//! permission and [`PolicyStore`] checks as the REST handlers. Mutations
+//! write like the generated handlers: each runs in a [`Tx`], and only once it
+//! commits are cached responses evicted, change [`events`](crate::events)
+//! published and the files of deleted rows removed from the [`FileStorage`].
+//!
+//! ```rust,ignore
+//! web::scope("/api")
+//!     .configure(|cfg| graphql::graphql_routes(cfg, any_pool.clone()))
+//!     .configure(|cfg| Post::configure(cfg, sqlite_pool.clone()))
+//! ```
+
+use std::sync::OnceLock;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, Schema, Type,
+    TypeRef, ValueAccessor,
+};
+use async_graphql::{Error, Name, Value};
+use sqlx::any::{AnyArguments, AnyRow};
+use sqlx::query::Query;
+use sqlx::{Any, AnyConnection, AnyPool, Row};
+
+use crate::auth::{OptionalUserContext, UserContext};
+use crate::cache::ResponseCache;
+use crate::events;
+use crate::files::{FileStorage, StoredFile};
+use crate::json_schema::is_read_only;
+use crate::migrate::{Backend, ColumnDef, ColumnType};
+use crate::permissions::PermissionMap;
+use crate::policy::{authorize_with, PolicyStore};
+use crate::resource::{registered, ResourceMeta};
+use crate::roles::RoleHierarchy;
+use crate::tx::Tx;
+
+/// Schema and pool for one `graphql_routes` registration
+struct GraphqlState {
+    pool: AnyPool,
+    schema: OnceLock<Result<Schema, String>>,
+}
+
+/// Who is calling, attached to each GraphQL request
+struct Caller {
+    user: Option<UserContext>,
+    hierarchy: RoleHierarchy,
+    permissions: PermissionMap,
+    store: Option<web::Data<PolicyStore>>,
+    /// Mutations evict the REST responses cached for their resource
+    cache: ResponseCache,
+    /// Deletes remove the files of the rows they delete
+    files: FileStorage,
+}
+
+/// Register `POST /graphql`. Resources are read from the registry, so mount this
+/// in the same scope as the resources' `configure`.
+pub fn graphql_routes(cfg: &mut web::ServiceConfig, db: AnyPool) {
+    cfg.app_data(web::Data::new(GraphqlState { pool: db, schema: OnceLock::new() }));
+    cfg.route("/graphql", web::post().to(execute));
+}
+
+async fn execute(
+    req: HttpRequest,
+    user: OptionalUserContext,
+    state: web::Data<GraphqlState>,
+    request: web::Json<async_graphql::Request>,
+) -> impl Responder {
+    let schema = match state.schema.get_or_init(|| build_schema(&registered(), state.pool.clone())) {
+        Ok(schema) => schema,
+        Err(e) => return HttpResponse::InternalServerError().body(e.clone()),
+    };
+
+    let caller = Caller {
+        user: user.0,
+        hierarchy: RoleHierarchy::from_request(&req).clone(),
+        permissions: PermissionMap::from_request(&req).clone(),
+        store: req.app_data::<web::Data<PolicyStore>>().cloned(),
+        cache: ResponseCache::from_request(&req).clone(),
+        files: FileStorage::from_request(&req).clone(),
+    };
+    HttpResponse::Ok().json(schema.execute(request.into_inner().data(caller)).await)
+}
+
+/// Build the schema for the given resources
+pub fn build_schema(resources: &[&'static ResourceMeta], pool: AnyPool) -> Result<Schema, String> {
+    let mut query = Object::new("Query");
+    let mut mutation = Object::new("Mutation");
+    let mut has_mutations = false;
+    let mut types: Vec<Type> = vec![];
+
+    for &meta in resources {
+        let mut object = Object::new(meta.name);
+        // Hidden fields can be written but never read, filtered or sorted on
+        for column in meta.columns().iter().filter(|c| !meta.is_hidden(c.name)) {
+            let name = column.name;
+            object = object.field(Field::new(name, output_type(column), move |ctx| {
+                FieldFuture::new(async move { Ok(column_value(&ctx, name)?.map(FieldValue::value)) })
+            }));
+        }
+        object = relation_fields(object, meta, resources);
+        types.push(object.into());
+        types.push(filter_input(meta).into());
+
+        if meta.operation("get").is_some() {
+            query = query.field(
+                Field::new(meta.table, TypeRef::named(meta.name), move |ctx| {
+                    FieldFuture::new(async move {
+                        authorize(&ctx, meta, "get").await?;
+                        let id = ctx.args.try_get("id")?.i64()?;
+                        Ok(fetch_one(&ctx, meta, meta.primary_key, id).await?.map(FieldValue::value))
+                    })
+                })
+                .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::INT))),
+            );
+        }
+        if meta.operation("list").is_some() {
+            query = query.field(list_field(format!("{}_list", meta.table), meta, None));
+        }
+
+        let has_writes = ["create", "update", "patch"].iter().any(|op| meta.operation(op).is_some());
+        if has_writes {
+            types.push(payload_input(meta, false).into());
+            types.push(payload_input(meta, true).into());
+        }
+        for op in ["create", "update", "patch", "delete"] {
+            if meta.operation(op).is_some() {
+                mutation = mutation.field(mutation_field(meta, op));
+                has_mutations = true;
+            }
+        }
+    }
+
+    let mut builder = Schema::build("Query", has_mutations.then_some("Mutation"), None)
+        .data(pool)
+        .register(query);
+    if has_mutations {
+        builder = builder.register(mutation);
+    }
+    for ty in types {
+        builder = builder.register(ty);
+    }
+    builder.finish().map_err(|e| format!("Invalid GraphQL schema: {}", e))
+}
+
+fn scalar(column: &ColumnDef) -> &'static str {
+    match column.column_type {
+        ColumnType::Integer => TypeRef::INT,
+        ColumnType::Real => TypeRef::FLOAT,
+        ColumnType::Boolean => TypeRef::BOOLEAN,
+        ColumnType::Text => TypeRef::STRING,
+    }
+}
+
+fn output_type(column: &ColumnDef) -> TypeRef {
+    if column.nullable {
+        TypeRef::named(scalar(column))
+    } else {
+        TypeRef::named_nn(scalar(column))
+    }
+}
+
+/// Value of a column on the row a field is resolved for
+fn column_value(ctx: &ResolverContext<'_>, name: &str) -> async_graphql::Result<Option<Value>> {
+    match ctx.parent_value.try_to_value()? {
+        Value::Object(row) => Ok(row.get(name).filter(|v| !matches!(v, Value::Null)).cloned()),
+        _ => Ok(None),
+    }
+}
+
+/// `PostInput` for create and update, or `PostPatch` with every field optional
+fn payload_input(meta: &ResourceMeta, patch: bool) -> InputObject {
+    let name = format!("{}{}", meta.name, if patch { "Patch" } else { "Input" });
+    let mut input = InputObject::new(name);
+    for column in meta.columns().iter().filter(|c| !is_read_only(meta, c)) {
+        let ty = if patch { TypeRef::named(scalar(column)) } else { output_type(column) };
+        input = input.field(InputValue::new(column.name, ty));
+    }
+    input
+}
+
+/// `PostFilter`: equality on every visible column, plus `<column>_contains` on text columns
+fn filter_input(meta: &ResourceMeta) -> InputObject {
+    let mut input = InputObject::new(format!("{}Filter", meta.name));
+    for column in meta.columns().iter().filter(|c| !meta.is_hidden(c.name)) {
+        input = input.field(InputValue::new(column.name, TypeRef::named(scalar(column))));
+        if column.column_type == ColumnType::Text {
+            input = input.field(InputValue::new(format!("{}_contains", column.name), TypeRef::named(TypeRef::STRING)));
+        }
+    }
+    input
+}
+
+/// `Comment.post` for each relation of `meta`, and `Post.comment_list` for each relation pointing at it
+fn relation_fields(mut object: Object, meta: &'static ResourceMeta, resources: &[&'static ResourceMeta]) -> Object {
+    for relation in meta.relations {
+        let Some(parent) = resources.iter().copied().find(|r| r.table == relation.parent_table) else {
+            continue;
+        };
+        let name = match relation.field.strip_suffix("_id") {
+            Some(stem) if meta.column(stem).is_none() => stem.to_string(),
+            _ => format!("{}_ref", relation.field),
+        };
+        let field = relation.field;
+        let parent_column = relation.parent_column;
+        object = object.field(Field::new(name, TypeRef::named(parent.name), move |ctx| {
+            FieldFuture::new(async move {
+                let Some(Value::Number(id)) = column_value(&ctx, field)? else {
+                    return Ok(None);
+                };
+                authorize(&ctx, parent, "get").await?;
+                let id = id.as_i64().unwrap_or_default();
+                Ok(fetch_one(&ctx, parent, parent_column, id).await?.map(FieldValue::value))
+            })
+        }));
+    }
+
+    for child in resources.iter().copied().filter(|r| r.operation("list").is_some()) {
+        let relations: Vec<_> = child.relations.iter().filter(|r| r.parent_table == meta.table).collect();
+        for relation in &relations {
+            let name = if relations.len() == 1 {
+                format!("{}_list", child.table)
+            } else {
+                format!("{}_list_by_{}", child.table, relation.field)
+            };
+            object = object.field(list_field(name, child, Some((relation.field, relation.parent_column))));
+        }
+    }
+    object
+}
+
+/// A list of `meta` rows, optionally restricted to the children of the parent row
+/// through `(child column, parent column)`
+fn list_field(name: String, meta: &'static ResourceMeta, parent: Option<(&'static str, &'static str)>) -> Field {
+    Field::new(name, TypeRef::named_nn_list_nn(meta.name), move |ctx| {
+        FieldFuture::new(async move {
+            authorize(&ctx, meta, "list").await?;
+
+            let mut conditions = vec![];
+            let mut values = vec![];
+            if let Some((child_column, parent_column)) = parent {
+                let Some(Value::Number(id)) = column_value(&ctx, parent_column)? else {
+                    return Ok(Some(FieldValue::list(Vec::<FieldValue>::new())));
+                };
+                conditions.push((child_column.to_string(), "="));
+                values.push(Bind::Int(id.as_i64()));
+            }
+            if let Some(filter) = ctx.args.get("filter") {
+                let visible = |name: &str| meta.column(name).filter(|c| !meta.is_hidden(c.name));
+                for (key, value) in filter.object()?.iter() {
+                    if value.is_null() {
+                        continue;
+                    }
+                    if let Some(column) = visible(key) {
+                        conditions.push((column.name.to_string(), "="));
+                        values.push(Bind::from_input(column, &value)?);
+                    } else if let Some(column) = key.strip_suffix("_contains").and_then(visible) {
+                        conditions.push((column.name.to_string(), "LIKE"));
+                        values.push(Bind::Text(Some(format!("%{}%", value.string()?))));
+                    }
+                }
+            }
+
+            let order_by = match ctx.args.get("order_by") {
+                Some(column) => {
+                    let column = column.string()?;
+                    meta.column(column)
+                        .filter(|c| !meta.is_hidden(c.name))
+                        .ok_or_else(|| Error::new(format!("Unknown column '{}'", column)))?
+                        .name
+                }
+                None => meta.primary_key,
+            };
+            let desc = ctx.args.get("desc").map(|d| d.boolean()).transpose()?.unwrap_or(false);
+            let limit = ctx.args.get("limit").map(|l| l.i64()).transpose()?;
+            let offset = ctx.args.get("offset").map(|o| o.i64()).transpose()?;
+
+            let pool = ctx.data::<AnyPool>()?;
+            let mut conn = pool.acquire().await?;
+            let backend = Backend::of(&conn).map_err(|e| Error::new(e.to_string()))?;
+
+            let mut sql = format!("SELECT * FROM {}", meta.table);
+            for (i, (column, op)) in conditions.iter().enumerate() {
+                sql.push_str(if i == 0 { " WHERE " } else { " AND " });
+                sql.push_str(&format!("{} {} {}", column, op, backend.placeholder(i + 1)));
+            }
+            sql.push_str(&format!(" ORDER BY {}{}", order_by, if desc { " DESC" } else { "" }));
+            match (limit, offset) {
+                (Some(limit), Some(offset)) => sql.push_str(&format!(" LIMIT {} OFFSET {}", limit.max(0), offset.max(0))),
+                (Some(limit), None) => sql.push_str(&format!(" LIMIT {}", limit.max(0))),
+                // MySQL has no OFFSET without LIMIT
+                (None, Some(offset)) => sql.push_str(&format!(" LIMIT {} OFFSET {}", i64::MAX, offset.max(0))),
+                (None, None) => {}
+            }
+
+            let mut query = sqlx::query(&sql);
+            for value in values {
+                query = value.bind(query);
+            }
+            let rows = query.fetch_all(&mut *conn).await?;
+            let rows: Vec<_> = rows.iter().map(|row| FieldValue::value(row_value(meta, row))).collect();
+            Ok(Some(FieldValue::list(rows)))
+        })
+    })
+    .argument(InputValue::new("filter", TypeRef::named(format!("{}Filter", meta.name))))
+    .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+    .argument(InputValue::new("offset", TypeRef::named(TypeRef::INT)))
+    .argument(InputValue::new("order_by", TypeRef::named(TypeRef::STRING)))
+    .argument(InputValue::new("desc", TypeRef::named(TypeRef::BOOLEAN)))
+}
+
+fn mutation_field(meta: &'static ResourceMeta, op: &'static str) -> Field {
+    let name = format!("{}_{}", op, meta.table);
+    let returns = if op == "delete" { TypeRef::named_nn(TypeRef::BOOLEAN) } else { TypeRef::named(meta.name) };
+
+    let field = Field::new(name, returns, move |ctx| {
+        FieldFuture::new(async move {
+            authorize(&ctx, meta, op).await?;
+            let pool = ctx.data::<AnyPool>()?;
+            let backend = Backend::of_pool(pool).await.map_err(|e| Error::new(e.to_string()))?;
+            let id = ctx.args.get("id").map(|id| id.i64()).transpose()?;
+
+            // Columns to write, in order
+            let mut columns = vec![];
+            let mut values = vec![];
+            if let Some(input) = ctx.args.get("input") {
+                let input = input.object()?;
+                for column in meta.columns().iter().filter(|c| !is_read_only(meta, c)) {
+                    match input.get(column.name) {
+                        Some(value) => {
+                            columns.push(column.name);
+                            values.push(Bind::from_input(column, &value)?);
+                        }
+                        // `PostInput` may omit nullable fields, which are written as NULL
+                        None if op != "patch" => {
+                            columns.push(column.name);
+                            values.push(Bind::null(column));
+                        }
+                        None => {}
+                    }
+                }
+            }
+
+            let touch = meta.column("updated_at").is_some_and(|c| c.default_now);
+            let id_placeholder = backend.placeholder(columns.len() + 1);
+            let sql = match op {
+                "create" => {
+                    let placeholders: Vec<_> = (1..=columns.len()).map(|n| backend.placeholder(n)).collect();
+                    let returning = if backend == Backend::Mysql { "" } else { " RETURNING *" };
+                    format!(
+                        "INSERT INTO {} ({}) VALUES ({}){}",
+                        meta.table,
+                        columns.join(", "),
+                        placeholders.join(", "),
+                        returning
+                    )
+                }
+                "delete" => format!("DELETE FROM {} WHERE {} = {}", meta.table, meta.primary_key, backend.placeholder(1)),
+                _ => {
+                    let mut assignments: Vec<_> = columns
+                        .iter()
+                        .enumerate()
+                        .map(|(i, column)| format!("{} = {}", column, backend.placeholder(i + 1)))
+                        .collect();
+                    if touch {
+                        assignments.push("updated_at = CURRENT_TIMESTAMP".to_string());
+                    }
+                    if assignments.is_empty() {
+                        // Nothing to change; just return the row
+                        let row = fetch_one(&ctx, meta, meta.primary_key, id.unwrap_or_default()).await?;
+                        return Ok(row.map(FieldValue::value));
+                    }
+                    format!(
+                        "UPDATE {} SET {} WHERE {} = {}",
+                        meta.table,
+                        assignments.join(", "),
+                        meta.primary_key,
+                        id_placeholder
+                    )
+                }
+            };
+
+            let mut query = sqlx::query(&sql);
+            for value in values {
+                query = value.bind(query);
+            }
+            if let Some(id) = id {
+                query = query.bind(id);
+            }
+
+            // As in the generated handlers, the cache, events and blob store only see committed writes
+            let caller = ctx.data::<Caller>()?;
+            let mut tx = Tx::<Any>::begin(pool).await?;
+            match op {
+                "create" => {
+                    let row = if backend != Backend::Mysql {
+                        Some(query.fetch_one(&mut *tx).await?)
+                    } else {
+                        query.execute(&mut *tx).await?;
+                        let sql = format!("SELECT * FROM {} WHERE {} = LAST_INSERT_ID()", meta.table, meta.primary_key);
+                        sqlx::query(&sql).fetch_optional(&mut *tx).await?
+                    };
+                    let row = row.map(|row| row_value(meta, &row));
+                    tx.commit().await?;
+                    caller.cache.invalidate(meta.table).await;
+                    if let Some(row) = &row {
+                        events::publish_with(meta.table, "create", primary_key(meta, row), row);
+                    }
+                    Ok(row.map(FieldValue::value))
+                }
+                "delete" => {
+                    let id = id.unwrap_or_default();
+                    let previous = if meta.files.is_empty() {
+                        None
+                    } else {
+                        select_one(&mut tx, backend, meta, meta.primary_key, id).await?
+                    };
+                    let deleted = query.execute(&mut *tx).await?.rows_affected() > 0;
+                    tx.commit().await?;
+                    if deleted {
+                        if let Some(previous) = &previous {
+                            let files: Vec<StoredFile> = meta
+                                .files
+                                .iter()
+                                .filter_map(|file| previous.try_get::<Option<StoredFile>, _>(file.field).ok().flatten())
+                                .collect();
+                            caller.files.remove(&files).await;
+                        }
+                        caller.cache.invalidate(meta.table).await;
+                        events::publish(meta.table, "delete", Some(id));
+                    }
+                    Ok(Some(FieldValue::value(deleted)))
+                }
+                _ => {
+                    let id = id.unwrap_or_default();
+                    let updated = query.execute(&mut *tx).await?.rows_affected() > 0;
+                    let row = select_one(&mut tx, backend, meta, meta.primary_key, id).await?;
+                    tx.commit().await?;
+                    if updated {
+                        caller.cache.invalidate(meta.table).await;
+                        let input = ctx.args.get("input").map(|input| input.as_value().clone());
+                        events::publish_with(meta.table, op, Some(id), &input);
+                    }
+                    Ok(row.map(|row| FieldValue::value(row_value(meta, &row))))
+                }
+            }
+        })
+    });
+
+    let id = InputValue::new("id", TypeRef::named_nn(TypeRef::INT));
+    match op {
+        "create" => field.argument(InputValue::new("input", TypeRef::named_nn(format!("{}Input", meta.name)))),
+        "update" => field
+            .argument(id)
+            .argument(InputValue::new("input", TypeRef::named_nn(format!("{}Input", meta.name)))),
+        "patch" => field
+            .argument(id)
+            .argument(InputValue::new("input", TypeRef::named_nn(format!("{}Patch", meta.name)))),
+        _ => field.argument(id),
+    }
+}
+
+/// Apply the operation's role, policy and permission requirements to the caller
+async fn authorize(ctx: &ResolverContext<'_>, meta: &ResourceMeta, op: &str) -> async_graphql::Result<()> {
+    let Some(operation) = meta.operation(op) else {
+        return Err(Error::new(format!("{} does not allow {}", meta.table, op)));
+    };
+    if operation.public {
+        return Ok(());
+    }
+
+    let caller = ctx.data::<Caller>()?;
+    let Some(user) = &caller.user else {
+        return Err(Error::new("Missing token"));
+    };
+    let store = caller.store.as_ref().map(|store| store.get_ref());
+    let roles_ok = authorize_with(store, &caller.hierarchy, user, meta.table, op, operation.roles).await?;
+    let permissions_ok = operation
+        .permissions
+        .is_none_or(|requirement| requirement.is_satisfied_by(user, &caller.permissions, &caller.hierarchy));
+
+    if roles_ok && permissions_ok {
+        Ok(())
+    } else {
+        Err(Error::new("Insufficient privileges"))
+    }
+}
+
+async fn fetch_one(
+    ctx: &ResolverContext<'_>,
+    meta: &ResourceMeta,
+    column: &str,
+    id: i64,
+) -> async_graphql::Result<Option<Value>> {
+    let pool = ctx.data::<AnyPool>()?;
+    let mut conn = pool.acquire().await?;
+    let backend = Backend::of(&conn).map_err(|e| Error::new(e.to_string()))?;
+
+    let row = select_one(&mut conn, backend, meta, column, id).await?;
+    Ok(row.map(|row| row_value(meta, &row)))
+}
+
+/// The row whose `column` is `id`, on a pooled connection or in a transaction
+async fn select_one(
+    conn: &mut AnyConnection,
+    backend: Backend,
+    meta: &ResourceMeta,
+    column: &str,
+    id: i64,
+) -> Result<Option<AnyRow>, sqlx::Error> {
+    let sql = format!("SELECT * FROM {} WHERE {} = {}", meta.table, column, backend.placeholder(1));
+    sqlx::query(&sql).bind(id).fetch_optional(&mut *conn).await
+}
+
+fn primary_key(meta: &ResourceMeta, row: &Value) -> Option<i64> {
+    match row {
+        Value::Object(fields) => match fields.get(meta.primary_key) {
+            Some(Value::Number(id)) => id.as_i64(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Convert a row to a GraphQL object, decoding each column by its declared type
+fn row_value(meta: &ResourceMeta, row: &AnyRow) -> Value {
+    let fields = meta.columns().iter().map(|column| {
+        let name = column.name;
+        let value = match column.column_type {
+            ColumnType::Integer => row.try_get::<Option<i64>, _>(name).ok().flatten().map(Value::from),
+            ColumnType::Real => row.try_get::<Option<f64>, _>(name).ok().flatten().map(Value::from),
+            // SQLite stores booleans as integers
+            ColumnType::Boolean => row
+                .try_get::<Option<bool>, _>(name)
+                .or_else(|_| row.try_get::<Option<i64>, _>(name).map(|v| v.map(|v| v != 0)))
+                .ok()
+                .flatten()
+                .map(Value::from),
+            ColumnType::Text => row.try_get::<Option<String>, _>(name).ok().flatten().map(Value::from),
+        };
+        (Name::new(name), value.unwrap_or(Value::Null))
+    });
+    Value::Object(fields.collect())
+}
+
+/// A typed value to bind to a query
+enum Bind {
+    Int(Option<i64>),
+    Real(Option<f64>),
+    Bool(Option<bool>),
+    Text(Option<String>),
+}
+
+impl Bind {
+    fn null(column: &ColumnDef) -> Self {
+        match column.column_type {
+            ColumnType::Integer => Bind::Int(None),
+            ColumnType::Real => Bind::Real(None),
+            ColumnType::Boolean => Bind::Bool(None),
+            ColumnType::Text => Bind::Text(None),
+        }
+    }
+
+    fn from_input(column: &ColumnDef, value: &ValueAccessor<'_>) -> async_graphql::Result<Self> {
+        if value.is_null() {
+            return Ok(Self::null(column));
+        }
+        Ok(match column.column_type {
+            ColumnType::Integer => Bind::Int(Some(value.i64()?)),
+            ColumnType::Real => Bind::Real(Some(value.f64()?)),
+            ColumnType::Boolean => Bind::Bool(Some(value.boolean()?)),
+            ColumnType::Text => Bind::Text(Some(value.string()?.to_string())),
+        })
+    }
+
+    fn bind<'q>(self, query: Query<'q, Any, AnyArguments<'q>>) -> Query<'q, Any, AnyArguments<'q>> {
+        match self {
+            Bind::Int(v) => query.bind(v),
+            Bind::Real(v) => query.bind(v),
+            Bind::Bool(v) => query.bind(v),
+            Bind::Text(v) => query.bind(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::PendingFile;
+    use crate::migrate::Migrator;
+    use crate::test_fixtures::{COMMENT, DOCUMENT, POST};
+    use async_graphql::value;
+    use sqlx::any::AnyPoolOptions;
+
+    #[actix_web::test]
+    async fn builds_queries_mutations_and_relations() {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPool::connect_lazy("sqlite::memory:").unwrap();
+        let sdl = build_schema(&[&POST, &COMMENT], pool).unwrap().sdl();
+
+        assert!(sdl.contains("post(id: Int!): Post"));
+        assert!(sdl.contains("title_contains: String"));
+        assert!(sdl.contains("create_comment(input: CommentInput!): Comment"));
+        assert!(!sdl.contains("delete_post"));
+        // Both directions of the relation
+        assert!(sdl.contains("post: Post"));
+        assert!(sdl.contains("comment_list(filter: CommentFilter"));
+    }
+
+    #[actix_web::test]
+    async fn hidden_fields_are_writable_only() {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPool::connect_lazy("sqlite::memory:").unwrap();
+        let schema = build_schema(&[&POST], pool).unwrap();
+        let sdl = schema.sdl();
+        let block = |start: &str| sdl.split(start).nth(1).and_then(|rest| rest.split('}').next()).unwrap().to_string();
+
+        assert!(!block("type Post {").contains("edit_token"));
+        assert!(!block("input PostFilter {").contains("edit_token"));
+        assert!(block("input PostInput {").contains("edit_token"));
+
+        let run = |query: &str| {
+            let caller = Caller {
+                user: None,
+                hierarchy: RoleHierarchy::default(),
+                permissions: PermissionMap::default(),
+                store: None,
+                cache: ResponseCache::memory(16),
+                files: FileStorage::local(std::env::temp_dir()),
+            };
+            schema.execute(async_graphql::Request::new(query).data(caller))
+        };
+        let sorted = run(r#"{ post_list(order_by: "edit_token") { id } }"#).await;
+        assert_eq!(sorted.errors[0].message, "Unknown column 'edit_token'");
+        assert!(!run("{ post_list { edit_token } }").await.errors.is_empty());
+        assert!(!run(r#"{ post_list(filter: { edit_token: "x" }) { id } }"#).await.errors.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn mutations_commit_and_remove_deleted_files() {
+        sqlx::any::install_default_drivers();
+        let dir = std::env::temp_dir().join(format!("vsr-graphql-{:x}", rand::random::<u64>()));
+        let pool = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        Migrator::new(pool.clone(), dir.join("migrations")).table(DOCUMENT.schema).sync("graphql").await.unwrap();
+        let storage = FileStorage::local(dir.join("files"));
+        let schema = build_schema(&[&DOCUMENT], pool.clone()).unwrap();
+        let run = |query: &str| {
+            let caller = Caller {
+                user: None,
+                hierarchy: RoleHierarchy::default(),
+                permissions: PermissionMap::default(),
+                store: None,
+                cache: ResponseCache::memory(16),
+                files: storage.clone(),
+            };
+            schema.execute(async_graphql::Request::new(query).data(caller))
+        };
+
+        let created = run(r#"mutation { create_document(input: { title: "Notes" }) { id title } }"#).await;
+        assert!(created.errors.is_empty(), "{:?}", created.errors);
+        assert_eq!(created.data, value!({ "create_document": { "id": 1, "title": "Notes" } }));
+
+        // Attach a file as an upload through REST would
+        let file = PendingFile {
+            field: "file",
+            filename: "notes.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            body: "hello".into(),
+        };
+        let (_, stored) = storage.store("document", vec![file]).await.unwrap().remove(0);
+        sqlx::query("UPDATE document SET file = ? WHERE id = 1")
+            .bind(serde_json::to_string(&stored).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(dir.join("files").join(&stored.key).exists());
+
+        let deleted = run("mutation { delete_document(id: 1) }").await;
+        assert_eq!(deleted.data, value!({ "delete_document": true }));
+        assert!(!dir.join("files").join(&stored.key).exists());
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,137 @@
+//! JSON Schema (draft 2020-12) for the payloads a resource accepts.
+//!
+//! Served by every derived struct at `GET /{table}/_schema`, and shared with the
+//! OpenAPI document so both describe columns the same way.
+
+use serde_json::{json, Map, Value};
+
+use crate::migrate::{ColumnDef, ColumnType};
+use crate::resource::ResourceMeta;
+
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Schema of a single column value
+///
+/// Nullable columns accept `null`, unique columns carry `x-unique` and foreign
+/// keys carry `x-references` as `table.column`.
+pub fn column_schema(column: &ColumnDef) -> Value {
+    let (ty, format) = match column.column_type {
+        ColumnType::Integer => ("integer", Some("int64")),
+        ColumnType::Real => ("number", Some("double")),
+        ColumnType::Boolean => ("boolean", None),
+        ColumnType::Text => ("string", None),
+    };
+
+    let mut schema = json!({ "type": ty });
+    if column.nullable {
+        schema["type"] = json!([ty, "null"]);
+    }
+    if let Some(format) = format {
+        schema["format"] = json!(format);
+    }
+    if column.unique {
+        schema["x-unique"] = json!(true);
+    }
+    if let Some(fk) = &column.references {
+        schema["x-references"] = json!(format!("{}.{}", fk.table, fk.column));
+    }
+    schema
+}
+
+/// Columns the server fills in itself; clients may send them but they are ignored.
+/// File fields are only set by uploading the file.
+pub fn is_read_only(meta: &ResourceMeta, column: &ColumnDef) -> bool {
+    column.name == meta.primary_key || column.default_now || meta.file(column.name).is_some()
+}
+
+/// Schema of a file field's metadata
+pub fn stored_file_schema() -> Value {
+    json!({
+        "type": ["object", "null"],
+        "properties": {
+            "key": { "type": "string" },
+            "filename": { "type": "string" },
+            "content_type": { "type": "string" },
+            "size": { "type": "integer", "format": "int64" }
+        },
+        "required": ["key", "filename", "content_type", "size"]
+    })
+}
+
+/// Body of `POST` and `PUT`: the whole struct, with generated columns read-only
+pub fn full_schema(meta: &ResourceMeta) -> Value {
+    let mut properties = Map::new();
+    let mut required = vec![];
+
+    for column in meta.columns() {
+        let mut schema = match meta.file(column.name) {
+            Some(_) => stored_file_schema(),
+            None => column_schema(column),
+        };
+        if is_read_only(meta, column) {
+            schema["readOnly"] = json!(true);
+        }
+        if !column.nullable {
+            required.push(column.name);
+        }
+        properties.insert(column.name.to_string(), schema);
+    }
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Body of `PATCH`: any subset of the writable columns
+pub fn patch_schema(meta: &ResourceMeta) -> Value {
+    let properties: Map<String, Value> = meta
+        .columns()
+        .iter()
+        .filter(|column| !is_read_only(meta, column))
+        .map(|column| (column.name.to_string(), column_schema(column)))
+        .collect();
+
+    json!({
+        "type": "object",
+        "description": format!("Fields of {} to change; omitted fields keep their value", meta.name),
+        "properties": properties,
+    })
+}
+
+/// Schema document with a `$defs` entry for each enabled write operation:
+/// `create`, `update` and `patch`
+pub fn payload_schemas(meta: &ResourceMeta) -> Value {
+    let mut defs = Map::new();
+    for op in ["create", "update"] {
+        if meta.operation(op).is_some() {
+            defs.insert(op.to_string(), full_schema(meta));
+        }
+    }
+    if meta.operation("patch").is_some() {
+        defs.insert("patch".to_string(), patch_schema(meta));
+    }
+
+    json!({
+        "$schema": DIALECT,
+        "$id": format!("{}/_schema", meta.path),
+        "title": meta.name,
+        "$defs": defs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::COMMENT;
+
+    #[test]
+    fn describes_enabled_payloads() {
+        let doc = payload_schemas(&COMMENT);
+        let defs = doc["$defs"].as_object().unwrap();
+
+        assert_eq!(defs.keys().collect::<Vec<_>>(), ["create", "patch"]);
+        assert_eq!(defs["create"]["required"], json!(["post_id", "title"]));
+        assert_eq!(defs["create"]["properties"]["id"]["readOnly"], true);
+        assert_eq!(defs["create"]["properties"]["post_id"]["x-references"], "post.id");
+        assert!(defs["patch"]["properties"].get("created_at").is_none());
+        assert!(defs["patch"].get("required").is_none());
+    }
+}
@@ -13,4 +13,27 @@ mod tests {
     }
 }
 
+pub mod aggregate;
 pub mod auth;
+pub mod bulk;
+pub mod cache;
+pub mod codec;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod events;
+pub mod files;
+pub mod roles;
+pub mod tx;
+pub mod permissions;
+pub mod policy;
+pub mod migrate;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod json_schema;
+pub mod openapi;
+pub mod query;
+pub mod resource;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
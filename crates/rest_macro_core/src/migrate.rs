@@ -0,0 +1,915 @@
+//! Versioned schema migrations generated from derived structs.
+//!
+//! Every `#[derive(RestApi)]` struct exposes its desired table as a
+//! [`TableSchema`] constant named `SCHEMA`. A [`Migrator`] compares those
+//! schemas with the live database and writes the difference as a pair of
+//! `migrations/<version>_<name>.up.sql` and `.down.sql` files. Applying
+//! migrations runs the pending `.up.sql` files in version order and records
+//! each one in the `_vsr_migrations` table. The `vsr migrate` commands of the
+//! CLI drive the same [`Migrator`].
+//!
+//! Generated migrations only ever add: new tables, and new columns on existing
+//! tables. Columns and tables that disappear from the structs are left in
+//! place, since dropping them would lose data. Write those migrations by hand.
+
+use chrono::Utc;
+use sqlx::pool::PoolConnection;
+use sqlx::{Any, AnyConnection, AnyPool, Connection};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::resource::Resource;
+
+/// Table that records applied migration versions
+pub const MIGRATIONS_TABLE: &str = "_vsr_migrations";
+
+/// Single-row table held while migrations are applied or reverted
+pub const LOCK_TABLE: &str = "_vsr_migrations_lock";
+
+/// Column types understood by the derive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Text,
+}
+
+/// A foreign key such as `post.id`
+#[derive(Debug, Clone, Copy)]
+pub struct ForeignKey {
+    pub table: &'static str,
+    pub column: &'static str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnDef {
+    pub name: &'static str,
+    pub column_type: ColumnType,
+    pub primary_key: bool,
+    pub nullable: bool,
+    pub unique: bool,
+    /// Filled with the current time on insert, as for `created_at` and `updated_at`
+    pub default_now: bool,
+    pub references: Option<ForeignKey>,
+}
+
+impl ColumnDef {
+    /// A required column without constraints, for schemas written by hand
+    pub const fn new(name: &'static str, column_type: ColumnType) -> Self {
+        ColumnDef {
+            name,
+            column_type,
+            primary_key: false,
+            nullable: false,
+            unique: false,
+            default_now: false,
+            references: None,
+        }
+    }
+
+    /// The auto-incrementing primary key `name`
+    pub const fn primary_key(name: &'static str) -> Self {
+        ColumnDef { primary_key: true, nullable: true, ..ColumnDef::new(name, ColumnType::Integer) }
+    }
+}
+
+/// The table a derived struct expects
+#[derive(Debug, Clone, Copy)]
+pub struct TableSchema {
+    pub name: &'static str,
+    pub columns: &'static [ColumnDef],
+    /// Unique constraints over several columns, added when the table is created
+    pub unique: &'static [&'static [&'static str]],
+}
+
+/// Database flavors with their own DDL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+impl Backend {
+    /// Detect the backend of a connection
+    pub fn of(conn: &AnyConnection) -> Result<Self, MigrateError> {
+        match conn.backend_name() {
+            "SQLite" => Ok(Backend::Sqlite),
+            "PostgreSQL" => Ok(Backend::Postgres),
+            "MySQL" => Ok(Backend::Mysql),
+            other => Err(MigrateError::Invalid(format!("unsupported database `{}`", other))),
+        }
+    }
+
+    /// Detect the backend behind a pool
+    pub async fn of_pool(pool: &AnyPool) -> Result<Self, MigrateError> {
+        Backend::of(&*pool.acquire().await?)
+    }
+
+    /// Bind parameter `n` (1-based) in this backend's syntax
+    pub fn placeholder(self, n: usize) -> String {
+        match self {
+            Backend::Postgres => format!("${}", n),
+            _ => "?".to_string(),
+        }
+    }
+
+    fn column_type(self, column: &ColumnDef) -> &'static str {
+        match (self, column.column_type) {
+            (Backend::Sqlite, ColumnType::Integer) => "INTEGER",
+            (_, ColumnType::Integer) => "BIGINT",
+            (Backend::Postgres, ColumnType::Real) => "DOUBLE PRECISION",
+            (Backend::Mysql, ColumnType::Real) => "DOUBLE",
+            (_, ColumnType::Real) => "REAL",
+            (_, ColumnType::Boolean) => "BOOLEAN",
+            // MySQL cannot index or default a TEXT column
+            (Backend::Mysql, ColumnType::Text) if column.unique || column.default_now => "VARCHAR(255)",
+            (_, ColumnType::Text) => "TEXT",
+        }
+    }
+
+    fn primary_key(self, name: &str) -> String {
+        match self {
+            Backend::Sqlite => format!("{} INTEGER PRIMARY KEY AUTOINCREMENT", name),
+            Backend::Postgres => format!("{} BIGSERIAL PRIMARY KEY", name),
+            Backend::Mysql => format!("{} BIGINT AUTO_INCREMENT PRIMARY KEY", name),
+        }
+    }
+
+    fn now_default(self) -> &'static str {
+        match self {
+            Backend::Mysql => "DEFAULT (CURRENT_TIMESTAMP)",
+            _ => "DEFAULT CURRENT_TIMESTAMP",
+        }
+    }
+
+    /// Column definition inside `CREATE TABLE`
+    fn create_column(self, column: &ColumnDef) -> String {
+        if column.primary_key {
+            return self.primary_key(column.name);
+        }
+
+        let mut def = format!("{} {}", column.name, self.column_type(column));
+        if column.default_now {
+            def.push(' ');
+            def.push_str(self.now_default());
+        } else if !column.nullable {
+            def.push_str(" NOT NULL");
+        }
+        if column.unique {
+            def.push_str(" UNIQUE");
+        }
+        if let Some(fk) = column.references {
+            def.push_str(&format!(" REFERENCES {}({})", fk.table, fk.column));
+        }
+        def
+    }
+
+    /// Column definition for `ALTER TABLE ... ADD COLUMN`.
+    ///
+    /// Existing rows need a value, so required columns get a zero default.
+    /// Uniqueness is added as a separate index and foreign keys are skipped,
+    /// since SQLite cannot add either through `ALTER TABLE`.
+    fn added_column(self, column: &ColumnDef) -> String {
+        let mut def = format!("{} {}", column.name, self.column_type(column));
+        if column.default_now {
+            // SQLite only accepts constant defaults on added columns
+            if self != Backend::Sqlite {
+                def.push(' ');
+                def.push_str(self.now_default());
+            }
+        } else if !column.nullable {
+            let zero = match column.column_type {
+                ColumnType::Integer | ColumnType::Real => "0",
+                ColumnType::Boolean => "FALSE",
+                ColumnType::Text => "''",
+            };
+            def.push_str(&format!(" NOT NULL DEFAULT {}", zero));
+        }
+        def
+    }
+
+    fn drop_index(self, index: &str, table: &str) -> String {
+        match self {
+            Backend::Mysql => format!("DROP INDEX {} ON {}", index, table),
+            _ => format!("DROP INDEX {}", index),
+        }
+    }
+
+    /// Names of the columns `table` currently has, empty when it does not exist
+    async fn live_columns(self, conn: &mut AnyConnection, table: &str) -> Result<HashSet<String>, MigrateError> {
+        let sql = match self {
+            Backend::Sqlite => "SELECT name FROM pragma_table_info(?)",
+            Backend::Postgres => {
+                "SELECT column_name::text FROM information_schema.columns \
+                 WHERE table_schema = current_schema() AND table_name = $1"
+            }
+            Backend::Mysql => {
+                "SELECT CAST(column_name AS CHAR) FROM information_schema.columns \
+                 WHERE table_schema = DATABASE() AND table_name = ?"
+            }
+        };
+
+        let columns = sqlx::query_scalar::<_, String>(sql).bind(table).fetch_all(conn).await?;
+        Ok(columns.into_iter().collect())
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrateError {
+    Io(std::io::Error),
+    Database(sqlx::Error),
+    /// Migrations must be applied before new ones are generated
+    Pending(Vec<String>),
+    /// Another process held the migration lock for longer than the timeout
+    Locked(String),
+    Invalid(String),
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::Io(e) => write!(f, "migration file error: {}", e),
+            MigrateError::Database(e) => write!(f, "migration database error: {}", e),
+            MigrateError::Pending(versions) => write!(
+                f,
+                "migrations {} are not applied yet, apply them before generating new ones",
+                versions.join(", ")
+            ),
+            MigrateError::Locked(holder) => write!(
+                f,
+                "migrations are locked by {}; if that process is gone, release the lock with `vsr migrate unlock`",
+                holder
+            ),
+            MigrateError::Invalid(msg) => write!(f, "invalid migration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+impl From<std::io::Error> for MigrateError {
+    fn from(e: std::io::Error) -> Self {
+        MigrateError::Io(e)
+    }
+}
+
+impl From<sqlx::Error> for MigrateError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrateError::Database(e)
+    }
+}
+
+/// A migration file pair found in the migrations directory
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: String,
+    pub name: String,
+    pub up: PathBuf,
+    pub down: Option<PathBuf>,
+}
+
+/// A row of `_vsr_migrations`
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: String,
+    pub name: String,
+    pub applied_at: String,
+}
+
+/// A migration as reported by [`Migrator::status`]
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: String,
+    pub name: String,
+    /// When it was applied, or `None` if it is pending
+    pub applied_at: Option<String>,
+    /// Whether its files are still in the migrations directory
+    pub has_files: bool,
+}
+
+/// Generates and applies migrations for a set of tables.
+///
+/// Applying and reverting take a lock in the `_vsr_migrations_lock` table, so
+/// two instances starting at once cannot both apply the same migration. A
+/// process that cannot get the lock within the timeout (60 seconds by
+/// default) fails with [`MigrateError::Locked`].
+///
+/// ```no_run
+/// # use rest_macro_core::migrate::{Migrator, TableSchema};
+/// # async fn run(pool: sqlx::AnyPool, post: TableSchema) -> Result<(), Box<dyn std::error::Error>> {
+/// let migrator = Migrator::new(pool, "migrations").table(post);
+/// migrator.generate("add_post").await?;
+/// migrator.run().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Migrator {
+    pool: AnyPool,
+    dir: PathBuf,
+    tables: Vec<TableSchema>,
+    lock_timeout: Duration,
+}
+
+impl Migrator {
+    pub fn new(pool: AnyPool, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            pool,
+            dir: dir.into(),
+            tables: vec![],
+            lock_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Add a table whose schema migrations should track, usually `Struct::SCHEMA`
+    pub fn table(mut self, schema: TableSchema) -> Self {
+        self.tables.push(schema);
+        self
+    }
+
+    /// Add the table of a derived resource, the same as `.table(T::metadata().schema)`
+    pub fn resource<T: Resource>(self) -> Self {
+        self.table(T::metadata().schema)
+    }
+
+    /// How long to wait for another process to release the migration lock
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Migration files in the directory, ordered by version
+    pub fn migrations(&self) -> Result<Vec<Migration>, MigrateError> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut migrations = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(stem) = file_name.strip_suffix(".up.sql") else {
+                continue;
+            };
+            let Some((version, name)) = stem.split_once('_') else {
+                return Err(MigrateError::Invalid(format!(
+                    "`{}` should be named `<version>_<name>.up.sql`",
+                    file_name
+                )));
+            };
+
+            let down = path.with_file_name(format!("{}.down.sql", stem));
+            migrations.push(Migration {
+                version: version.to_string(),
+                name: name.to_string(),
+                down: down.exists().then_some(down),
+                up: path,
+            });
+        }
+
+        migrations.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(migrations)
+    }
+
+    /// Rows of `_vsr_migrations`, ordered by version
+    pub async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.applied_on(&mut conn).await
+    }
+
+    /// Migrations that exist on disk but have not been applied
+    pub async fn pending(&self) -> Result<Vec<Migration>, MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.pending_on(&mut conn).await
+    }
+
+    /// Every migration on disk or in the database, ordered by version
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>, MigrateError> {
+        let applied = self.applied().await?;
+        let mut status: Vec<MigrationStatus> = self
+            .migrations()?
+            .into_iter()
+            .map(|m| MigrationStatus {
+                applied_at: applied
+                    .iter()
+                    .find(|a| a.version == m.version)
+                    .map(|a| a.applied_at.clone()),
+                version: m.version,
+                name: m.name,
+                has_files: true,
+            })
+            .collect();
+
+        for migration in applied {
+            if !status.iter().any(|s| s.version == migration.version) {
+                status.push(MigrationStatus {
+                    version: migration.version,
+                    name: migration.name,
+                    applied_at: Some(migration.applied_at),
+                    has_files: false,
+                });
+            }
+        }
+
+        status.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(status)
+    }
+
+    /// Apply every pending migration, each in its own transaction
+    pub async fn run(&self) -> Result<Vec<Migration>, MigrateError> {
+        let mut conn = self.lock().await?;
+        let result = self.run_pending(&mut conn).await;
+        self.release(&mut conn, result).await
+    }
+
+    /// Revert the last `steps` applied migrations with their `.down.sql` files, newest first
+    pub async fn revert(&self, steps: usize) -> Result<Vec<Migration>, MigrateError> {
+        let mut conn = self.lock().await?;
+        let result = self.revert_applied(&mut conn, steps).await;
+        self.release(&mut conn, result).await
+    }
+
+    /// Revert the last applied migration and apply it again
+    pub async fn redo(&self) -> Result<Option<Migration>, MigrateError> {
+        let mut conn = self.lock().await?;
+        let result = async {
+            let Some(migration) = self.revert_applied(&mut conn, 1).await?.pop() else {
+                return Ok(None);
+            };
+            self.apply(&mut conn, &migration).await?;
+            Ok(Some(migration))
+        }
+        .await;
+        self.release(&mut conn, result).await
+    }
+
+    /// Diff the registered tables against the live database and write the
+    /// changes as a new migration, or return `None` when nothing changed
+    pub async fn generate(&self, name: &str) -> Result<Option<Migration>, MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.generate_on(&mut conn, name).await
+    }
+
+    /// Apply pending migrations, then generate and apply one for any remaining
+    /// schema changes. Convenient during development; production deployments
+    /// should ship the generated files and only call [`Migrator::run`].
+    pub async fn sync(&self, name: &str) -> Result<Vec<Migration>, MigrateError> {
+        let mut conn = self.lock().await?;
+        let result = async {
+            let mut applied = self.run_pending(&mut conn).await?;
+            if let Some(migration) = self.generate_on(&mut conn, name).await? {
+                self.apply(&mut conn, &migration).await?;
+                applied.push(migration);
+            }
+            Ok(applied)
+        }
+        .await;
+        self.release(&mut conn, result).await
+    }
+
+    /// Who holds the migration lock and since when, if anyone
+    pub async fn lock_holder(&self) -> Result<Option<String>, MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.lock_holder_on(&mut conn).await
+    }
+
+    /// Release a lock left behind by a process that died while migrating
+    pub async fn force_unlock(&self) -> Result<(), MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.ensure_migrations_table(&mut conn).await?;
+        self.unlock(&mut conn).await
+    }
+
+    async fn applied_on(&self, conn: &mut AnyConnection) -> Result<Vec<AppliedMigration>, MigrateError> {
+        self.ensure_migrations_table(conn).await?;
+        let rows = sqlx::query_as::<_, (String, String, String)>(&format!(
+            "SELECT version, name, applied_at FROM {} ORDER BY version",
+            MIGRATIONS_TABLE
+        ))
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(version, name, applied_at)| AppliedMigration { version, name, applied_at })
+            .collect())
+    }
+
+    async fn pending_on(&self, conn: &mut AnyConnection) -> Result<Vec<Migration>, MigrateError> {
+        let applied: HashSet<String> = self.applied_on(conn).await?.into_iter().map(|m| m.version).collect();
+        Ok(self
+            .migrations()?
+            .into_iter()
+            .filter(|m| !applied.contains(&m.version))
+            .collect())
+    }
+
+    async fn generate_on(&self, conn: &mut AnyConnection, name: &str) -> Result<Option<Migration>, MigrateError> {
+        let pending = self.pending_on(conn).await?;
+        if !pending.is_empty() {
+            return Err(MigrateError::Pending(pending.into_iter().map(|m| m.version).collect()));
+        }
+
+        let backend = Backend::of(conn)?;
+        let mut up = vec![];
+        let mut down = vec![];
+
+        let mut new_tables = vec![];
+        for table in &self.tables {
+            let live = backend.live_columns(conn, table.name).await?;
+            if live.is_empty() {
+                new_tables.push(*table);
+                continue;
+            }
+
+            for column in table.columns.iter().filter(|c| !live.contains(c.name)) {
+                up.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    table.name,
+                    backend.added_column(column)
+                ));
+                let index = format!("{}_{}_key", table.name, column.name);
+                if column.unique {
+                    up.push(format!(
+                        "CREATE UNIQUE INDEX {} ON {} ({})",
+                        index, table.name, column.name
+                    ));
+                }
+                down.push(format!("ALTER TABLE {} DROP COLUMN {}", table.name, column.name));
+                if column.unique {
+                    down.push(backend.drop_index(&index, table.name));
+                }
+            }
+        }
+
+        for table in order_by_references(new_tables) {
+            let mut columns: Vec<String> = table.columns.iter().map(|c| backend.create_column(c)).collect();
+            columns.extend(table.unique.iter().map(|set| format!("UNIQUE ({})", set.join(", "))));
+            up.push(format!("CREATE TABLE {} (\n    {}\n)", table.name, columns.join(",\n    ")));
+            down.push(format!("DROP TABLE {}", table.name));
+        }
+
+        if up.is_empty() {
+            return Ok(None);
+        }
+        // Undo in the reverse order of the changes
+        down.reverse();
+
+        write_migration(&self.dir, name, render(&up), render(&down)).map(Some)
+    }
+
+    async fn run_pending(&self, conn: &mut AnyConnection) -> Result<Vec<Migration>, MigrateError> {
+        // Read under the lock, so migrations applied by the previous holder are skipped
+        let pending = self.pending_on(conn).await?;
+        for migration in &pending {
+            self.apply(conn, migration).await?;
+        }
+        Ok(pending)
+    }
+
+    async fn revert_applied(&self, conn: &mut AnyConnection, steps: usize) -> Result<Vec<Migration>, MigrateError> {
+        let files = self.migrations()?;
+        let backend = Backend::of(conn)?;
+        let mut reverted = vec![];
+
+        for applied in self.applied_on(conn).await?.into_iter().rev().take(steps) {
+            let migration = files
+                .iter()
+                .find(|m| m.version == applied.version)
+                .ok_or_else(|| {
+                    MigrateError::Invalid(format!(
+                        "migration {}_{} is applied but its files are missing",
+                        applied.version, applied.name
+                    ))
+                })?;
+            let down = migration.down.as_ref().ok_or_else(|| {
+                MigrateError::Invalid(format!(
+                    "migration {}_{} has no .down.sql file",
+                    migration.version, migration.name
+                ))
+            })?;
+            let sql = fs::read_to_string(down)?;
+
+            let mut tx = conn.begin().await?;
+            sqlx::raw_sql(&sql).execute(&mut *tx).await?;
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE version = {}",
+                MIGRATIONS_TABLE,
+                backend.placeholder(1)
+            ))
+            .bind(&migration.version)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            reverted.push(migration.clone());
+        }
+
+        Ok(reverted)
+    }
+
+    /// Apply one migration and record it
+    async fn apply(&self, conn: &mut AnyConnection, migration: &Migration) -> Result<(), MigrateError> {
+        let sql = fs::read_to_string(&migration.up)?;
+        let backend = Backend::of(conn)?;
+
+        let mut tx = conn.begin().await?;
+        sqlx::raw_sql(&sql).execute(&mut *tx).await?;
+        sqlx::query(&format!(
+            "INSERT INTO {} (version, name, applied_at) VALUES ({}, {}, {})",
+            MIGRATIONS_TABLE,
+            backend.placeholder(1),
+            backend.placeholder(2),
+            backend.placeholder(3)
+        ))
+        .bind(&migration.version)
+        .bind(&migration.name)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Take the migration lock on a dedicated connection, waiting up to the
+    /// lock timeout for another process to finish. Everything done under the
+    /// lock uses that connection, so each step sees the schema left by the last.
+    async fn lock(&self) -> Result<PoolConnection<Any>, MigrateError> {
+        let mut conn = self.pool.acquire().await?;
+        self.ensure_migrations_table(&mut conn).await?;
+        let backend = Backend::of(&conn)?;
+        let started = Instant::now();
+        let sql = format!(
+            "INSERT INTO {} (id, owner, locked_at) VALUES (1, {}, {})",
+            LOCK_TABLE,
+            backend.placeholder(1),
+            backend.placeholder(2)
+        );
+
+        loop {
+            let inserted = sqlx::query(&sql)
+                .bind(format!("pid {}", std::process::id()))
+                .bind(Utc::now().to_rfc3339())
+                .execute(&mut *conn)
+                .await;
+
+            match inserted {
+                Ok(_) => return Ok(conn),
+                Err(e) if e.as_database_error().is_some_and(|e| e.is_unique_violation()) => {
+                    if started.elapsed() >= self.lock_timeout {
+                        let holder = self.lock_holder_on(&mut conn).await?.unwrap_or_default();
+                        return Err(MigrateError::Locked(holder));
+                    }
+                    actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn unlock(&self, conn: &mut AnyConnection) -> Result<(), MigrateError> {
+        sqlx::query(&format!("DELETE FROM {}", LOCK_TABLE))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Release the lock after `result`, which is returned as is. A failure to
+    /// unlock is only logged, so it never hides what happened to the migrations.
+    async fn release<T>(&self, conn: &mut AnyConnection, result: Result<T, MigrateError>) -> Result<T, MigrateError> {
+        if let Err(e) = self.unlock(conn).await {
+            log::warn!("Failed to release the migration lock, release it with `vsr migrate unlock`: {}", e);
+        }
+        result
+    }
+
+    async fn lock_holder_on(&self, conn: &mut AnyConnection) -> Result<Option<String>, MigrateError> {
+        self.ensure_migrations_table(conn).await?;
+        let holder = sqlx::query_as::<_, (String, String)>(&format!(
+            "SELECT owner, locked_at FROM {}",
+            LOCK_TABLE
+        ))
+        .fetch_optional(conn)
+        .await?;
+        Ok(holder.map(|(owner, locked_at)| format!("{} since {}", owner, locked_at)))
+    }
+
+    async fn ensure_migrations_table(&self, conn: &mut AnyConnection) -> Result<(), MigrateError> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version VARCHAR(32) PRIMARY KEY,
+                name VARCHAR(255) NOT NULL,
+                applied_at VARCHAR(64) NOT NULL
+            )",
+            MIGRATIONS_TABLE
+        ))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY,
+                owner VARCHAR(255) NOT NULL,
+                locked_at VARCHAR(64) NOT NULL
+            )",
+            LOCK_TABLE
+        ))
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Write an empty migration pair in `dir` to fill in by hand
+pub fn create_migration(dir: &Path, name: &str) -> Result<Migration, MigrateError> {
+    let header = "-- Write the migration here\n";
+    write_migration(dir, name, header.to_string(), header.to_string())
+}
+
+fn write_migration(dir: &Path, name: &str, up: String, down: String) -> Result<Migration, MigrateError> {
+    fs::create_dir_all(dir)?;
+    let version = next_version(dir, Utc::now().format("%Y%m%d%H%M%S").to_string())?;
+    let name = sanitize_name(name);
+    let up_path = dir.join(format!("{}_{}.up.sql", version, name));
+    let down_path = dir.join(format!("{}_{}.down.sql", version, name));
+    fs::write(&up_path, up)?;
+    fs::write(&down_path, down)?;
+
+    Ok(Migration {
+        version,
+        name,
+        up: up_path,
+        down: Some(down_path),
+    })
+}
+
+/// `timestamp`, or one past the newest version in `dir` when that is not
+/// older, so two migrations written within a second still sort in order
+fn next_version(dir: &Path, timestamp: String) -> Result<String, MigrateError> {
+    let mut newest: Option<u64> = None;
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        let version = file_name.to_str().and_then(|n| n.split_once('_')).and_then(|(v, _)| v.parse::<u64>().ok());
+        newest = newest.max(version);
+    }
+    match (newest, timestamp.parse::<u64>()) {
+        (Some(newest), Ok(now)) if newest >= now => Ok((newest + 1).to_string()),
+        _ => Ok(timestamp),
+    }
+}
+
+/// Put tables after the new tables they reference, so foreign keys resolve
+fn order_by_references(mut tables: Vec<TableSchema>) -> Vec<TableSchema> {
+    let mut ordered: Vec<TableSchema> = vec![];
+    while !tables.is_empty() {
+        let ready = tables.iter().position(|table| {
+            table.columns.iter().filter_map(|c| c.references).all(|fk| {
+                fk.table == table.name
+                    || !tables.iter().any(|t| t.name == fk.table)
+            })
+        });
+        // A reference cycle cannot be ordered, so keep the declared order
+        let next = ready.unwrap_or(0);
+        ordered.push(tables.remove(next));
+    }
+    ordered
+}
+
+fn render(statements: &[String]) -> String {
+    let mut sql = String::from("-- Generated by very_simple_rest\n\n");
+    for statement in statements {
+        sql.push_str(statement);
+        sql.push_str(";\n\n");
+    }
+    sql
+}
+
+/// Keep migration names to lowercase letters, digits and underscores
+fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if name.is_empty() { "migration".to_string() } else { name }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::any::AnyPoolOptions;
+
+    const ID: ColumnDef = ColumnDef::primary_key("id");
+    const POST_V1: TableSchema = TableSchema {
+        name: "post",
+        columns: &[ID, ColumnDef::new("title", ColumnType::Text)],
+        unique: &[],
+    };
+    const POST_V2: TableSchema = TableSchema {
+        name: "post",
+        columns: &[
+            ID,
+            ColumnDef::new("title", ColumnType::Text),
+            ColumnDef::new("score", ColumnType::Real),
+            ColumnDef { unique: true, nullable: true, ..ColumnDef::new("slug", ColumnType::Text) },
+        ],
+        unique: &[],
+    };
+
+    /// A fresh SQLite file and migrations directory
+    async fn scratch() -> (AnyPool, PathBuf) {
+        sqlx::any::install_default_drivers();
+        let dir = std::env::temp_dir().join(format!("vsr-migrate-{:x}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite:{}?mode=rwc", dir.join("app.db").display());
+        let pool = AnyPoolOptions::new().max_connections(4).connect(&url).await.unwrap();
+        (pool, dir.join("migrations"))
+    }
+
+    async fn columns(pool: &AnyPool) -> Vec<String> {
+        sqlx::query_scalar("SELECT name FROM pragma_table_info('post') ORDER BY cid").fetch_all(pool).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn syncs_adds_columns_and_reverts() {
+        let (pool, dir) = scratch().await;
+
+        let created = Migrator::new(pool.clone(), &dir).table(POST_V1).sync("create post").await.unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].name, "create_post");
+        assert!(fs::read_to_string(&created[0].up).unwrap().contains("CREATE TABLE post"));
+        sqlx::query("INSERT INTO post (title) VALUES ('kept')").execute(&pool).await.unwrap();
+
+        // Generated within the same second, yet ordered after the first
+        let migrator = Migrator::new(pool.clone(), &dir).table(POST_V2);
+        let added = migrator.generate("add score").await.unwrap().unwrap();
+        assert!(added.version > created[0].version);
+        let up = fs::read_to_string(&added.up).unwrap();
+        assert!(up.contains("ALTER TABLE post ADD COLUMN score REAL NOT NULL DEFAULT 0"));
+        assert!(up.contains("CREATE UNIQUE INDEX post_slug_key ON post (slug)"));
+        assert_eq!(migrator.pending().await.unwrap().len(), 1);
+        assert!(matches!(migrator.generate("again").await, Err(MigrateError::Pending(_))));
+
+        assert_eq!(migrator.run().await.unwrap().len(), 1);
+        assert_eq!(columns(&pool).await, ["id", "title", "score", "slug"]);
+        assert!(migrator.generate("nothing").await.unwrap().is_none());
+
+        assert_eq!(migrator.revert(1).await.unwrap()[0].version, added.version);
+        assert_eq!(columns(&pool).await, ["id", "title"]);
+        let status = migrator.status().await.unwrap();
+        assert!(status[0].applied_at.is_some() && status[1].applied_at.is_none());
+
+        migrator.run().await.unwrap();
+        assert_eq!(migrator.redo().await.unwrap().unwrap().version, added.version);
+        assert_eq!(columns(&pool).await.len(), 4);
+        let title: String = sqlx::query_scalar("SELECT title FROM post").fetch_one(&pool).await.unwrap();
+        assert_eq!(title, "kept");
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[actix_web::test]
+    async fn lock_blocks_other_migrators_until_released() {
+        let (pool, dir) = scratch().await;
+        let holder = Migrator::new(pool.clone(), &dir).table(POST_V1);
+        let mut conn = holder.lock().await.unwrap();
+
+        let other = Migrator::new(pool.clone(), &dir).table(POST_V1).with_lock_timeout(Duration::ZERO);
+        assert!(matches!(other.sync("create post").await, Err(MigrateError::Locked(_))));
+        assert!(other.lock_holder().await.unwrap().unwrap().starts_with("pid "));
+
+        holder.release(&mut conn, Ok(())).await.unwrap();
+        assert_eq!(other.sync("create post").await.unwrap().len(), 1);
+        assert!(other.lock_holder().await.unwrap().is_none());
+
+        // A lock left by a dead process is released by force
+        drop(holder.lock().await.unwrap());
+        other.force_unlock().await.unwrap();
+        other.run().await.unwrap();
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[actix_web::test]
+    async fn unlock_failure_keeps_the_migration_error() {
+        let (pool, dir) = scratch().await;
+        let migrator = Migrator::new(pool, &dir);
+        let mut conn = migrator.lock().await.unwrap();
+        sqlx::query(&format!("DROP TABLE {}", LOCK_TABLE)).execute(&mut *conn).await.unwrap();
+
+        let result: Result<(), _> = migrator.release(&mut conn, Err(MigrateError::Invalid("boom".into()))).await;
+        assert!(matches!(result, Err(MigrateError::Invalid(msg)) if msg == "boom"));
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
@@ -0,0 +1,749 @@
+//! OpenAPI 3.1 document for every resource mounted through `configure`.
+//!
+//! The document is built from the [`resource`](crate::resource) registry on each
+//! request to `/openapi.json`, so it always matches the resources the server has
+//! configured. With the `swagger-ui` or `redoc` features, a browsable page is
+//! served next to it at `/docs` or `/redoc`.
+//!
+//! ```rust,ignore
+//! App::new()
+//!     .configure(OpenApi::new("Blog API", "1.0.0").base_path("/api").routes())
+//!     .service(
+//!         web::scope("/api")
+//!             .configure(|cfg| auth::auth_routes(cfg, pool.clone()))
+//!             .configure(|cfg| Post::configure(cfg, sqlite_pool.clone())),
+//!     )
+//! ```
+
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::aggregate::TOTAL_COUNT_HEADER;
+use crate::bulk::MAX_IMPORT_SIZE;
+use crate::codec::Codec;
+use crate::json_schema::{column_schema, full_schema, is_read_only, patch_schema};
+use crate::migrate::{ColumnDef, ColumnType};
+use crate::resource::{registered, OperationMeta, ResourceMeta};
+
+/// Settings for the generated document
+#[derive(Debug, Clone)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    base_path: String,
+    auth: bool,
+}
+
+impl OpenApi {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            base_path: String::new(),
+            auth: true,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Scope the resources and `auth_routes` are mounted in, such as `/api`
+    pub fn base_path(mut self, base_path: impl Into<String>) -> Self {
+        self.base_path = base_path.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Leave the `/auth` endpoints out, for apps that don't mount `auth_routes`
+    pub fn without_auth(mut self) -> Self {
+        self.auth = false;
+        self
+    }
+
+    /// Build the document from every resource registered so far
+    pub fn document(&self) -> Value {
+        self.document_for(&registered())
+    }
+
+    /// Build the document for the given resources
+    pub fn document_for(&self, resources: &[&ResourceMeta]) -> Value {
+        let mut paths = Map::new();
+        let mut schemas = Map::new();
+        let mut tags = vec![];
+
+        for meta in resources {
+            resource_schemas(meta, &mut schemas);
+            resource_paths(meta, &mut paths);
+            tags.push(json!({ "name": meta.name }));
+        }
+
+        if self.auth {
+            auth_schemas(&mut schemas);
+            auth_paths(&mut paths);
+            tags.push(json!({ "name": "auth" }));
+        }
+
+        let mut info = json!({ "title": self.title, "version": self.version });
+        if let Some(description) = &self.description {
+            info["description"] = json!(description);
+        }
+
+        let server = if self.base_path.is_empty() { "/" } else { self.base_path.as_str() };
+
+        json!({
+            "openapi": "3.1.0",
+            "info": info,
+            "servers": [{ "url": server }],
+            "tags": tags,
+            "paths": paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+                }
+            }
+        })
+    }
+
+    /// Route configuration serving `/openapi.json`, plus `/docs` with the
+    /// `swagger-ui` feature and `/redoc` with the `redoc` feature
+    pub fn routes(self) -> impl FnOnce(&mut web::ServiceConfig) {
+        move |cfg| {
+            let spec = self.clone();
+            cfg.route(
+                "/openapi.json",
+                web::get().to(move || {
+                    let document = spec.document();
+                    async move { HttpResponse::Ok().json(document) }
+                }),
+            );
+
+            #[cfg(feature = "swagger-ui")]
+            {
+                let html = swagger_ui_html(&self.title);
+                cfg.route(
+                    "/docs",
+                    web::get().to(move || {
+                        let html = html.clone();
+                        async move { HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html) }
+                    }),
+                );
+            }
+
+            #[cfg(feature = "redoc")]
+            {
+                let html = redoc_html(&self.title);
+                cfg.route(
+                    "/redoc",
+                    web::get().to(move || {
+                        let html = html.clone();
+                        async move { HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html) }
+                    }),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "swagger-ui")]
+fn swagger_ui_html(title: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>{title}</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({{ url: "openapi.json", dom_id: "#swagger-ui" }});</script>
+</body>
+</html>"##
+    )
+}
+
+#[cfg(feature = "redoc")]
+fn redoc_html(title: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>{title}</title>
+</head>
+<body>
+  <redoc spec-url="openapi.json"></redoc>
+  <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+</body>
+</html>"##
+    )
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn resource_schemas(meta: &ResourceMeta, schemas: &mut Map<String, Value>) {
+    schemas.insert(meta.name.to_string(), full_schema(meta));
+    schemas.insert(format!("Partial{}", meta.name), patch_schema(meta));
+}
+
+/// Fill in security, role requirements and the auth error responses of an operation
+fn secure(operation: &mut Value, op: &OperationMeta) {
+    if op.public {
+        return;
+    }
+
+    operation["security"] = json!([{ "bearerAuth": [] }]);
+    operation["responses"]["401"] = json!({ "description": "Missing or invalid token" });
+    operation["responses"]["403"] = json!({ "description": "Insufficient privileges" });
+
+    let mut requirements = vec![];
+    if let Some(roles) = &op.roles {
+        operation["x-required-roles"] = json!({ "any": roles.any, "all": roles.all });
+        if !roles.any.is_empty() {
+            requirements.push(format!("any role of: {}", roles.any.join(", ")));
+        }
+        if !roles.all.is_empty() {
+            requirements.push(format!("all roles of: {}", roles.all.join(", ")));
+        }
+    }
+    if let Some(permissions) = &op.permissions {
+        operation["x-required-permissions"] = json!({ "any": permissions.any, "all": permissions.all });
+        if !permissions.any.is_empty() {
+            requirements.push(format!("any permission of: {}", permissions.any.join(", ")));
+        }
+        if !permissions.all.is_empty() {
+            requirements.push(format!("all permissions of: {}", permissions.all.join(", ")));
+        }
+    }
+    if !requirements.is_empty() {
+        operation["description"] = json!(format!("Requires {}.", requirements.join("; ")));
+    }
+}
+
+fn id_parameter(name: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "int64" }
+    })
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({ "required": true, "content": { "application/json": { "schema": schema } } })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+/// The same schema under every compiled-in `Codec`
+fn codec_content(schema: Value) -> Value {
+    let content: Map<String, Value> = Codec::ALL
+        .iter()
+        .map(|codec| (codec.media_type().to_string(), json!({ "schema": schema })))
+        .collect();
+    Value::Object(content)
+}
+
+fn payload_body(schema: Value) -> Value {
+    json!({ "required": true, "content": codec_content(schema) })
+}
+
+fn rows_response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": codec_content(schema) })
+}
+
+fn resource_paths(meta: &ResourceMeta, paths: &mut Map<String, Value>) {
+    let lower = meta.name.to_lowercase();
+    let item_path = format!("{}/{{id}}", meta.path);
+    let mut collection = Map::new();
+    let mut item = Map::new();
+
+    for op in meta.operations {
+        let (method, mut operation) = match op.name {
+            "list" => (
+                "get",
+                json!({
+                    "summary": format!("List {}", meta.table),
+                    "parameters": filter_parameters(meta),
+                    "responses": {
+                        "200": rows_response(
+                            "The matching rows in primary key order",
+                            json!({ "type": "array", "items": schema_ref(meta.name) })
+                        ),
+                        "400": { "description": "Unknown filter or invalid value" }
+                    }
+                }),
+            ),
+            "get" => (
+                "get",
+                json!({
+                    "summary": format!("Get a {} by id", lower),
+                    "parameters": [id_parameter("id")],
+                    "responses": {
+                        "200": rows_response("The row", schema_ref(meta.name)),
+                        "404": { "description": "Not found" }
+                    }
+                }),
+            ),
+            "create" => {
+                let mut operation = json!({
+                    "summary": format!("Create a {}", lower),
+                    "requestBody": payload_body(schema_ref(meta.name)),
+                    "responses": {
+                        "201": { "description": "Created" },
+                        "400": { "description": "Invalid request" }
+                    }
+                });
+                let unique: Vec<_> = meta.columns().iter().filter(|c| c.unique).map(|c| c.name).collect();
+                if !unique.is_empty() && meta.operation("update").is_some() {
+                    operation["parameters"] = json!([{
+                        "name": "on_conflict",
+                        "in": "query",
+                        "required": false,
+                        "description": "Update the existing row with the same value in this unique column instead of failing",
+                        "schema": { "type": "string", "enum": unique }
+                    }]);
+                    operation["responses"]["200"] = json!({ "description": "Existing row updated" });
+                }
+                ("post", operation)
+            }
+            "update" => (
+                "put",
+                json!({
+                    "summary": format!("Replace a {}", lower),
+                    "parameters": [id_parameter("id")],
+                    "requestBody": payload_body(schema_ref(meta.name)),
+                    "responses": { "200": { "description": "Updated" } }
+                }),
+            ),
+            "patch" => (
+                "patch",
+                json!({
+                    "summary": format!("Update some fields of a {}", lower),
+                    "parameters": [id_parameter("id")],
+                    "requestBody": payload_body(schema_ref(&format!("Partial{}", meta.name))),
+                    "responses": {
+                        "200": { "description": "Updated" },
+                        "404": { "description": "Not found" }
+                    }
+                }),
+            ),
+            "delete" => (
+                "delete",
+                json!({
+                    "summary": format!("Delete a {}", lower),
+                    "parameters": [id_parameter("id")],
+                    "responses": { "200": { "description": "Deleted" } }
+                }),
+            ),
+            _ => continue,
+        };
+
+        if matches!(op.name, "create" | "update") && !meta.files.is_empty() {
+            operation["requestBody"]["content"]["multipart/form-data"] = multipart_content(meta);
+        }
+        operation["operationId"] = json!(format!("{}_{}", op.name, lower));
+        operation["tags"] = json!([meta.name]);
+        secure(&mut operation, op);
+
+        if op.name == "list" {
+            let mut head = json!({
+                "summary": format!("Count {}", meta.table),
+                "operationId": format!("count_{}_head", lower),
+                "tags": [meta.name],
+                "parameters": filter_parameters(meta),
+                "responses": {
+                    "200": {
+                        "description": "The number of rows `list` returns, without a body",
+                        "headers": { TOTAL_COUNT_HEADER: { "schema": { "type": "integer", "format": "int64" } } }
+                    }
+                }
+            });
+            secure(&mut head, op);
+            collection.insert("head".to_string(), head);
+        }
+        match op.name {
+            "list" | "create" => collection.insert(method.to_string(), operation),
+            _ => item.insert(method.to_string(), operation),
+        };
+    }
+
+    if !collection.is_empty() {
+        paths.insert(meta.path.to_string(), Value::Object(collection));
+    }
+    if !item.is_empty() {
+        paths.insert(item_path, Value::Object(item));
+    }
+
+    let writes: Vec<_> = ["create", "update", "patch"].into_iter().filter(|op| meta.operation(op).is_some()).collect();
+    if !writes.is_empty() {
+        let mut operation = json!({
+            "summary": format!("JSON Schema for {} payloads", lower),
+            "operationId": format!("schema_{}", lower),
+            "tags": [meta.name],
+            "responses": {
+                "200": json_response(
+                    &format!("Draft 2020-12 schema with `$defs` for {}", writes.join(", ")),
+                    json!({ "type": "object" }),
+                )
+            }
+        });
+        if writes.iter().all(|op| meta.operation(op).is_some_and(|op| !op.public)) {
+            operation["security"] = json!([{ "bearerAuth": [] }]);
+            operation["responses"]["401"] = json!({ "description": "Missing or invalid token" });
+            operation["responses"]["403"] = json!({ "description": "Insufficient privileges" });
+            operation["description"] = json!(format!("Requires the checks of any of: {}.", writes.join(", ")));
+        }
+        paths.insert(format!("{}/_schema", meta.path), json!({ "get": operation }));
+    }
+
+    if let Some(create) = meta.operation("create") {
+        let mut operation = json!({
+            "summary": format!("Import {} rows from CSV or NDJSON", lower),
+            "description": "Every line is validated first; nothing is inserted unless all lines are valid. \
+                Rows are then inserted in one transaction. Ids and timestamps are ignored.",
+            "operationId": format!("import_{}", lower),
+            "tags": [meta.name],
+            "parameters": [format_parameter()],
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "text/csv": { "schema": { "type": "string" } },
+                    "application/x-ndjson": { "schema": { "type": "string" } }
+                }
+            },
+            "responses": {
+                "200": json_response("Rows inserted", import_report()),
+                "413": { "description": format!("Upload larger than {} MiB", MAX_IMPORT_SIZE >> 20) },
+                "422": json_response("Invalid lines; nothing was inserted", import_report())
+            }
+        });
+        secure(&mut operation, create);
+        paths.insert(format!("{}/import", meta.path), json!({ "post": operation }));
+    }
+
+    if let Some(get) = meta.operation("get") {
+        for file in meta.files {
+            let mut operation = json!({
+                "summary": format!("Download the {} of a {}", file.field, lower),
+                "operationId": format!("download_{}_{}", lower, file.field),
+                "tags": [meta.name],
+                "parameters": [id_parameter("id")],
+                "responses": {
+                    "200": {
+                        "description": "The file, with its uploaded name in `Content-Disposition`",
+                        "content": { "application/octet-stream": { "schema": { "type": "string", "format": "binary" } } }
+                    },
+                    "404": { "description": "No such row, or no file uploaded" }
+                }
+            });
+            secure(&mut operation, get);
+            paths.insert(format!("{}/{{id}}/{}", meta.path, file.field), json!({ "get": operation }));
+        }
+    }
+
+    // Exports, aggregates, counts, event streams and nested routes share the `list` operation's access rules
+    let Some(list) = meta.operation("list") else { return };
+
+    let mut parameters = vec![format_parameter()];
+    parameters.extend(filter_parameters(meta));
+    let mut operation = json!({
+        "summary": format!("Export {} rows as CSV or NDJSON", lower),
+        "operationId": format!("export_{}", lower),
+        "tags": [meta.name],
+        "parameters": parameters,
+        "responses": {
+            "200": {
+                "description": "Streamed matching rows in primary key order, without hidden fields; \
+                    CSV starts with a header row",
+                "content": {
+                    "text/csv": { "schema": { "type": "string" } },
+                    "application/x-ndjson": { "schema": { "type": "string" } }
+                }
+            }
+        }
+    });
+    secure(&mut operation, list);
+    paths.insert(format!("{}/export", meta.path), json!({ "get": operation }));
+
+    let mut operation = json!({
+        "summary": format!("Count {}", meta.table),
+        "operationId": format!("count_{}", lower),
+        "tags": [meta.name],
+        "parameters": filter_parameters(meta),
+        "responses": {
+            "200": rows_response(
+                "The number of rows `list` returns",
+                json!({
+                    "type": "object",
+                    "properties": { "count": { "type": "integer", "format": "int64" } },
+                    "required": ["count"]
+                })
+            )
+        }
+    });
+    secure(&mut operation, list);
+    paths.insert(format!("{}/count", meta.path), json!({ "get": operation }));
+
+    let numeric: Vec<_> = meta
+        .columns()
+        .iter()
+        .filter(|c| matches!(c.column_type, ColumnType::Integer | ColumnType::Real) && !meta.is_hidden(c.name))
+        .map(|c| c.name)
+        .collect();
+    let mut parameters = vec![
+        list_parameter("count", "`*`, or comma-separated columns to count non-null values of"),
+        list_parameter("sum", &format!("Numeric columns to sum: {}", numeric.join(", "))),
+        list_parameter("avg", &format!("Numeric columns to average: {}", numeric.join(", "))),
+        list_parameter("group_by", "Columns to group and order by"),
+    ];
+    parameters.extend(filter_parameters(meta));
+    let mut operation = json!({
+        "summary": format!("Count, sum and average {} rows", lower),
+        "operationId": format!("aggregate_{}", lower),
+        "tags": [meta.name],
+        "parameters": parameters,
+        "responses": {
+            "200": rows_response(
+                "One row per group, or a single row without `group_by`, holding the `group_by` columns, \
+                `count` for `*`, `count_<column>`, `sum_<column>` and `avg_<column>`",
+                json!({
+                    "type": "array",
+                    "items": { "type": "object", "additionalProperties": true }
+                })
+            ),
+            "400": { "description": "Unknown or non-numeric column, nothing to aggregate, or an invalid filter" }
+        }
+    });
+    secure(&mut operation, list);
+    paths.insert(format!("{}/aggregate", meta.path), json!({ "get": operation }));
+
+    let mut operation = json!({
+        "summary": format!("Stream {} changes as Server-Sent Events", lower),
+        "description": "Resume with `Last-Event-ID` or `?last_event_id=`. \
+            The same events are sent as JSON text messages by the WebSocket at `events/ws`.",
+        "operationId": format!("events_{}", lower),
+        "tags": [meta.name],
+        "parameters": [{
+            "name": "last_event_id",
+            "in": "query",
+            "required": false,
+            "schema": { "type": "integer", "format": "int64" }
+        }],
+        "responses": {
+            "200": {
+                "description": "One `create`, `upsert`, `update`, `patch`, `delete` or `reset` event per change",
+                "content": { "text/event-stream": { "schema": { "type": "string" } } }
+            }
+        }
+    });
+    secure(&mut operation, list);
+    paths.insert(format!("{}/events", meta.path), json!({ "get": operation }));
+
+    for relation in meta.relations {
+        let Some(nested_path) = relation.nested_path else { continue };
+        let mut operation = json!({
+            "summary": format!("List {} by {}", meta.table, relation.field),
+            "operationId": format!("list_{}_by_{}", lower, relation.field),
+            "tags": [meta.name],
+            "parameters": [id_parameter("parent_id")],
+            "responses": {
+                "200": rows_response("Rows belonging to the parent", json!({ "type": "array", "items": schema_ref(meta.name) }))
+            }
+        });
+        secure(&mut operation, list);
+        paths.insert(nested_path.to_string(), json!({ "get": operation }));
+    }
+}
+
+/// `multipart/form-data` with a text part per writable column and a binary part per file field
+fn multipart_content(meta: &ResourceMeta) -> Value {
+    let mut properties = Map::new();
+    let mut encoding = Map::new();
+    for column in meta.columns() {
+        if let Some(file) = meta.file(column.name) {
+            let mut description = format!("Up to {} bytes", file.max_size);
+            if !file.types.is_empty() {
+                description.push_str(&format!(" of {}", file.types.join(", ")));
+                encoding.insert(column.name.to_string(), json!({ "contentType": file.types.join(", ") }));
+            }
+            properties.insert(
+                column.name.to_string(),
+                json!({ "type": "string", "format": "binary", "description": description }),
+            );
+        } else if !is_read_only(meta, column) {
+            properties.insert(column.name.to_string(), column_schema(column));
+        }
+    }
+    json!({ "schema": { "type": "object", "properties": properties }, "encoding": encoding })
+}
+
+/// `?column=value` for every column that can be filtered on, and `?column_contains=` for text columns
+fn filter_parameters(meta: &ResourceMeta) -> Vec<Value> {
+    let mut parameters = vec![];
+    for column in meta.columns().iter().filter(|c| !meta.is_hidden(c.name) && meta.file(c.name).is_none()) {
+        parameters.push(json!({
+            "name": column.name,
+            "in": "query",
+            "required": false,
+            "schema": column_schema(&ColumnDef { nullable: false, ..*column })
+        }));
+        if column.column_type == ColumnType::Text {
+            parameters.push(json!({
+                "name": format!("{}_contains", column.name),
+                "in": "query",
+                "required": false,
+                "schema": { "type": "string" }
+            }));
+        }
+    }
+    parameters
+}
+
+fn list_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": { "type": "string" }
+    })
+}
+
+fn format_parameter() -> Value {
+    json!({
+        "name": "format",
+        "in": "query",
+        "required": false,
+        "schema": { "type": "string", "enum": ["csv", "ndjson"], "default": "csv" }
+    })
+}
+
+fn import_report() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "inserted": { "type": "integer" },
+            "errors": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": { "line": { "type": "integer" }, "error": { "type": "string" } },
+                    "required": ["line", "error"]
+                }
+            }
+        },
+        "required": ["inserted", "errors"]
+    })
+}
+
+fn auth_schemas(schemas: &mut Map<String, Value>) {
+    let credentials = json!({
+        "type": "object",
+        "properties": {
+            "email": { "type": "string" },
+            "password": { "type": "string", "format": "password" }
+        },
+        "required": ["email", "password"]
+    });
+    schemas.insert("RegisterInput".to_string(), credentials.clone());
+    schemas.insert("LoginInput".to_string(), credentials);
+    schemas.insert(
+        "Token".to_string(),
+        json!({ "type": "object", "properties": { "token": { "type": "string" } }, "required": ["token"] }),
+    );
+    schemas.insert(
+        "UserContext".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "format": "int64" },
+                "roles": { "type": "array", "items": { "type": "string" } },
+                "permissions": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["id", "roles"]
+        }),
+    );
+}
+
+fn auth_paths(paths: &mut Map<String, Value>) {
+    paths.insert(
+        "/auth/register".to_string(),
+        json!({ "post": {
+            "summary": "Register a user",
+            "operationId": "register",
+            "tags": ["auth"],
+            "requestBody": json_body(schema_ref("RegisterInput")),
+            "responses": { "201": { "description": "Registered" } }
+        }}),
+    );
+    paths.insert(
+        "/auth/login".to_string(),
+        json!({ "post": {
+            "summary": "Log in and get a JWT",
+            "operationId": "login",
+            "tags": ["auth"],
+            "requestBody": json_body(schema_ref("LoginInput")),
+            "responses": {
+                "200": json_response("Bearer token", schema_ref("Token")),
+                "401": { "description": "Invalid credentials" }
+            }
+        }}),
+    );
+    paths.insert(
+        "/auth/me".to_string(),
+        json!({ "get": {
+            "summary": "The authenticated user",
+            "operationId": "me",
+            "tags": ["auth"],
+            "security": [{ "bearerAuth": [] }],
+            "responses": {
+                "200": json_response("Claims of the bearer token", schema_ref("UserContext")),
+                "401": { "description": "Missing or invalid token" }
+            }
+        }}),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::POST;
+
+    #[test]
+    fn documents_operations_and_requirements() {
+        let doc = OpenApi::new("Test", "1").base_path("/api/").without_auth().document_for(&[&POST]);
+
+        assert_eq!(doc["servers"][0]["url"], "/api");
+        assert!(doc["paths"]["/post"]["get"].get("security").is_none());
+        assert_eq!(doc["paths"]["/post"]["post"]["security"][0]["bearerAuth"], json!([]));
+        assert_eq!(doc["paths"]["/post"]["post"]["parameters"][0]["schema"]["enum"], json!(["slug"]));
+        assert_eq!(doc["paths"]["/post/{id}"]["put"]["x-required-roles"]["any"], json!(["editor"]));
+        assert_eq!(doc["paths"]["/post/_schema"]["get"]["security"][0]["bearerAuth"], json!([]));
+        assert!(doc["paths"].get("/auth/login").is_none());
+
+        let filters: Vec<_> = doc["paths"]["/post"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(filters, ["id", "slug", "slug_contains", "title", "title_contains", "score"]);
+        assert_eq!(doc["paths"]["/post/export"]["get"]["parameters"][4]["name"], "title");
+
+        let post = &doc["components"]["schemas"]["Post"];
+        assert_eq!(post["properties"]["id"]["type"], json!(["integer", "null"]));
+        assert_eq!(post["properties"]["id"]["readOnly"], true);
+        assert_eq!(post["required"], json!(["slug", "title"]));
+        assert!(doc["components"]["schemas"]["PartialPost"]["properties"].get("id").is_none());
+    }
+}
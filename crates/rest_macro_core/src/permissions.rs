@@ -0,0 +1,186 @@
+use actix_web::{web, HttpRequest};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::auth::UserContext;
+use crate::roles::RoleHierarchy;
+
+/// Permissions required for a generated operation, such as `post:write`.
+///
+/// The user must hold at least one permission from `any` (when it is not
+/// empty) and every permission in `all`. Permissions come from the token's
+/// `permissions` claim and from the [`PermissionMap`] entries of the roles the
+/// user holds or outranks. Superuser roles pass every check.
+#[derive(Debug, Clone, Copy)]
+pub struct PermissionRequirement {
+    pub any: &'static [&'static str],
+    pub all: &'static [&'static str],
+}
+
+impl PermissionRequirement {
+    pub fn is_satisfied_by(
+        &self,
+        user: &UserContext,
+        permissions: &PermissionMap,
+        hierarchy: &RoleHierarchy,
+    ) -> bool {
+        if hierarchy.is_superuser(&user.roles) {
+            return true;
+        }
+
+        let granted = permissions.granted_to(user, hierarchy);
+        (self.any.is_empty() || self.any.iter().any(|p| granted.contains(p)))
+            && self.all.iter().all(|p| granted.contains(p))
+    }
+}
+
+/// Permission sets granted to each role.
+///
+/// Generated handlers look the map up per request: a `web::Data<PermissionMap>`
+/// registered as app data wins, then the `ROLE_PERMISSIONS` environment
+/// variable, then an empty map where roles grant no permissions.
+///
+/// ```
+/// use rest_macro_core::permissions::PermissionMap;
+///
+/// let map = PermissionMap::parse("editor = post:read post:write; user = post:read").unwrap();
+/// assert!(map.role_grants("editor", "post:write"));
+/// assert!(!map.role_grants("user", "post:write"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PermissionMap {
+    roles: HashMap<String, HashSet<String>>,
+}
+
+/// Error returned when a permission map declaration cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionMapError(String);
+
+impl fmt::Display for PermissionMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid role permissions: {}", self.0)
+    }
+}
+
+impl std::error::Error for PermissionMapError {}
+
+impl PermissionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grant `permissions` to everyone holding `role`
+    pub fn grant<I, S>(mut self, role: impl Into<String>, permissions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.roles
+            .entry(role.into())
+            .or_default()
+            .extend(permissions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Parse a declaration such as `"editor = post:read post:write; user = post:read"`.
+    ///
+    /// Entries are separated by semicolons, and permissions by spaces or commas.
+    pub fn parse(spec: &str) -> Result<Self, PermissionMapError> {
+        let mut map = Self::new();
+
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (role, permissions) = entry.split_once('=').ok_or_else(|| {
+                PermissionMapError(format!("expected `role = permission ...`, found `{}`", entry))
+            })?;
+
+            let role = role.trim();
+            if role.is_empty() {
+                return Err(PermissionMapError(format!("missing role name in `{}`", entry)));
+            }
+
+            let permissions: Vec<&str> = permissions
+                .split([' ', ','])
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .collect();
+            map = map.grant(role, permissions);
+        }
+
+        Ok(map)
+    }
+
+    /// Whether `role` itself is granted `permission`
+    pub fn role_grants(&self, role: &str, permission: &str) -> bool {
+        self.roles.get(role).is_some_and(|granted| granted.contains(permission))
+    }
+
+    /// Every permission `user` holds, directly or through the roles it holds or outranks
+    pub fn granted_to<'a>(&'a self, user: &'a UserContext, hierarchy: &RoleHierarchy) -> HashSet<&'a str> {
+        let mut granted: HashSet<&str> = user.permissions.iter().map(String::as_str).collect();
+        for (role, permissions) in &self.roles {
+            if hierarchy.grants(&user.roles, role) {
+                granted.extend(permissions.iter().map(String::as_str));
+            }
+        }
+        granted
+    }
+
+    /// The permission map that applies to a request
+    pub fn from_request(req: &HttpRequest) -> &PermissionMap {
+        match req.app_data::<web::Data<PermissionMap>>() {
+            Some(map) => map.get_ref(),
+            None => Self::from_env(),
+        }
+    }
+
+    /// The map declared by `ROLE_PERMISSIONS`, or an empty one
+    fn from_env() -> &'static PermissionMap {
+        static FROM_ENV: OnceLock<PermissionMap> = OnceLock::new();
+
+        FROM_ENV.get_or_init(|| {
+            let _ = dotenv::dotenv();
+
+            match env::var("ROLE_PERMISSIONS") {
+                Ok(spec) => Self::parse(&spec).unwrap_or_else(|e| {
+                    eprintln!("WARNING: {}. Roles will not grant any permissions", e);
+                    Self::default()
+                }),
+                Err(_) => Self::default(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(roles: &[&str], permissions: &[&str]) -> UserContext {
+        UserContext {
+            id: 1,
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn permissions_come_from_token_and_roles() {
+        let map = PermissionMap::parse("editor = post:write; user = post:read").unwrap();
+        let hierarchy = RoleHierarchy::parse("editor > user").unwrap();
+        let write = PermissionRequirement { any: &["post:write"], all: &[] };
+        let read = PermissionRequirement { any: &[], all: &["post:read"] };
+
+        assert!(write.is_satisfied_by(&user(&[], &["post:write"]), &map, &hierarchy));
+        assert!(write.is_satisfied_by(&user(&["editor"], &[]), &map, &hierarchy));
+        assert!(read.is_satisfied_by(&user(&["editor"], &[]), &map, &hierarchy));
+        assert!(!write.is_satisfied_by(&user(&["user"], &[]), &map, &hierarchy));
+    }
+
+    #[test]
+    fn rejects_malformed_declarations() {
+        assert!(PermissionMap::parse("editor post:write").is_err());
+        assert!(PermissionMap::parse(" = post:write").is_err());
+    }
+}
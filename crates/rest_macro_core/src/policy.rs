@@ -0,0 +1,420 @@
+//! Role grants stored in the database, editable at runtime.
+//!
+//! Without a [`PolicyStore`] registered as app data, generated handlers only
+//! enforce their compile-time `#[require_role]` requirements. With one, a
+//! `resource`/`operation` pair that has rows in the `permission` table is open
+//! to exactly the roles listed there, replacing the compile-time roles for that
+//! operation. Pairs without rows keep their compile-time requirement, and
+//! `public` operations stay public.
+//!
+//! The table is created by migrations like any other: add [`SCHEMA`] to the
+//! [`Migrator`](crate::migrate::Migrator) before connecting the store.
+//!
+//! ```rust,ignore
+//! Migrator::new(pool.clone(), "migrations").table(policy::SCHEMA).run().await?;
+//! let store = PolicyStore::connect(pool).await?;
+//! ```
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyPool, FromRow};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::auth::{require_superuser, UserContext};
+use crate::migrate::{Backend, ColumnDef, ColumnType, TableSchema};
+use crate::resource;
+use crate::roles::{RoleHierarchy, RoleRequirement};
+
+/// The `permission` table, for the [`Migrator`](crate::migrate::Migrator)
+pub const SCHEMA: TableSchema = TableSchema {
+    name: "permission",
+    columns: &[
+        ColumnDef::primary_key("id"),
+        ColumnDef::new("resource", ColumnType::Text),
+        ColumnDef::new("operation", ColumnType::Text),
+        ColumnDef::new("role", ColumnType::Text),
+    ],
+    unique: &[&["resource", "operation", "role"]],
+};
+
+/// One row of the `permission` table: `role` may perform `operation` on `resource`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PolicyGrant {
+    pub id: Option<i64>,
+    pub resource: String,
+    pub operation: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PolicyGrantInput {
+    pub resource: String,
+    pub operation: String,
+    pub role: String,
+}
+
+struct CachedGrants {
+    loaded_at: Instant,
+    /// Roles granted for each `(resource, operation)` pair
+    roles: HashMap<(String, String), Vec<String>>,
+}
+
+/// Cached view of the `permission` table.
+///
+/// Grants are loaded on first use and reloaded once the cache is older than
+/// its TTL (30 seconds by default), so edits made by other instances or
+/// directly in the database show up without a restart. Edits made through
+/// [`policy_routes`] take effect immediately.
+pub struct PolicyStore {
+    pool: AnyPool,
+    backend: Backend,
+    ttl: Duration,
+    cache: RwLock<Option<CachedGrants>>,
+    /// Bumped by every invalidation, so a reload that started before one is not cached
+    generation: AtomicU64,
+}
+
+impl PolicyStore {
+    /// A store backed by the `permission` table, which must already be migrated
+    pub async fn connect(pool: AnyPool) -> Result<Self, sqlx::Error> {
+        let backend = Backend::of_pool(&pool)
+            .await
+            .map_err(|e| sqlx::Error::Configuration(e.to_string().into()))?;
+        // Fail at startup rather than on the first request when the table is missing
+        sqlx::query("SELECT id FROM permission WHERE 1 = 0").execute(&pool).await?;
+
+        Ok(Self {
+            pool,
+            backend,
+            ttl: Duration::from_secs(30),
+            cache: RwLock::new(None),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    /// How long loaded grants are trusted before the table is read again
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Roles granted for `operation` on `resource`, or `None` when the table has no rows for it
+    pub async fn roles_for(
+        &self,
+        resource: &str,
+        operation: &str,
+    ) -> Result<Option<Vec<String>>, sqlx::Error> {
+        let key = (resource.to_string(), operation.to_string());
+
+        {
+            let cache = self.cache.read().unwrap();
+            if let Some(cached) = cache.as_ref()
+                && cached.loaded_at.elapsed() < self.ttl
+            {
+                return Ok(cached.roles.get(&key).cloned());
+            }
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let grants = sqlx::query_as::<_, PolicyGrant>("SELECT * FROM permission")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut roles: HashMap<(String, String), Vec<String>> = HashMap::new();
+        for grant in grants {
+            roles
+                .entry((grant.resource, grant.operation))
+                .or_default()
+                .push(grant.role);
+        }
+        let found = roles.get(&key).cloned();
+        self.cache_loaded(generation, roles);
+        Ok(found)
+    }
+
+    /// Cache grants read at `generation`, unless an edit landed while they were read
+    fn cache_loaded(&self, generation: u64, roles: HashMap<(String, String), Vec<String>>) {
+        let mut cache = self.cache.write().unwrap();
+        if self.generation.load(Ordering::Acquire) == generation {
+            *cache = Some(CachedGrants {
+                loaded_at: Instant::now(),
+                roles,
+            });
+        }
+    }
+
+    /// Drop the cached grants so the next check reads the table again
+    pub fn invalidate(&self) {
+        let mut cache = self.cache.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        *cache = None;
+    }
+}
+
+/// Decide whether `user` may perform `operation` on `resource`.
+///
+/// Used by generated handlers. `fallback` is the compile-time requirement,
+/// which applies when no [`PolicyStore`] is registered or the store has no
+/// grants for this operation.
+pub async fn authorize(
+    req: &HttpRequest,
+    user: &UserContext,
+    resource: &str,
+    operation: &str,
+    fallback: Option<RoleRequirement>,
+) -> Result<bool, sqlx::Error> {
+    let store = req.app_data::<web::Data<PolicyStore>>().map(|store| store.get_ref());
+    authorize_with(store, RoleHierarchy::from_request(req), user, resource, operation, fallback).await
+}
+
+/// [`authorize`] with the store and hierarchy passed in, for callers without an
+/// `HttpRequest` at hand such as GraphQL resolvers
+pub async fn authorize_with(
+    store: Option<&PolicyStore>,
+    hierarchy: &RoleHierarchy,
+    user: &UserContext,
+    resource: &str,
+    operation: &str,
+    fallback: Option<RoleRequirement>,
+) -> Result<bool, sqlx::Error> {
+    if let Some(store) = store
+        && let Some(roles) = store.roles_for(resource, operation).await?
+    {
+        return Ok(hierarchy.is_superuser(&user.roles)
+            || roles.iter().any(|role| hierarchy.grants(&user.roles, role)));
+    }
+
+    Ok(fallback.is_none_or(|requirement| requirement.is_satisfied_by(user, hierarchy)))
+}
+
+pub async fn list_grants(
+    req: HttpRequest,
+    user: UserContext,
+    store: web::Data<PolicyStore>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    match sqlx::query_as::<_, PolicyGrant>(
+        "SELECT * FROM permission ORDER BY resource, operation, role",
+    )
+    .fetch_all(&store.pool)
+    .await
+    {
+        Ok(grants) => HttpResponse::Ok().json(grants),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Grant a role an operation. The resource must be configured and the operation
+/// enabled on it and not public, since any other grant would never be consulted.
+pub async fn add_grant(
+    req: HttpRequest,
+    input: web::Json<PolicyGrantInput>,
+    user: UserContext,
+    store: web::Data<PolicyStore>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    let input = input.into_inner();
+    if input.role.trim().is_empty() {
+        return HttpResponse::BadRequest().body("role cannot be empty");
+    }
+    let Some(meta) = resource::lookup(&input.resource) else {
+        return HttpResponse::BadRequest().body(format!("unknown resource `{}`", input.resource));
+    };
+    match meta.operation(&input.operation) {
+        Some(operation) if operation.public => {
+            return HttpResponse::BadRequest().body(format!(
+                "`{}` on `{}` is public, so grants do not apply to it",
+                input.operation, input.resource
+            ));
+        }
+        Some(_) => {}
+        None => {
+            let operations: Vec<_> = meta.operations.iter().map(|op| op.name).collect();
+            return HttpResponse::BadRequest().body(format!(
+                "unknown operation `{}` for `{}`, expected one of: {}",
+                input.operation,
+                input.resource,
+                operations.join(", ")
+            ));
+        }
+    }
+
+    let backend = store.backend;
+    let sql = format!(
+        "INSERT INTO permission (resource, operation, role) VALUES ({}, {}, {})",
+        backend.placeholder(1),
+        backend.placeholder(2),
+        backend.placeholder(3)
+    );
+    let result = sqlx::query(&sql)
+        .bind(&input.resource)
+        .bind(&input.operation)
+        .bind(&input.role)
+        .execute(&store.pool)
+        .await;
+
+    match result {
+        Ok(_) => {
+            store.invalidate();
+            HttpResponse::Created().finish()
+        }
+        Err(e) if e.as_database_error().is_some_and(|e| e.is_unique_violation()) => {
+            HttpResponse::Conflict().body("Grant already exists")
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn remove_grant(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    user: UserContext,
+    store: web::Data<PolicyStore>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    match sqlx::query(&format!("DELETE FROM permission WHERE id = {}", store.backend.placeholder(1)))
+        .bind(path.into_inner())
+        .execute(&store.pool)
+        .await
+    {
+        Ok(res) if res.rows_affected() > 0 => {
+            store.invalidate();
+            HttpResponse::Ok().finish()
+        }
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Register the policy endpoints under `/policy`.
+///
+/// The [`PolicyStore`] itself must be registered as app data on the `App`, so
+/// the generated handlers in every scope can consult it.
+pub fn policy_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/policy", web::get().to(list_grants));
+    cfg.route("/policy", web::post().to(add_grant));
+    cfg.route("/policy/{id}", web::delete().to(remove_grant));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::issue_token;
+    use crate::migrate::Migrator;
+    use crate::test_fixtures::{COMMENT, POST};
+    use actix_web::{test, App};
+    use sqlx::any::AnyPoolOptions;
+
+    async fn store() -> PolicyStore {
+        sqlx::any::install_default_drivers();
+        let dir = std::env::temp_dir().join(format!("vsr-policy-{:x}", rand::random::<u64>()));
+        let pool = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        Migrator::new(pool.clone(), &dir).table(SCHEMA).sync("policy").await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+        PolicyStore::connect(pool).await.unwrap()
+    }
+
+    fn user(roles: &[&str]) -> UserContext {
+        UserContext { id: 1, roles: roles.iter().map(|r| r.to_string()).collect(), permissions: vec![] }
+    }
+
+    #[actix_web::test]
+    async fn grants_and_revokes_through_routes() {
+        resource::register(&POST);
+        resource::register(&COMMENT);
+        let store = web::Data::new(store().await);
+        let app = test::init_service(App::new().app_data(store.clone()).configure(policy_routes)).await;
+        let token = |roles: &[&str]| {
+            let roles = roles.iter().map(|r| r.to_string()).collect();
+            format!("Bearer {}", issue_token(1, roles, vec![], chrono::Duration::minutes(5)).unwrap())
+        };
+        let grant = |body: serde_json::Value, roles: &[&str]| {
+            test::TestRequest::post()
+                .uri("/policy")
+                .insert_header(("Authorization", token(roles)))
+                .set_json(body)
+                .to_request()
+        };
+        let patch = serde_json::json!({ "resource": "comment", "operation": "patch", "role": "user" });
+
+        assert_eq!(test::call_service(&app, grant(patch.clone(), &["user"])).await.status(), 403);
+        assert_eq!(test::call_service(&app, grant(patch.clone(), &["admin"])).await.status(), 201);
+        assert_eq!(test::call_service(&app, grant(patch.clone(), &["admin"])).await.status(), 409);
+        assert_eq!(store.roles_for("comment", "patch").await.unwrap(), Some(vec!["user".to_string()]));
+
+        // Grants that could never match a request
+        for (resource, operation) in [("comment", "purge"), ("comment", "delete"), ("comments", "patch"), ("post", "list")] {
+            let body = serde_json::json!({ "resource": resource, "operation": operation, "role": "user" });
+            let response = test::call_service(&app, grant(body, &["admin"])).await;
+            assert_eq!(response.status(), 400, "{} {}", resource, operation);
+        }
+
+        let list = test::TestRequest::get().uri("/policy").insert_header(("Authorization", token(&["admin"])));
+        let grants: Vec<PolicyGrant> = test::call_and_read_body_json(&app, list.to_request()).await;
+        assert_eq!(grants.len(), 1);
+
+        let revoke = || {
+            test::TestRequest::delete()
+                .uri(&format!("/policy/{}", grants[0].id.unwrap()))
+                .insert_header(("Authorization", token(&["admin"])))
+                .to_request()
+        };
+        assert_eq!(test::call_service(&app, revoke()).await.status(), 200);
+        assert_eq!(test::call_service(&app, revoke()).await.status(), 404);
+        assert_eq!(store.roles_for("comment", "patch").await.unwrap(), None);
+    }
+
+    async fn allowed(store: Option<&PolicyStore>, roles: &[&str], operation: &str) -> bool {
+        const ADMIN_ONLY: Option<RoleRequirement> = Some(RoleRequirement { any: &["admin"], all: &[] });
+        authorize_with(store, &RoleHierarchy::default(), &user(roles), "comment", operation, ADMIN_ONLY)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn grants_override_compile_time_roles() {
+        let store = store().await;
+        assert!(!allowed(Some(&store), &["user"], "delete").await);
+        assert!(allowed(Some(&store), &["admin"], "delete").await);
+
+        sqlx::query("INSERT INTO permission (resource, operation, role) VALUES ('comment', 'delete', 'user')")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        // Cached until invalidated
+        assert!(!allowed(Some(&store), &["user"], "delete").await);
+        store.invalidate();
+        assert!(allowed(Some(&store), &["user"], "delete").await);
+        assert!(!allowed(Some(&store), &["editor"], "delete").await);
+        // Superusers keep access, and operations without rows keep `#[require_role]`
+        assert!(allowed(Some(&store), &["admin"], "delete").await);
+        assert!(!allowed(Some(&store), &["user"], "update").await);
+        assert!(!allowed(None, &["user"], "delete").await);
+    }
+
+    #[actix_web::test]
+    async fn invalidation_discards_in_flight_reloads() {
+        let store = store().await;
+        let stale = HashMap::from([(("comment".to_string(), "delete".to_string()), vec!["user".to_string()])]);
+
+        let generation = store.generation.load(Ordering::Acquire);
+        store.invalidate();
+        store.cache_loaded(generation, stale.clone());
+        assert!(store.cache.read().unwrap().is_none());
+
+        store.cache_loaded(store.generation.load(Ordering::Acquire), stale);
+        assert!(store.cache.read().unwrap().is_some());
+    }
+}
@@ -0,0 +1,225 @@
+//! Filters shared by `GET /{table}`, `GET /{table}/count`, `HEAD /{table}`,
+//! `GET /{table}/export` and `GET /{table}/aggregate`.
+//!
+//! Every query parameter naming a column filters on it, as the GraphQL
+//! `filter` argument does: `?post_id=3` matches the column exactly and
+//! `?title_contains=rust` matches a text column by substring. Values are
+//! parsed by the column's type, and booleans are `true`, `false`, `1` or `0`.
+//! Hidden and file fields cannot be filtered on. Rows are returned in primary
+//! key order.
+//!
+//! ```text
+//! GET /comment?post_id=3&title_contains=rust
+//! GET /comment/count?post_id=3
+//! ```
+
+use std::collections::BTreeMap;
+
+use sqlx::error::BoxDynError;
+use sqlx::{Arguments, Database, Encode, Type};
+
+use crate::migrate::{Backend, ColumnDef, ColumnType};
+use crate::resource::ResourceMeta;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Equals,
+    Contains,
+}
+
+/// A filter value, typed by its column
+#[derive(Debug, Clone, PartialEq)]
+enum FilterValue {
+    Int(i64),
+    Real(f64),
+    Bool(bool),
+    Text(String),
+}
+
+/// The rows a list-like request covers
+#[derive(Debug)]
+pub struct ListQuery {
+    table: &'static str,
+    primary_key: &'static str,
+    conditions: Vec<(&'static str, Condition)>,
+    values: Vec<FilterValue>,
+}
+
+impl ListQuery {
+    /// Every row of the resource
+    pub fn all(meta: &'static ResourceMeta) -> Self {
+        ListQuery { table: meta.table, primary_key: meta.primary_key, conditions: vec![], values: vec![] }
+    }
+
+    /// Filters from the query string. `reserved` parameters, such as export's
+    /// `format`, belong to the handler and are skipped.
+    pub fn parse(meta: &'static ResourceMeta, params: &BTreeMap<String, String>, reserved: &[&str]) -> Result<Self, String> {
+        let mut query = Self::all(meta);
+        for (key, value) in params.iter().filter(|(key, _)| !reserved.contains(&key.as_str())) {
+            let (column, condition) = match meta.column(key) {
+                Some(column) => (column, Condition::Equals),
+                None => match key.strip_suffix("_contains").and_then(|name| meta.column(name)) {
+                    Some(column) if column.column_type == ColumnType::Text => (column, Condition::Contains),
+                    _ => return Err(format!("unknown filter `{}`", key)),
+                },
+            };
+            if meta.is_hidden(column.name) || meta.file(column.name).is_some() {
+                return Err(format!("cannot filter on `{}`", column.name));
+            }
+
+            let value = match condition {
+                Condition::Contains => FilterValue::Text(format!("%{}%", value)),
+                Condition::Equals => parse_value(column, value)?,
+            };
+            query.conditions.push((column.name, condition));
+            query.values.push(value);
+        }
+        Ok(query)
+    }
+
+    /// ` WHERE ...` over the filters, or nothing without any
+    pub fn where_sql(&self, backend: Backend) -> String {
+        let mut sql = String::new();
+        for (i, (column, condition)) in self.conditions.iter().enumerate() {
+            let op = match condition {
+                Condition::Equals => "=",
+                Condition::Contains => "LIKE",
+            };
+            sql.push_str(if i == 0 { " WHERE " } else { " AND " });
+            sql.push_str(&format!("{} {} {}", column, op, backend.placeholder(i + 1)));
+        }
+        sql
+    }
+
+    /// The matching rows
+    pub fn select_sql(&self, backend: Backend) -> String {
+        format!("SELECT * FROM {}{} ORDER BY {}", self.table, self.where_sql(backend), self.primary_key)
+    }
+
+    /// The number of matching rows
+    pub fn count_sql(&self, backend: Backend) -> String {
+        format!("SELECT COUNT(*) FROM {}{}", self.table, self.where_sql(backend))
+    }
+
+    /// One page of the matching rows, after a primary key and up to a limit,
+    /// bound by [`page_arguments`](Self::page_arguments)
+    pub fn page_sql(&self, backend: Backend) -> String {
+        let n = self.values.len();
+        format!(
+            "SELECT * FROM {}{} {} {} > {} ORDER BY {} LIMIT {}",
+            self.table,
+            self.where_sql(backend),
+            if n == 0 { "WHERE" } else { "AND" },
+            self.primary_key,
+            backend.placeholder(n + 1),
+            self.primary_key,
+            backend.placeholder(n + 2)
+        )
+    }
+
+    /// The filter values, for the queries above
+    pub fn arguments<'q, DB>(&self) -> Result<<DB as Database>::Arguments<'q>, BoxDynError>
+    where
+        DB: Database,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        bool: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        let mut arguments = <DB as Database>::Arguments::<'q>::default();
+        for value in &self.values {
+            match value {
+                FilterValue::Int(v) => arguments.add(*v)?,
+                FilterValue::Real(v) => arguments.add(*v)?,
+                FilterValue::Bool(v) => arguments.add(*v)?,
+                FilterValue::Text(v) => arguments.add(v.clone())?,
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// The filter values followed by `after` and `limit`, for [`page_sql`](Self::page_sql)
+    pub fn page_arguments<'q, DB>(&self, after: i64, limit: i64) -> Result<<DB as Database>::Arguments<'q>, BoxDynError>
+    where
+        DB: Database,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        bool: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        let mut arguments = self.arguments::<DB>()?;
+        arguments.add(after)?;
+        arguments.add(limit)?;
+        Ok(arguments)
+    }
+}
+
+fn parse_value(column: &ColumnDef, value: &str) -> Result<FilterValue, String> {
+    let invalid = |expected: &str| format!("`{}` must be {}, got `{}`", column.name, expected, value);
+    Ok(match column.column_type {
+        ColumnType::Integer => FilterValue::Int(value.parse().map_err(|_| invalid("an integer"))?),
+        ColumnType::Real => FilterValue::Real(value.parse().map_err(|_| invalid("a number"))?),
+        ColumnType::Boolean => FilterValue::Bool(match value {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => return Err(invalid("a boolean")),
+        }),
+        ColumnType::Text => FilterValue::Text(value.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::POST;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::Sqlite;
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn builds_filtered_queries() {
+        let query = ListQuery::parse(&POST, &params(&[("title_contains", "rust"), ("score", "1.5"), ("format", "csv")]), &["format"]).unwrap();
+        assert_eq!(query.select_sql(Backend::Postgres), "SELECT * FROM post WHERE score = $1 AND title LIKE $2 ORDER BY id");
+        assert_eq!(query.count_sql(Backend::Sqlite), "SELECT COUNT(*) FROM post WHERE score = ? AND title LIKE ?");
+        assert_eq!(
+            query.page_sql(Backend::Postgres),
+            "SELECT * FROM post WHERE score = $1 AND title LIKE $2 AND id > $3 ORDER BY id LIMIT $4"
+        );
+        assert_eq!(ListQuery::all(&POST).page_sql(Backend::Sqlite), "SELECT * FROM post WHERE id > ? ORDER BY id LIMIT ?");
+    }
+
+    #[test]
+    fn rejects_unknown_hidden_and_mistyped_filters() {
+        let error = |pairs: &[(&str, &str)]| ListQuery::parse(&POST, &params(pairs), &[]).unwrap_err();
+        assert!(error(&[("author", "a")]).contains("unknown filter"));
+        assert!(error(&[("score_contains", "1")]).contains("unknown filter"));
+        assert!(error(&[("edit_token_contains", "a")]).contains("cannot filter on `edit_token`"));
+        assert!(error(&[("id", "one")]).contains("must be an integer"));
+    }
+
+    #[actix_web::test]
+    async fn binds_filters_and_pages() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE post (id INTEGER PRIMARY KEY, slug TEXT, title TEXT, score REAL)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO post VALUES (1, 'a', 'Rust', 1.0), (2, 'b', 'Trust', 2.0), (3, 'c', 'Go', 1.0)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let query = ListQuery::parse(&POST, &params(&[("title_contains", "ust")]), &[]).unwrap();
+        let count: i64 = sqlx::query_scalar_with(&query.count_sql(Backend::Sqlite), query.arguments::<Sqlite>().unwrap())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let page: Vec<i64> = sqlx::query_scalar_with(&query.page_sql(Backend::Sqlite).replace('*', "id"), query.page_arguments::<Sqlite>(1, 10).unwrap())
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(page, [2]);
+    }
+}
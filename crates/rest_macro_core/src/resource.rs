@@ -0,0 +1,136 @@
+//! Static metadata describing what `#[derive(RestApi)]` generated.
+//!
+//! Every derived struct implements [`Resource`], and its `configure` adds the
+//! metadata to a process-wide registry, so tooling such as OpenAPI generation
+//! can discover every mounted resource through [`registered`].
+
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::Value;
+
+use crate::migrate::{ColumnDef, TableSchema};
+use crate::permissions::PermissionRequirement;
+use crate::roles::RoleRequirement;
+
+/// Implemented by `#[derive(RestApi)]` for every resource struct
+pub trait Resource {
+    fn metadata() -> &'static ResourceMeta;
+}
+
+#[derive(Debug)]
+pub struct ResourceMeta {
+    /// Name of the Rust struct, such as `Post`
+    pub name: &'static str,
+    pub table: &'static str,
+    /// Collection route relative to the scope `configure` is mounted in, such as `/post`
+    pub path: &'static str,
+    pub primary_key: &'static str,
+    /// Table layout, also used to generate migrations
+    pub schema: TableSchema,
+    pub relations: &'static [RelationMeta],
+    /// Fields holding uploaded files
+    pub files: &'static [FileMeta],
+    /// `#[rest_api(hidden)]` fields, left out of exports, change events and webhook payloads
+    pub hidden: &'static [&'static str],
+    /// Enabled operations, in the order `list`, `get`, `create`, `update`, `patch`, `delete`
+    pub operations: &'static [OperationMeta],
+}
+
+impl ResourceMeta {
+    pub fn columns(&self) -> &'static [ColumnDef] {
+        self.schema.columns
+    }
+
+    pub fn column(&self, name: &str) -> Option<&'static ColumnDef> {
+        self.schema.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn operation(&self, name: &str) -> Option<&'static OperationMeta> {
+        self.operations.iter().find(|op| op.name == name)
+    }
+
+    pub fn file(&self, field: &str) -> Option<&'static FileMeta> {
+        self.files.iter().find(|f| f.field == field)
+    }
+
+    pub fn is_hidden(&self, field: &str) -> bool {
+        self.hidden.contains(&field)
+    }
+
+    /// Remove the hidden fields from a serialized row
+    pub fn redact(&self, row: &mut Value) {
+        if let Value::Object(fields) = row {
+            for field in self.hidden {
+                fields.remove(*field);
+            }
+        }
+    }
+}
+
+/// A `#[relation]` on one of the resource's fields
+#[derive(Debug)]
+pub struct RelationMeta {
+    pub field: &'static str,
+    pub parent_table: &'static str,
+    pub parent_column: &'static str,
+    /// Path of the nested route such as `/post/{parent_id}/comment`, if one is registered
+    pub nested_path: Option<&'static str>,
+}
+
+/// A `#[rest_api(file(...))]` field, stored as a [`StoredFile`](crate::files::StoredFile)
+#[derive(Debug)]
+pub struct FileMeta {
+    pub field: &'static str,
+    /// Largest accepted upload in bytes
+    pub max_size: u64,
+    /// Accepted content types such as `image/*` or `application/pdf`; empty accepts any
+    pub types: &'static [&'static str],
+}
+
+impl FileMeta {
+    pub fn accepts(&self, content_type: &str) -> bool {
+        let content_type = content_type.to_ascii_lowercase();
+        self.types.is_empty()
+            || self.types.iter().any(|pattern| match pattern.strip_suffix("/*") {
+                Some("*") => true,
+                Some(kind) => content_type.split_once('/').is_some_and(|(k, _)| k == kind),
+                None => content_type == pattern.to_ascii_lowercase(),
+            })
+    }
+}
+
+/// One generated operation and who may call it
+#[derive(Debug)]
+pub struct OperationMeta {
+    pub name: &'static str,
+    /// Open to anonymous callers
+    pub public: bool,
+    /// Compile-time role requirement, if any
+    pub roles: Option<RoleRequirement>,
+    /// Compile-time permission requirement, if any
+    pub permissions: Option<PermissionRequirement>,
+}
+
+fn registry() -> &'static Mutex<Vec<&'static ResourceMeta>> {
+    static REGISTRY: OnceLock<Mutex<Vec<&'static ResourceMeta>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(vec![]))
+}
+
+/// Record a configured resource. Called by the generated `configure`; registering
+/// the same table again, as every server worker does, has no effect.
+pub fn register(meta: &'static ResourceMeta) {
+    let mut registry = registry().lock().unwrap();
+    if !registry.iter().any(|m| m.table == meta.table) {
+        registry.push(meta);
+    }
+}
+
+/// The configured resource on `table`
+pub fn lookup(table: &str) -> Option<&'static ResourceMeta> {
+    registry().lock().unwrap().iter().copied().find(|meta| meta.table == table)
+}
+
+/// Every resource configured so far, in registration order
+pub fn registered() -> Vec<&'static ResourceMeta> {
+    registry().lock().unwrap().clone()
+}
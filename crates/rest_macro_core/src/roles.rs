@@ -0,0 +1,237 @@
+use actix_web::{web, HttpRequest};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::auth::UserContext;
+
+/// Roles required for a generated operation.
+///
+/// The user must hold at least one role from `any` (when it is not empty) and
+/// every role in `all`. Held roles are matched through a [`RoleHierarchy`], so
+/// a role also satisfies every role it outranks.
+#[derive(Debug, Clone, Copy)]
+pub struct RoleRequirement {
+    pub any: &'static [&'static str],
+    pub all: &'static [&'static str],
+}
+
+impl RoleRequirement {
+    pub fn is_satisfied_by(&self, user: &UserContext, hierarchy: &RoleHierarchy) -> bool {
+        (self.any.is_empty() || self.any.iter().any(|role| hierarchy.grants(&user.roles, role)))
+            && self.all.iter().all(|role| hierarchy.grants(&user.roles, role))
+    }
+}
+
+/// Ranking of roles, where a higher role implicitly holds every role below it.
+///
+/// Declared with chains such as `admin > editor > user`. A superuser role
+/// outranks every role, including ones the hierarchy never mentions. The
+/// default hierarchy makes `admin` a superuser and nothing else.
+///
+/// Generated handlers look the hierarchy up per request: a
+/// `web::Data<RoleHierarchy>` registered as app data wins, then the
+/// `ROLE_HIERARCHY` environment variable, then the default.
+///
+/// ```
+/// use rest_macro_core::roles::RoleHierarchy;
+///
+/// let hierarchy = RoleHierarchy::parse("admin > *, admin > editor > user").unwrap();
+/// assert!(hierarchy.grants(&["editor".to_string()], "user"));
+/// assert!(!hierarchy.grants(&["editor".to_string()], "admin"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RoleHierarchy {
+    /// Each role mapped to every role it outranks, directly or transitively
+    outranks: HashMap<String, HashSet<String>>,
+    superusers: HashSet<String>,
+}
+
+impl Default for RoleHierarchy {
+    fn default() -> Self {
+        Self::new().superuser("admin")
+    }
+}
+
+/// Error returned when a role hierarchy declaration cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleHierarchyError(String);
+
+impl fmt::Display for RoleHierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid role hierarchy: {}", self.0)
+    }
+}
+
+impl std::error::Error for RoleHierarchyError {}
+
+impl RoleHierarchy {
+    /// An empty hierarchy: every role only satisfies itself
+    pub fn new() -> Self {
+        Self {
+            outranks: HashMap::new(),
+            superusers: HashSet::new(),
+        }
+    }
+
+    /// Declare a chain of roles, highest first: `chain(["admin", "editor", "user"])`
+    pub fn chain<I, S>(mut self, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let roles: Vec<String> = roles.into_iter().map(Into::into).collect();
+        for pair in roles.windows(2) {
+            self.outranks
+                .entry(pair[0].clone())
+                .or_default()
+                .insert(pair[1].clone());
+        }
+        self.close();
+        self
+    }
+
+    /// Let `role` satisfy every requirement
+    pub fn superuser(mut self, role: impl Into<String>) -> Self {
+        self.superusers.insert(role.into());
+        self
+    }
+
+    /// Parse a declaration such as `"admin > editor > user, admin > auditor"`.
+    ///
+    /// Chains are separated by commas or semicolons. `role > *` makes `role` a
+    /// superuser.
+    pub fn parse(spec: &str) -> Result<Self, RoleHierarchyError> {
+        let mut hierarchy = Self::new();
+
+        for chain in spec.split([',', ';']).map(str::trim).filter(|c| !c.is_empty()) {
+            let roles: Vec<&str> = chain.split('>').map(str::trim).collect();
+            if roles.iter().any(|role| role.is_empty()) {
+                return Err(RoleHierarchyError(format!("empty role name in `{}`", chain)));
+            }
+
+            match roles.iter().position(|role| *role == "*") {
+                Some(pos) if pos == roles.len() - 1 && roles.len() == 2 => {
+                    hierarchy = hierarchy.superuser(roles[0]);
+                }
+                Some(_) => {
+                    return Err(RoleHierarchyError(format!(
+                        "`*` must appear alone after a single role, as in `admin > *`, not `{}`",
+                        chain
+                    )));
+                }
+                None => {
+                    if roles.len() < 2 {
+                        return Err(RoleHierarchyError(format!(
+                            "`{}` does not rank any roles, expected `higher > lower`",
+                            chain
+                        )));
+                    }
+                    hierarchy = hierarchy.chain(roles);
+                }
+            }
+        }
+
+        Ok(hierarchy)
+    }
+
+    /// Whether any of the `held` roles is or outranks `required`
+    pub fn grants(&self, held: &[String], required: &str) -> bool {
+        held.iter().any(|role| {
+            role == required
+                || self.superusers.contains(role)
+                || self.outranks.get(role).is_some_and(|lower| lower.contains(required))
+        })
+    }
+
+    /// Whether any of the `held` roles is a superuser
+    pub fn is_superuser(&self, held: &[String]) -> bool {
+        held.iter().any(|role| self.superusers.contains(role))
+    }
+
+    /// The hierarchy that applies to a request
+    pub fn from_request(req: &HttpRequest) -> &RoleHierarchy {
+        match req.app_data::<web::Data<RoleHierarchy>>() {
+            Some(hierarchy) => hierarchy.get_ref(),
+            None => Self::from_env(),
+        }
+    }
+
+    /// The hierarchy declared by `ROLE_HIERARCHY`, or the default one
+    fn from_env() -> &'static RoleHierarchy {
+        static FROM_ENV: OnceLock<RoleHierarchy> = OnceLock::new();
+
+        FROM_ENV.get_or_init(|| {
+            let _ = dotenv::dotenv();
+
+            match env::var("ROLE_HIERARCHY") {
+                Ok(spec) if !spec.trim().is_empty() => match Self::parse(&spec) {
+                    Ok(hierarchy) => hierarchy,
+                    Err(e) => {
+                        eprintln!("WARNING: {}. Falling back to the default role hierarchy", e);
+                        Self::default()
+                    }
+                },
+                _ => Self::default(),
+            }
+        })
+    }
+
+    /// Extend `outranks` until it is transitively closed
+    fn close(&mut self) {
+        loop {
+            let mut changed = false;
+            let roles: Vec<String> = self.outranks.keys().cloned().collect();
+            for role in roles {
+                let lower: Vec<String> = self.outranks[&role].iter().cloned().collect();
+                for below in lower {
+                    let transitive: Vec<String> = self
+                        .outranks
+                        .get(&below)
+                        .map(|set| set.iter().cloned().collect())
+                        .unwrap_or_default();
+                    let entry = self.outranks.get_mut(&role).unwrap();
+                    for t in transitive {
+                        changed |= entry.insert(t);
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn chains_are_transitive() {
+        let hierarchy = RoleHierarchy::parse("admin > editor > user").unwrap();
+        assert!(hierarchy.grants(&roles(&["admin"]), "user"));
+        assert!(hierarchy.grants(&roles(&["editor"]), "user"));
+        assert!(!hierarchy.grants(&roles(&["user"]), "editor"));
+        assert!(!hierarchy.grants(&roles(&["admin"]), "auditor"));
+    }
+
+    #[test]
+    fn default_keeps_admin_bypass() {
+        let hierarchy = RoleHierarchy::default();
+        assert!(hierarchy.grants(&roles(&["admin"]), "anything"));
+        assert!(!hierarchy.grants(&roles(&["user"]), "editor"));
+    }
+
+    #[test]
+    fn rejects_malformed_declarations() {
+        assert!(RoleHierarchy::parse("admin").is_err());
+        assert!(RoleHierarchy::parse("admin > > user").is_err());
+        assert!(RoleHierarchy::parse("* > admin").is_err());
+    }
+}
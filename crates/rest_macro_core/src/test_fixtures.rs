@@ -0,0 +1,97 @@
+//! Resources shared by the unit tests: a `post`, its `comment`s and a
+//! `document` with a file, as `#[derive(RestApi)]` would describe them.
+
+use crate::migrate::{ColumnDef, ColumnType, ForeignKey, TableSchema};
+use crate::resource::{FileMeta, OperationMeta, RelationMeta, ResourceMeta};
+use crate::roles::RoleRequirement;
+
+/// `id`, a unique `slug`, `title`, an optional `score` and a hidden
+/// `edit_token`. Anyone may list and get posts, and only editors may update them.
+pub static POST: ResourceMeta = ResourceMeta {
+    name: "Post",
+    table: "post",
+    path: "/post",
+    primary_key: "id",
+    schema: TableSchema {
+        name: "post",
+        columns: &[
+            ColumnDef::primary_key("id"),
+            ColumnDef { unique: true, ..ColumnDef::new("slug", ColumnType::Text) },
+            ColumnDef::new("title", ColumnType::Text),
+            ColumnDef { nullable: true, ..ColumnDef::new("score", ColumnType::Real) },
+            ColumnDef { nullable: true, ..ColumnDef::new("edit_token", ColumnType::Text) },
+        ],
+        unique: &[],
+    },
+    relations: &[],
+    files: &[],
+    hidden: &["edit_token"],
+    operations: &[
+        OperationMeta { name: "list", public: true, roles: None, permissions: None },
+        OperationMeta { name: "get", public: true, roles: None, permissions: None },
+        OperationMeta { name: "create", public: false, roles: None, permissions: None },
+        OperationMeta {
+            name: "update",
+            public: false,
+            roles: Some(RoleRequirement { any: &["editor"], all: &[] }),
+            permissions: None,
+        },
+    ],
+};
+
+/// `id`, `post_id` referencing `post.id`, `title`, an optional `rating` and
+/// `created_at`. Comments can be listed, created and patched by signed-in users.
+pub static COMMENT: ResourceMeta = ResourceMeta {
+    name: "Comment",
+    table: "comment",
+    path: "/comment",
+    primary_key: "id",
+    schema: TableSchema {
+        name: "comment",
+        columns: &[
+            ColumnDef::primary_key("id"),
+            ColumnDef {
+                references: Some(ForeignKey { table: "post", column: "id" }),
+                ..ColumnDef::new("post_id", ColumnType::Integer)
+            },
+            ColumnDef::new("title", ColumnType::Text),
+            ColumnDef { nullable: true, ..ColumnDef::new("rating", ColumnType::Real) },
+            ColumnDef { nullable: true, default_now: true, ..ColumnDef::new("created_at", ColumnType::Text) },
+        ],
+        unique: &[],
+    },
+    relations: &[RelationMeta { field: "post_id", parent_table: "post", parent_column: "id", nested_path: None }],
+    files: &[],
+    hidden: &[],
+    operations: &[
+        OperationMeta { name: "list", public: false, roles: None, permissions: None },
+        OperationMeta { name: "create", public: false, roles: None, permissions: None },
+        OperationMeta { name: "patch", public: false, roles: None, permissions: None },
+    ],
+};
+
+/// `id`, `title` and an optional `file`. Anyone may list, get, create and delete documents.
+pub static DOCUMENT: ResourceMeta = ResourceMeta {
+    name: "Document",
+    table: "document",
+    path: "/document",
+    primary_key: "id",
+    schema: TableSchema {
+        name: "document",
+        columns: &[
+            ColumnDef::primary_key("id"),
+            ColumnDef::new("title", ColumnType::Text),
+            ColumnDef { nullable: true, ..ColumnDef::new("file", ColumnType::Text) },
+        ],
+        unique: &[],
+    },
+    relations: &[],
+    files: &[FileMeta { field: "file", max_size: 1024, types: &[] }],
+    hidden: &[],
+    operations: &[
+        OperationMeta { name: "list", public: true, roles: None, permissions: None },
+        OperationMeta { name: "get", public: true, roles: None, permissions: None },
+        OperationMeta { name: "create", public: true, roles: None, permissions: None },
+        OperationMeta { name: "delete", public: true, roles: None, permissions: None },
+    ],
+};
@@ -0,0 +1,234 @@
+//! Request-scoped database transactions.
+//!
+//! [`Tx`] begins a transaction on the `web::Data<Pool<DB>>` registered as app
+//! data (as every resource's `configure` does) before the handler runs.
+//! Queries run on it through `&mut *tx`. Routes wrapped in [`auto_commit`]
+//! commit the transaction once the handler returns a `2xx` response and roll
+//! it back otherwise, so a handler only has to run its queries:
+//!
+//! ```rust,ignore
+//! App::new().service(
+//!     web::resource("/post/{id}/publish")
+//!         .wrap(from_fn(tx::auto_commit))
+//!         .route(web::post().to(publish)),
+//! )
+//!
+//! async fn publish(path: web::Path<i64>, mut tx: Tx<Sqlite>) -> impl Responder {
+//!     match publish_post(&mut tx, path.into_inner()).await {
+//!         Ok(()) => HttpResponse::Ok().finish(),
+//!         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+//!     }
+//! }
+//!
+//! async fn publish_post(conn: &mut SqliteConnection, id: i64) -> Result<(), sqlx::Error> {
+//!     sqlx::query("UPDATE post SET draft = 0 WHERE id = ?").bind(id).execute(&mut *conn).await?;
+//!     sqlx::query("INSERT INTO comment (post_id, title, content) VALUES (?, 'Published', '')")
+//!         .bind(id)
+//!         .execute(&mut *conn)
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Without `auto_commit`, a `Tx` dropped without a commit is rolled back, so
+//! an early return never leaves half of a handler's writes behind. Handlers
+//! that must act after the commit, such as publishing an event, call
+//! [`Tx::commit`] or [`Tx::finish`] themselves. The generated `create`,
+//! `update`, `patch`, `delete` and `import` handlers do so, and begin their
+//! transaction with [`Tx::begin`] only once the caller passed the role and
+//! permission checks.
+
+use std::ops::{Deref, DerefMut};
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{error, web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::{BoxFuture, LocalBoxFuture};
+use sqlx::{Database, Pool, Transaction};
+
+/// Commits (`true`) or rolls back (`false`) a transaction handed over by a dropped [`Tx`]
+type Finish = Box<dyn FnOnce(bool) -> BoxFuture<'static, Result<(), sqlx::Error>> + Send>;
+
+/// Transactions left open by the handler, kept in the request extensions by [`auto_commit`]
+#[derive(Clone, Default)]
+struct Pending(Arc<Mutex<Vec<Finish>>>);
+
+/// A transaction begun for the current request
+pub struct Tx<DB: Database> {
+    /// Only `None` once `commit` or `rollback` took it
+    tx: Option<Transaction<'static, DB>>,
+    /// Where to hand the transaction on drop, when [`auto_commit`] wraps the route
+    pending: Option<Pending>,
+}
+
+impl<DB: Database> Tx<DB> {
+    /// Begin a transaction that is rolled back unless committed, whether or
+    /// not the route is wrapped in [`auto_commit`]
+    pub async fn begin(pool: &Pool<DB>) -> Result<Self, sqlx::Error> {
+        Ok(Tx { tx: Some(pool.begin().await?), pending: None })
+    }
+
+    pub async fn commit(mut self) -> Result<(), sqlx::Error> {
+        self.take().commit().await
+    }
+
+    pub async fn rollback(mut self) -> Result<(), sqlx::Error> {
+        self.take().rollback().await
+    }
+
+    fn take(&mut self) -> Transaction<'static, DB> {
+        self.tx.take().expect("the transaction is only taken when the Tx is consumed")
+    }
+
+    /// Commit if `response` is a `2xx`, otherwise roll back. A failed commit
+    /// replaces the response with `500 Internal Server Error`.
+    pub async fn finish(self, response: HttpResponse) -> HttpResponse {
+        if !response.status().is_success() {
+            if let Err(e) = self.rollback().await {
+                log::warn!("Failed to roll back transaction: {}", e);
+            }
+            return response;
+        }
+        match self.commit().await {
+            Ok(()) => response,
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    }
+}
+
+impl<DB: Database> Deref for Tx<DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &DB::Connection {
+        self.tx.as_ref().expect("the transaction is only taken when the Tx is consumed")
+    }
+}
+
+impl<DB: Database> DerefMut for Tx<DB> {
+    fn deref_mut(&mut self) -> &mut DB::Connection {
+        self.tx.as_mut().expect("the transaction is only taken when the Tx is consumed")
+    }
+}
+
+/// Hands a transaction that is still open to [`auto_commit`]; without it, the
+/// transaction is rolled back
+impl<DB: Database> Drop for Tx<DB> {
+    fn drop(&mut self) {
+        if let (Some(tx), Some(pending)) = (self.tx.take(), self.pending.take()) {
+            pending.0.lock().unwrap().push(Box::new(move |commit| {
+                Box::pin(async move { if commit { tx.commit().await } else { tx.rollback().await } })
+            }));
+        }
+    }
+}
+
+/// Begins the transaction on the pool in app data; `500` if there is none
+impl<DB: Database> FromRequest for Tx<DB> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let pool = req.app_data::<web::Data<Pool<DB>>>().cloned();
+        let pending = req.extensions().get::<Pending>().cloned();
+        Box::pin(async move {
+            let Some(pool) = pool else {
+                return Err(error::ErrorInternalServerError("No database pool is registered for this route"));
+            };
+            let mut tx = Tx::begin(&pool).await.map_err(error::ErrorInternalServerError)?;
+            tx.pending = pending;
+            Ok(tx)
+        })
+    }
+}
+
+/// Middleware that commits the [`Tx`] extracted for a request once its
+/// handler returns a `2xx` response, and rolls it back otherwise. Register it
+/// with [`from_fn`](actix_web::middleware::from_fn). A failed commit replaces
+/// the response with `500 Internal Server Error`.
+pub async fn auto_commit<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let pending = Pending::default();
+    req.extensions_mut().insert(pending.clone());
+    let res = next.call(req).await?;
+
+    let commit = res.status().is_success();
+    let open = std::mem::take(&mut *pending.0.lock().unwrap());
+    let mut failed = None;
+    for finish in open {
+        match finish(commit).await {
+            Err(e) if commit => failed = Some(e),
+            Err(e) => log::warn!("Failed to roll back transaction: {}", e),
+            Ok(()) => {}
+        }
+    }
+    Ok(match failed {
+        Some(e) => res.into_response(HttpResponse::InternalServerError().body(e.to_string())).map_into_right_body(),
+        None => res.map_into_left_body(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::Sqlite;
+
+    #[actix_web::test]
+    async fn commits_only_successful_responses() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE post (title TEXT)").execute(&pool).await.unwrap();
+
+        for (title, mut response) in [("kept", HttpResponse::Created()), ("dropped", HttpResponse::BadRequest())] {
+            let mut tx = Tx::<Sqlite>::begin(&pool).await.unwrap();
+            sqlx::query("INSERT INTO post VALUES (?)").bind(title).execute(&mut *tx).await.unwrap();
+            tx.finish(response.finish()).await;
+        }
+        // Dropping rolls back too
+        let mut tx = Tx::<Sqlite>::begin(&pool).await.unwrap();
+        sqlx::query("INSERT INTO post VALUES ('abandoned')").execute(&mut *tx).await.unwrap();
+        drop(tx);
+
+        let titles: Vec<String> = sqlx::query_scalar("SELECT title FROM post").fetch_all(&pool).await.unwrap();
+        assert_eq!(titles, ["kept"]);
+    }
+
+    /// Inserts every title in one transaction, failing on the first duplicate
+    async fn insert_titles(titles: web::Json<Vec<String>>, mut tx: Tx<Sqlite>) -> HttpResponse {
+        for title in titles.into_inner() {
+            if let Err(e) = sqlx::query("INSERT INTO post VALUES (?)").bind(title).execute(&mut *tx).await {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+        }
+        HttpResponse::Created().finish()
+    }
+
+    #[actix_web::test]
+    async fn auto_commit_keeps_whole_requests_only() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE post (title TEXT NOT NULL UNIQUE)").execute(&pool).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(web::resource("/posts").wrap(from_fn(auto_commit)).route(web::post().to(insert_titles)))
+                .route("/unwrapped", web::post().to(insert_titles)),
+        )
+        .await;
+        let post = |uri: &str, titles: &[&str]| test::TestRequest::post().uri(uri).set_json(titles).to_request();
+
+        assert_eq!(test::call_service(&app, post("/posts", &["a", "b"])).await.status(), 201);
+        // `c` is inserted before `a` fails, and goes with the rest of the request
+        assert_eq!(test::call_service(&app, post("/posts", &["c", "a"])).await.status(), 500);
+        // Without the middleware, nothing commits what the handler left open
+        assert_eq!(test::call_service(&app, post("/unwrapped", &["d"])).await.status(), 201);
+
+        let titles: Vec<String> = sqlx::query_scalar("SELECT title FROM post ORDER BY title").fetch_all(&pool).await.unwrap();
+        assert_eq!(titles, ["a", "b"]);
+    }
+}
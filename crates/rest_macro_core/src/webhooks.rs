@@ -0,0 +1,674 @@
+//! Outgoing webhooks for resource changes, enabled by the `webhooks` feature.
+//!
+//! Subscriptions live in a `webhook` table managed by superusers through
+//! [`webhook_routes`]. Once [`Webhooks::start`] runs, every [change
+//! event](crate::events) matching a subscription's resource and events is
+//! recorded in `webhook_delivery` and POSTed to its URL as JSON, signed with
+//! the subscription's secret:
+//!
+//! ```text
+//! X-Webhook-Signature: sha256=<hex HMAC-SHA256 of the body>
+//! X-Webhook-Event: post.create
+//! X-Webhook-Delivery: 42
+//! ```
+//!
+//! Failed deliveries (network errors and non-2xx responses) are retried with
+//! exponential backoff, 5 attempts starting at 2 seconds by default. Deliveries
+//! still pending when the process stops are resumed by the next `start`.
+//!
+//! Both tables are created by migrations: add [`WEBHOOK_SCHEMA`] and
+//! [`DELIVERY_SCHEMA`] to the [`Migrator`](crate::migrate::Migrator) before
+//! connecting.
+//!
+//! ```rust,ignore
+//! Migrator::new(pool.clone(), "migrations")
+//!     .table(webhooks::WEBHOOK_SCHEMA)
+//!     .table(webhooks::DELIVERY_SCHEMA)
+//!     .run()
+//!     .await?;
+//! let webhooks = Webhooks::connect(pool).await?;
+//! ```
+
+use std::time::Duration;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use hmac::{Hmac, Mac};
+use rand::distr::Alphanumeric;
+use rand::{rng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{AnyPool, FromRow, Row};
+
+use crate::auth::{require_superuser, UserContext};
+use crate::events::{self, ChangeEvent};
+use crate::migrate::{Backend, ColumnDef, ColumnType, ForeignKey, TableSchema};
+
+/// The `webhook` table, for the [`Migrator`](crate::migrate::Migrator)
+pub const WEBHOOK_SCHEMA: TableSchema = TableSchema {
+    name: "webhook",
+    columns: &[
+        ColumnDef::primary_key("id"),
+        ColumnDef::new("url", ColumnType::Text),
+        ColumnDef::new("resource", ColumnType::Text),
+        ColumnDef::new("events", ColumnType::Text),
+        ColumnDef::new("secret", ColumnType::Text),
+        ColumnDef { nullable: true, default_now: true, ..ColumnDef::new("created_at", ColumnType::Text) },
+    ],
+    unique: &[],
+};
+
+/// The `webhook_delivery` table, for the [`Migrator`](crate::migrate::Migrator)
+pub const DELIVERY_SCHEMA: TableSchema = TableSchema {
+    name: "webhook_delivery",
+    columns: &[
+        ColumnDef::primary_key("id"),
+        ColumnDef {
+            references: Some(ForeignKey { table: "webhook", column: "id" }),
+            ..ColumnDef::new("webhook_id", ColumnType::Integer)
+        },
+        ColumnDef::new("event_id", ColumnType::Integer),
+        ColumnDef::new("resource", ColumnType::Text),
+        ColumnDef::new("action", ColumnType::Text),
+        ColumnDef::new("payload", ColumnType::Text),
+        ColumnDef::new("status", ColumnType::Text),
+        ColumnDef::new("attempts", ColumnType::Integer),
+        ColumnDef { nullable: true, ..ColumnDef::new("response_status", ColumnType::Integer) },
+        ColumnDef { nullable: true, ..ColumnDef::new("error", ColumnType::Text) },
+        ColumnDef { nullable: true, default_now: true, ..ColumnDef::new("created_at", ColumnType::Text) },
+        ColumnDef { nullable: true, default_now: true, ..ColumnDef::new("updated_at", ColumnType::Text) },
+    ],
+    unique: &[],
+};
+
+/// Actions a subscription can listen to, as published by the generated handlers
+const ACTIONS: [&str; 5] = ["create", "upsert", "update", "patch", "delete"];
+
+/// One row of the `webhook` table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookSubscription {
+    pub id: Option<i64>,
+    pub url: String,
+    /// Table name, or `*` for every resource
+    pub resource: String,
+    /// Comma-separated actions, or `*` for all of them
+    pub events: String,
+    /// Only returned once, when the subscription is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: Option<String>,
+}
+
+impl WebhookSubscription {
+    /// Whether this subscription wants `event`
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        (self.resource == "*" || self.resource == event.resource)
+            && (self.events == "*" || self.events.split(',').any(|e| e.trim() == event.action))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookInput {
+    pub url: String,
+    pub resource: String,
+    /// Defaults to every action
+    pub events: Option<Vec<String>>,
+    /// Generated when omitted
+    pub secret: Option<String>,
+}
+
+/// One row of the `webhook_delivery` table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Option<i64>,
+    pub webhook_id: i64,
+    pub event_id: i64,
+    pub resource: String,
+    pub action: String,
+    pub payload: String,
+    /// `pending`, `succeeded` or `failed`
+    pub status: String,
+    pub attempts: i64,
+    /// Status code of the last response, if one was received
+    pub response_status: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// `sha256=<hex>` signature of `body` with `secret`, as sent in `X-Webhook-Signature`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Webhook subscriptions, their delivery log and the dispatcher that sends them
+#[derive(Clone)]
+pub struct Webhooks {
+    pool: AnyPool,
+    backend: Backend,
+    http: reqwest::Client,
+    max_attempts: i64,
+    backoff: Duration,
+}
+
+impl Webhooks {
+    /// Webhooks stored in the `webhook` and `webhook_delivery` tables, which must already be migrated
+    pub async fn connect(pool: AnyPool) -> Result<Self, sqlx::Error> {
+        let backend = Backend::of_pool(&pool)
+            .await
+            .map_err(|e| sqlx::Error::Configuration(e.to_string().into()))?;
+        // Fail at startup rather than on the first event when a table is missing
+        sqlx::query("SELECT id FROM webhook WHERE 1 = 0").execute(&pool).await?;
+        sqlx::query("SELECT id FROM webhook_delivery WHERE 1 = 0").execute(&pool).await?;
+
+        Ok(Self {
+            pool,
+            backend,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("default TLS backend is available"),
+            max_attempts: 5,
+            backoff: Duration::from_secs(2),
+        })
+    }
+
+    /// Attempts per delivery, and the delay before the first retry, which doubles after each failure
+    pub fn with_retries(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        self.max_attempts = i64::from(max_attempts.max(1));
+        self.backoff = backoff;
+        self
+    }
+
+    /// Resume pending deliveries and start forwarding change events.
+    ///
+    /// Call once, from within the actix runtime.
+    pub fn start(&self) {
+        let webhooks = self.clone();
+        // Listening before spawning catches events published while pending deliveries resume
+        let mut events = events::listen();
+        actix_web::rt::spawn(async move {
+            webhooks.resume_pending().await;
+            while let Some(event) = events.recv().await {
+                webhooks.enqueue(&event).await;
+            }
+        });
+    }
+
+    async fn resume_pending(&self) {
+        let pending = sqlx::query_as::<_, WebhookDelivery>("SELECT * FROM webhook_delivery WHERE status = 'pending'")
+            .fetch_all(&self.pool)
+            .await;
+        let pending = match pending {
+            Ok(pending) => pending,
+            Err(e) => return log::error!("Failed to load pending webhook deliveries: {}", e),
+        };
+        for delivery in pending {
+            match self.subscription(delivery.webhook_id).await {
+                Ok(Some(webhook)) => self.spawn_delivery(webhook, delivery),
+                Ok(None) => {}
+                Err(e) => log::error!("Failed to load webhook {}: {}", delivery.webhook_id, e),
+            }
+        }
+    }
+
+    /// Record a delivery for each matching subscription and send it in the background
+    async fn enqueue(&self, event: &ChangeEvent) {
+        let sql = format!("SELECT * FROM webhook WHERE resource = {} OR resource = '*'", self.backend.placeholder(1));
+        let webhooks = match sqlx::query_as::<_, WebhookSubscription>(&sql)
+            .bind(&event.resource)
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(webhooks) => webhooks,
+            Err(e) => return log::error!("Failed to load webhooks: {}", e),
+        };
+
+        let payload = serde_json::to_string(event).unwrap_or_default();
+        for webhook in webhooks.into_iter().filter(|w| w.matches(event)) {
+            let Some(webhook_id) = webhook.id else { continue };
+            let sql = format!(
+                "INSERT INTO webhook_delivery (webhook_id, event_id, resource, action, payload, status, attempts)
+                 VALUES ({}, {}, {}, {}, {}, 'pending', 0){}",
+                self.backend.placeholder(1),
+                self.backend.placeholder(2),
+                self.backend.placeholder(3),
+                self.backend.placeholder(4),
+                self.backend.placeholder(5),
+                returning_id(self.backend)
+            );
+            let result = inserted_id(
+                &self.pool,
+                self.backend,
+                sqlx::query(&sql)
+                    .bind(webhook_id)
+                    .bind(event.id as i64)
+                    .bind(&event.resource)
+                    .bind(&event.action)
+                    .bind(&payload),
+            )
+            .await;
+
+            match result {
+                Ok(id) => {
+                    let delivery = WebhookDelivery {
+                        id: Some(id),
+                        webhook_id,
+                        event_id: event.id as i64,
+                        resource: event.resource.clone(),
+                        action: event.action.clone(),
+                        payload: payload.clone(),
+                        status: "pending".to_string(),
+                        attempts: 0,
+                        response_status: None,
+                        error: None,
+                        created_at: None,
+                        updated_at: None,
+                    };
+                    self.spawn_delivery(webhook, delivery);
+                }
+                Err(e) => log::error!("Failed to record webhook delivery: {}", e),
+            }
+        }
+    }
+
+    fn spawn_delivery(&self, webhook: WebhookSubscription, delivery: WebhookDelivery) {
+        let webhooks = self.clone();
+        actix_web::rt::spawn(async move { webhooks.deliver(webhook, delivery).await });
+    }
+
+    /// Send until a 2xx response or the attempts run out, logging each attempt
+    async fn deliver(&self, webhook: WebhookSubscription, delivery: WebhookDelivery) {
+        let Some(delivery_id) = delivery.id else { return };
+        let signature = sign(&webhook.secret, delivery.payload.as_bytes());
+        let event = format!("{}.{}", delivery.resource, delivery.action);
+
+        for attempt in delivery.attempts + 1..=self.max_attempts {
+            let response = self
+                .http
+                .post(&webhook.url)
+                .header("Content-Type", "application/json")
+                .header("X-Webhook-Signature", &signature)
+                .header("X-Webhook-Event", &event)
+                .header("X-Webhook-Delivery", delivery_id.to_string())
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+
+            let (response_status, error) = match response {
+                Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+                Ok(response) => (Some(response.status().as_u16()), Some(format!("HTTP {}", response.status()))),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let status = match &error {
+                None => "succeeded",
+                Some(_) if attempt == self.max_attempts => "failed",
+                Some(_) => "pending",
+            };
+
+            let sql = format!(
+                "UPDATE webhook_delivery
+                 SET status = {}, attempts = {}, response_status = {}, error = {}, updated_at = CURRENT_TIMESTAMP
+                 WHERE id = {}",
+                self.backend.placeholder(1),
+                self.backend.placeholder(2),
+                self.backend.placeholder(3),
+                self.backend.placeholder(4),
+                self.backend.placeholder(5)
+            );
+            let logged = sqlx::query(&sql)
+                .bind(status)
+                .bind(attempt)
+                .bind(response_status.map(i64::from))
+                .bind(&error)
+                .bind(delivery_id)
+                .execute(&self.pool)
+                .await;
+            if let Err(e) = logged {
+                log::error!("Failed to log webhook delivery {}: {}", delivery_id, e);
+            }
+
+            if error.is_none() || attempt == self.max_attempts {
+                return;
+            }
+            let delay = self.backoff * 2u32.saturating_pow((attempt - 1) as u32);
+            actix_web::rt::time::sleep(delay).await;
+        }
+    }
+
+    async fn subscription(&self, id: i64) -> Result<Option<WebhookSubscription>, sqlx::Error> {
+        let sql = format!("SELECT * FROM webhook WHERE id = {}", self.backend.placeholder(1));
+        sqlx::query_as::<_, WebhookSubscription>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+}
+
+/// ` RETURNING id`, except on MySQL, which has no `RETURNING` and reports
+/// inserted ids with the result instead; see [`inserted_id`]
+fn returning_id(backend: Backend) -> &'static str {
+    if backend == Backend::Mysql { "" } else { " RETURNING id" }
+}
+
+/// Run an `INSERT` ending in [`returning_id`] and return the new row's id
+async fn inserted_id<'q>(
+    pool: &AnyPool,
+    backend: Backend,
+    query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+) -> Result<i64, sqlx::Error> {
+    if backend != Backend::Mysql {
+        return query.fetch_one(pool).await?.try_get(0);
+    }
+    query
+        .execute(pool)
+        .await?
+        .last_insert_id()
+        .ok_or_else(|| sqlx::Error::Protocol("the database did not report the inserted id".into()))
+}
+
+pub async fn list_webhooks(req: HttpRequest, user: UserContext, webhooks: web::Data<Webhooks>) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    match sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook ORDER BY id")
+        .fetch_all(&webhooks.pool)
+        .await
+    {
+        Ok(rows) => HttpResponse::Ok().json(rows),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Create a subscription; the response is the only place its secret is returned
+pub async fn add_webhook(
+    req: HttpRequest,
+    input: web::Json<WebhookInput>,
+    user: UserContext,
+    webhooks: web::Data<Webhooks>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    let input = input.into_inner();
+    if !input.url.starts_with("http://") && !input.url.starts_with("https://") {
+        return HttpResponse::BadRequest().body("url must start with http:// or https://");
+    }
+    if input.resource.trim().is_empty() {
+        return HttpResponse::BadRequest().body("resource cannot be empty");
+    }
+    let events = match input.events {
+        None => "*".to_string(),
+        Some(events) if events.iter().any(|e| e == "*") => "*".to_string(),
+        Some(events) => {
+            if let Some(unknown) = events.iter().find(|e| !ACTIONS.contains(&e.as_str())) {
+                return HttpResponse::BadRequest().body(format!(
+                    "unknown event `{}`, expected `*` or any of: {}",
+                    unknown,
+                    ACTIONS.join(", ")
+                ));
+            }
+            events.join(",")
+        }
+    };
+    let secret = input
+        .secret
+        .filter(|secret| !secret.is_empty())
+        .unwrap_or_else(|| rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect());
+
+    let backend = webhooks.backend;
+    let sql = format!(
+        "INSERT INTO webhook (url, resource, events, secret) VALUES ({}, {}, {}, {}){}",
+        backend.placeholder(1),
+        backend.placeholder(2),
+        backend.placeholder(3),
+        backend.placeholder(4),
+        returning_id(backend)
+    );
+    let result = inserted_id(
+        &webhooks.pool,
+        backend,
+        sqlx::query(&sql).bind(&input.url).bind(&input.resource).bind(&events).bind(&secret),
+    )
+    .await;
+
+    match result {
+        Ok(id) => HttpResponse::Created().json(serde_json::json!({
+            "id": id,
+            "url": input.url,
+            "resource": input.resource,
+            "events": events,
+            "secret": secret,
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Delete a subscription and its delivery log
+pub async fn remove_webhook(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    user: UserContext,
+    webhooks: web::Data<Webhooks>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    let id = path.into_inner();
+    let placeholder = webhooks.backend.placeholder(1);
+    if let Err(e) = sqlx::query(&format!("DELETE FROM webhook_delivery WHERE webhook_id = {}", placeholder))
+        .bind(id)
+        .execute(&webhooks.pool)
+        .await
+    {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    match sqlx::query(&format!("DELETE FROM webhook WHERE id = {}", placeholder))
+        .bind(id)
+        .execute(&webhooks.pool)
+        .await
+    {
+        Ok(res) if res.rows_affected() > 0 => HttpResponse::Ok().finish(),
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// The 100 most recent deliveries of a subscription, newest first
+pub async fn list_deliveries(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    user: UserContext,
+    webhooks: web::Data<Webhooks>,
+) -> impl Responder {
+    if let Some(denied) = require_superuser(&req, &user) {
+        return denied;
+    }
+
+    let id = path.into_inner();
+    match webhooks.subscription(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+    let sql = format!(
+        "SELECT * FROM webhook_delivery WHERE webhook_id = {} ORDER BY id DESC LIMIT 100",
+        webhooks.backend.placeholder(1)
+    );
+    match sqlx::query_as::<_, WebhookDelivery>(&sql)
+        .bind(id)
+        .fetch_all(&webhooks.pool)
+        .await
+    {
+        Ok(rows) => HttpResponse::Ok().json(rows),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Register the webhook endpoints under `/webhooks`.
+///
+/// The [`Webhooks`] itself must be registered as app data, and started with
+/// [`Webhooks::start`].
+pub fn webhook_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/webhooks", web::get().to(list_webhooks));
+    cfg.route("/webhooks", web::post().to(add_webhook));
+    cfg.route("/webhooks/{id}", web::delete().to(remove_webhook));
+    cfg.route("/webhooks/{id}/deliveries", web::get().to(list_deliveries));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::issue_token;
+    use crate::migrate::Migrator;
+    use crate::resource;
+    use crate::test_fixtures::POST;
+    use actix_web::{test as actix_test, App};
+    use sqlx::any::AnyPoolOptions;
+
+    async fn webhooks() -> Webhooks {
+        sqlx::any::install_default_drivers();
+        let dir = std::env::temp_dir().join(format!("vsr-webhooks-{:x}", rand::random::<u64>()));
+        let pool = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        Migrator::new(pool.clone(), &dir)
+            .table(WEBHOOK_SCHEMA)
+            .table(DELIVERY_SCHEMA)
+            .sync("webhooks")
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+        Webhooks::connect(pool).await.unwrap().with_retries(1, Duration::ZERO)
+    }
+
+    fn token(roles: &[&str]) -> String {
+        let roles = roles.iter().map(|r| r.to_string()).collect();
+        format!("Bearer {}", issue_token(1, roles, vec![], chrono::Duration::minutes(5)).unwrap())
+    }
+
+    #[test]
+    fn signs_and_matches() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let webhook = WebhookSubscription {
+            id: Some(1),
+            url: "http://localhost".to_string(),
+            resource: "post".to_string(),
+            events: "create,delete".to_string(),
+            secret: String::new(),
+            created_at: None,
+        };
+        let event = |resource: &str, action: &str| ChangeEvent {
+            id: 1,
+            resource: resource.to_string(),
+            action: action.to_string(),
+            record_id: None,
+            data: None,
+        };
+        assert!(webhook.matches(&event("post", "delete")));
+        assert!(!webhook.matches(&event("post", "patch")));
+        assert!(!webhook.matches(&event("comment", "create")));
+    }
+
+    #[actix_web::test]
+    async fn manages_webhooks_through_routes() {
+        let webhooks = web::Data::new(webhooks().await);
+        let app = actix_test::init_service(App::new().app_data(webhooks.clone()).configure(webhook_routes)).await;
+        let add = |body: serde_json::Value, roles: &[&str]| {
+            actix_test::TestRequest::post()
+                .uri("/webhooks")
+                .insert_header(("Authorization", token(roles)))
+                .set_json(body)
+                .to_request()
+        };
+        let body = serde_json::json!({ "url": "http://localhost/hook", "resource": "post", "events": ["create"] });
+
+        assert_eq!(actix_test::call_service(&app, add(body.clone(), &["user"])).await.status(), 403);
+        let bad_url = serde_json::json!({ "url": "ftp://localhost", "resource": "post" });
+        assert_eq!(actix_test::call_service(&app, add(bad_url, &["admin"])).await.status(), 400);
+        let created: serde_json::Value = actix_test::call_and_read_body_json(&app, add(body, &["admin"])).await;
+        assert_eq!(created["events"], "create");
+        assert_eq!(created["secret"].as_str().map(str::len), Some(32));
+
+        let get = |uri: &str| actix_test::TestRequest::get().uri(uri).insert_header(("Authorization", token(&["admin"])));
+        let listed: Vec<serde_json::Value> = actix_test::call_and_read_body_json(&app, get("/webhooks").to_request()).await;
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].get("secret").is_none());
+        let uri = format!("/webhooks/{}/deliveries", created["id"]);
+        let deliveries: Vec<WebhookDelivery> = actix_test::call_and_read_body_json(&app, get(&uri).to_request()).await;
+        assert!(deliveries.is_empty());
+
+        let remove = || {
+            actix_test::TestRequest::delete()
+                .uri(&format!("/webhooks/{}", created["id"]))
+                .insert_header(("Authorization", token(&["admin"])))
+                .to_request()
+        };
+        assert_eq!(actix_test::call_service(&app, remove()).await.status(), 200);
+        assert_eq!(actix_test::call_service(&app, remove()).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn records_payloads_without_hidden_fields() {
+        resource::register(&POST);
+        let webhooks = webhooks().await;
+        // Nothing listens on the discard port, so the single attempt fails fast
+        sqlx::query("INSERT INTO webhook (url, resource, events, secret) VALUES ('http://127.0.0.1:9', 'post', '*', 's')")
+            .execute(&webhooks.pool)
+            .await
+            .unwrap();
+        webhooks.start();
+        events::publish_with("post", "create", Some(1), &serde_json::json!({ "title": "x", "edit_token": "secret" }));
+
+        let mut payloads = vec![];
+        for _ in 0..100 {
+            payloads = sqlx::query_scalar::<_, String>("SELECT payload FROM webhook_delivery")
+                .fetch_all(&webhooks.pool)
+                .await
+                .unwrap();
+            if !payloads.is_empty() {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!payloads.is_empty(), "no delivery was recorded");
+        for payload in payloads {
+            let event: serde_json::Value = serde_json::from_str(&payload).unwrap();
+            assert!(event["data"].get("edit_token").is_none(), "{}", payload);
+        }
+    }
+
+    #[actix_web::test]
+    async fn enqueues_bursts_larger_than_the_event_history() {
+        let webhooks = webhooks().await;
+        sqlx::query("INSERT INTO webhook (url, resource, events, secret) VALUES ('http://127.0.0.1:9', 'burst', '*', 's')")
+            .execute(&webhooks.pool)
+            .await
+            .unwrap();
+        webhooks.start();
+        let published = events::HISTORY as i64 + 10;
+        for id in 0..published {
+            events::publish("burst", "delete", Some(id));
+        }
+
+        let mut recorded = 0;
+        for _ in 0..500 {
+            recorded = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM webhook_delivery WHERE resource = 'burst'")
+                .fetch_one(&webhooks.pool)
+                .await
+                .unwrap();
+            if recorded == published {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(recorded, published);
+    }
+}
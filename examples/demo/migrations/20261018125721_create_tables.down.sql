@@ -0,0 +1,8 @@
+-- Generated by very_simple_rest
+
+DROP TABLE comment;
+
+DROP TABLE post;
+
+DROP TABLE user;
+
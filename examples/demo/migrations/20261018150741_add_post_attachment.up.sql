@@ -0,0 +1,4 @@
+-- Generated by very_simple_rest
+
+ALTER TABLE post ADD COLUMN attachment TEXT;
+
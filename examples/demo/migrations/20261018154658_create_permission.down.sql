@@ -0,0 +1,4 @@
+-- Generated by very_simple_rest
+
+DROP TABLE permission;
+
@@ -0,0 +1,10 @@
+-- Generated by very_simple_rest
+
+CREATE TABLE permission (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    resource TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    role TEXT NOT NULL,
+    UNIQUE (resource, operation, role)
+);
+
@@ -0,0 +1,6 @@
+-- Generated by very_simple_rest
+
+DROP TABLE webhook_delivery;
+
+DROP TABLE webhook;
+
@@ -1,13 +1,17 @@
-use sqlx::{Sqlite, SqlitePool};
+use sqlx::SqlitePool;
 use very_simple_rest::prelude::*;
+use very_simple_rest::graphql;
+use very_simple_rest::webhooks::{self, webhook_routes, Webhooks};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
-#[rest_api(table = "post", id = "id", db = "sqlite")]
-#[require_role(read = "user", update = "user", patch = "user", delete = "user")]
+#[rest_api(table = "post", id = "id", db = "sqlite", cache(ttl = 60))]
+#[require_role(read = "public", update = "user", patch = "user", delete = "user")]
 pub struct Post {
     pub id: Option<i64>,
     pub title: String,
     pub content: String,
+    #[rest_api(file(max_size = "10MB", types = "image/*, application/pdf"))]
+    pub attachment: Option<StoredFile>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
@@ -15,6 +19,7 @@ pub struct Post {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
 #[rest_api(table = "comment", id = "id", db = "sqlite")]
 #[require_role(read = "user", update = "user", patch = "user", delete = "user")]
+#[require_permission(delete = "comment:moderate")]
 pub struct Comment {
     pub id: Option<i64>,
     pub title: String,
@@ -30,7 +35,10 @@ pub struct Comment {
 #[require_role(read = "admin", update = "admin", delete = "admin")]
 pub struct User {
     pub id: Option<i64>,
+    #[rest_api(unique)]
     pub email: String,
+    #[rest_api(hidden)]
+    #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: String,
 }
@@ -39,38 +47,66 @@ fn log_available_endpoints() {
     let id = "1";
     info!("===== Available API Endpoints =====");
 
+    // Documentation endpoints
+    info!("Documentation:");
+    info!("  GET    /openapi.json      - OpenAPI 3.1 document");
+    info!("  GET    /docs              - Swagger UI");
+    info!("  POST   /api/graphql       - GraphQL over all resources");
+    info!("");
+
     // Auth endpoints
     info!("Authentication:");
     info!("  POST   /api/auth/register  - Register a new user");
     info!("  POST   /api/auth/login     - Login and get a JWT token");
     info!("  GET    /api/auth/me        - Get authenticated user info");
 
+    // Policy endpoints
+    info!("Policy (requires admin role):");
+    info!("  GET    /api/policy        - List runtime role grants");
+    info!("  POST   /api/policy        - Grant a role an operation on a resource");
+    info!("  DELETE /api/policy/{id}   - Remove a grant");
+
+    // Webhook endpoints
+    info!("Webhooks (requires admin role):");
+    info!("  GET    /api/webhooks      - List webhook subscriptions");
+    info!("  POST   /api/webhooks      - Subscribe a URL to resource changes");
+    info!("  DELETE /api/webhooks/{id} - Remove a subscription");
+    info!("  GET    /api/webhooks/{id}/deliveries - Delivery log");
+
     // User endpoints
     info!("Users (requires admin role):");
     info!("  GET    /api/user          - Get all users");
     info!("  GET    /api/user/{id}     - Get user by ID");
     info!("  POST   /api/user          - Create a new user");
+    info!("  POST   /api/user?on_conflict=email - Create or update a user by email");
     info!("  PUT    /api/user/{id}     - Update user");
     info!("  DELETE /api/user/{id}     - Delete user");
 
     // Post endpoints
-    info!("Posts (requires user role):");
+    info!("Posts (reads are public, writes require user role):");
     info!("  GET    /api/post          - Get all posts");
     info!("  GET    /api/post/{id}     - Get post by ID");
-    info!("  POST   /api/post          - Create a new post");
+    info!("  GET    /api/post/{id}/attachment - Download a post's attachment");
+    info!("  POST   /api/post          - Create a new post (JSON or multipart with an attachment)");
     info!("  PUT    /api/post/{id}     - Update post");
     info!("  PATCH  /api/post/{id}     - Update post");
     info!("  DELETE /api/post/{id}     - Delete post");
+    info!("  GET    /api/post/_schema  - JSON Schema for post payloads");
+    info!("  GET    /api/post/events   - Stream post changes (SSE; /events/ws for WebSocket)");
+    info!("  GET    /api/post/export   - Export posts (?format=csv|ndjson, filtered like the list)");
+    info!("  POST   /api/post/import   - Import posts from CSV or NDJSON");
 
     // Comment endpoints
     info!("Comments (requires user role):");
-    info!("  GET    /api/comment         - Get all comments");
+    info!("  GET    /api/comment         - Get all comments (?post_id=1&title_contains=hi to filter)");
+    info!("  GET    /api/comment/count   - Count comments (also X-Total-Count on HEAD /api/comment)");
     info!("  GET    /api/comment/{id}    - Get comment by ID");
     info!("  POST   /api/comment         - Create a new comment");
     info!("  PUT    /api/comment/{id}    - Update comment");
     info!("  PATCH  /api/comment/{id}    - Update comment");
-    info!("  DELETE /api/comment/{id}    - Delete comment");
+    info!("  DELETE /api/comment/{id}    - Delete comment (requires comment:moderate)");
     info!("  GET    /api/post/{id}/comment - Get comments for a post");
+    info!("  GET    /api/comment/aggregate?count=*&group_by=post_id - Count comments per post");
 
     info!("=====================================");
 }
@@ -92,8 +128,36 @@ async fn main() -> std::io::Result<()> {
 
     info!("Database connection established");
 
-    // Tables will be automatically created by the RestApi macro
-    info!("Configuring server with automatic table creation...");
+    // Bring the tables in line with the structs, writing a migration for any change
+    info!("Applying schema migrations...");
+    let migrator = Migrator::new(any_pool.clone(), "migrations")
+        .table(policy::SCHEMA)
+        .table(webhooks::WEBHOOK_SCHEMA)
+        .table(webhooks::DELIVERY_SCHEMA)
+        .resource::<User>()
+        .resource::<Post>()
+        .resource::<Comment>();
+    match migrator.sync("schema").await {
+        Ok(applied) => {
+            for migration in applied {
+                info!("Applied migration {}_{}", migration.version, migration.name);
+            }
+        }
+        Err(e) => {
+            error!("Failed to migrate the database: {} - shutting down", e);
+            return Ok(());
+        }
+    }
+
+    // Role grants in the `permission` table override `#[require_role]` at runtime
+    let policy_store = web::Data::new(PolicyStore::connect(any_pool.clone()).await.unwrap());
+
+    // Admin-managed webhook subscriptions, called after every write
+    let webhooks = Webhooks::connect(any_pool.clone()).await.unwrap();
+    webhooks.start();
+    let webhooks = web::Data::new(webhooks);
+
+    info!("Configuring server...");
 
     let server_pool = pool.clone();
     let server_any_pool = any_pool.clone();
@@ -109,10 +173,21 @@ async fn main() -> std::io::Result<()> {
             .wrap(Logger::default())
             .wrap(cors)
             .wrap(DefaultHeaders::new().add(("X-Version", "0.1.0")))
+            // Moderators may delete any comment
+            .app_data(web::Data::new(
+                PermissionMap::new().grant("moderator", ["comment:moderate"]),
+            ))
+            .app_data(policy_store.clone())
+            .app_data(webhooks.clone())
+            // API documentation
+            .configure(OpenApi::new("very_simple_rest demo", "0.1.0").base_path("/api").routes())
             // Api routes
             .service(
                 scope("/api")
                     .configure(|cfg| auth::auth_routes(cfg, server_any_pool.clone()))
+                    .configure(policy::policy_routes)
+                    .configure(webhook_routes)
+                    .configure(|cfg| graphql::graphql_routes(cfg, server_any_pool.clone()))
                     .configure(|cfg| User::configure(cfg, server_pool.clone()))
                     .configure(|cfg| Post::configure(cfg, server_pool.clone()))
                     .configure(|cfg| Comment::configure(cfg, server_pool.clone())),
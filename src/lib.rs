@@ -14,17 +14,25 @@ authorization, and relationship handling.
 - Zero-boilerplate REST APIs with a single derive macro
 - JWT-based authentication with role management
 - Role-Based Access Control (RBAC) for endpoint protection
-- Automatic database schema generation
+- Versioned schema migrations generated from your structs
+- OpenAPI 3.1 document at `/openapi.json`, with optional Swagger UI (`swagger-ui` feature) or Redoc (`redoc` feature)
+- Typed reqwest clients for every resource (`client` feature)
+- GraphQL endpoint over all resources (`graphql` feature)
+- Server-Sent Events and WebSocket streams of resource changes at `/{table}/events`
+- Signed outgoing webhooks with retries and a delivery log (`webhooks` feature)
+- Opt-in response caching with `#[rest_api(cache(ttl = 60))]`, evicted on writes
+- CSV and NDJSON export and import at `/{table}/export` and `/{table}/import`
+- Row counts at `/{table}/count` and in `HEAD /{table}`, and grouped sums and averages at `/{table}/aggregate`
+- File fields with multipart uploads, pluggable blob storage and downloads at `/{table}/{id}/{field}`
+- MessagePack and CBOR bodies next to JSON, negotiated by `Accept` and `Content-Type` (`msgpack` and `cbor` features)
+- A `Tx` extractor for request-scoped transactions, used by the generated write handlers
 - Relationship handling with nested routes
 - Support for SQLite, PostgreSQL, and MySQL (via feature flags)
 
 ## Quick Start
 
-```rust
-use actix_web::{App, HttpServer, web};
-use serde::{Deserialize, Serialize};
-use sqlx::{AnyPool, FromRow};
-use rest_api::prelude::*;
+```rust,no_run
+use very_simple_rest::prelude::*;
 
 // Define your data models with RBAC
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
@@ -43,13 +51,20 @@ async fn main() -> std::io::Result<()> {
     sqlx::any::install_default_drivers();
 
     let pool = AnyPool::connect("sqlite:app.db?mode=rwc").await.unwrap();
+    let sqlite_pool = sqlx::SqlitePool::connect("sqlite:app.db?mode=rwc").await.unwrap();
+
+    Migrator::new(pool.clone(), "migrations")
+        .table(Post::SCHEMA)
+        .run()
+        .await
+        .unwrap();
 
     HttpServer::new(move || {
         App::new()
             .service(
                 web::scope("/api")
                     .configure(|cfg| auth::auth_routes(cfg, pool.clone()))
-                    .configure(|cfg| Post::configure(cfg, pool.clone()))
+                    .configure(|cfg| Post::configure(cfg, sqlite_pool.clone()))
             )
     })
     .bind(("127.0.0.1", 8080))?
@@ -74,8 +89,78 @@ pub use rest_macro_core as core;
 
 pub mod auth {
     pub use rest_macro_core::auth::{
-        auth_routes, login, me, register, LoginInput, RegisterInput, User, UserContext,
-        ensure_admin_exists,
+        auth_routes, issue_token, login, me, register, require_superuser, LoginInput, OptionalUserContext,
+        RegisterInput, User, UserContext, ensure_admin_exists,
+    };
+}
+
+pub mod cache {
+    pub use rest_macro_core::cache::{CacheBackend, MemoryCache, ResponseCache};
+}
+
+pub mod codec {
+    pub use rest_macro_core::codec::{Codec, Payload};
+}
+
+#[cfg(feature = "client")]
+pub mod client {
+    pub use rest_macro_core::client::{ApiClient, AuthClient, ClientError, Method};
+}
+
+pub mod files {
+    pub use rest_macro_core::files::{BlobStore, FileStorage, LocalBlobStore, StoredFile};
+}
+
+pub mod events {
+    pub use rest_macro_core::events::{listen, publish, publish_with, subscribe, ChangeEvent, Subscription, Update};
+}
+
+#[cfg(feature = "graphql")]
+pub mod graphql {
+    pub use rest_macro_core::graphql::{build_schema, graphql_routes};
+}
+
+pub mod migrate {
+    pub use rest_macro_core::migrate::{
+        create_migration, AppliedMigration, Backend, ColumnDef, ColumnType, ForeignKey, MigrateError,
+        Migration, MigrationStatus, Migrator, TableSchema,
+    };
+}
+
+pub mod openapi {
+    pub use rest_macro_core::openapi::OpenApi;
+}
+
+pub mod permissions {
+    pub use rest_macro_core::permissions::{PermissionMap, PermissionMapError, PermissionRequirement};
+}
+
+pub mod policy {
+    pub use rest_macro_core::policy::{policy_routes, PolicyGrant, PolicyStore, SCHEMA};
+}
+
+pub mod query {
+    pub use rest_macro_core::query::ListQuery;
+}
+
+pub mod resource {
+    pub use rest_macro_core::resource::{
+        register, registered, OperationMeta, RelationMeta, Resource, ResourceMeta,
+    };
+}
+
+pub mod roles {
+    pub use rest_macro_core::roles::{RoleHierarchy, RoleHierarchyError, RoleRequirement};
+}
+
+pub mod tx {
+    pub use rest_macro_core::tx::{auto_commit, Tx};
+}
+
+#[cfg(feature = "webhooks")]
+pub mod webhooks {
+    pub use rest_macro_core::webhooks::{
+        sign, webhook_routes, WebhookDelivery, WebhookSubscription, Webhooks, DELIVERY_SCHEMA, WEBHOOK_SCHEMA,
     };
 }
 
@@ -90,6 +175,14 @@ pub use sqlx;
 pub mod prelude {
     pub use crate::auth;
     pub use crate::auth::UserContext;
+    pub use crate::files::StoredFile;
+    pub use crate::migrate::Migrator;
+    pub use crate::openapi::OpenApi;
+    pub use crate::permissions::PermissionMap;
+    pub use crate::policy::{self, PolicyStore};
+    pub use crate::resource::Resource;
+    pub use crate::roles::RoleHierarchy;
+    pub use crate::tx::Tx;
     pub use crate::core;
     pub use crate::RestApi;
 
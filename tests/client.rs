@@ -0,0 +1,71 @@
+//! The generated `<Struct>Client` calls the routes `configure` registers, against a live server
+#![cfg(feature = "client")]
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::{App, HttpServer};
+use very_simple_rest::client::ApiClient;
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "topic", operations = "list, get, create")]
+#[require_role(read = "public", create = "user")]
+pub struct Topic {
+    pub id: Option<i64>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "reply")]
+#[require_role(read = "public", create = "user", update = "user", delete = "user")]
+pub struct Reply {
+    pub id: Option<i64>,
+    #[relation(references = "topic.id")]
+    pub topic_id: i64,
+    pub body: String,
+}
+
+#[actix_web::test]
+async fn generated_clients_reach_their_routes() {
+    let db = database(&[Topic::SCHEMA, Reply::SCHEMA]).await;
+    let server = HttpServer::new(move || {
+        let db = db.clone();
+        App::new().configure(|cfg| {
+            Topic::configure(cfg, db.clone());
+            Reply::configure(cfg, db);
+        })
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let url = format!("http://{}", server.addrs()[0]);
+    let handle = server.run();
+    actix_web::rt::spawn(handle);
+
+    let api = ApiClient::new(&url);
+    let topics = TopicClient::new(api.clone());
+    let replies = ReplyClient::new(api.clone());
+
+    let topic = Topic { id: None, title: "Clients".into() };
+    assert_eq!(topics.create(&topic).await.unwrap_err().status(), Some(401));
+    api.set_token(Some(bearer(&["user"]).trim_start_matches("Bearer ").to_string()));
+    topics.create(&topic).await.unwrap();
+    topics.create(&Topic { id: None, title: "Other".into() }).await.unwrap();
+    assert_eq!(topics.list().await.unwrap().len(), 2);
+    assert_eq!(topics.get(1).await.unwrap().title, "Clients");
+
+    replies.create(&Reply { id: None, topic_id: 1, body: "First".into() }).await.unwrap();
+    replies.create(&Reply { id: None, topic_id: 2, body: "Elsewhere".into() }).await.unwrap();
+    replies.update(1, &Reply { id: Some(1), topic_id: 1, body: "Edited".into() }).await.unwrap();
+    let changes = PartialReply { body: Some("Patched".into()), ..Default::default() };
+    replies.patch(2, &changes).await.unwrap();
+
+    assert_eq!(replies.get(1).await.unwrap().body, "Edited");
+    assert_eq!(replies.list_by_topic_id(2).await.unwrap(), [Reply { id: Some(2), topic_id: 2, body: "Patched".into() }]);
+    assert_eq!(replies.list().await.unwrap().len(), 2);
+
+    replies.delete(2).await.unwrap();
+    assert_eq!(replies.get(2).await.unwrap_err().status(), Some(404));
+    assert!(replies.list_by_topic_id(2).await.unwrap().is_empty());
+}
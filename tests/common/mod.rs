@@ -0,0 +1,48 @@
+//! Helpers shared by the integration tests, which mount `#[derive(RestApi)]`
+//! resources on a fresh SQLite database
+
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use very_simple_rest::auth::issue_token;
+use very_simple_rest::migrate::{Migrator, TableSchema};
+use very_simple_rest::sqlx::sqlite::SqlitePoolOptions;
+use very_simple_rest::sqlx::{self, AnyPool, SqlitePool};
+
+/// A new database file with `tables` migrated, and a pool on it for the generated handlers
+pub async fn database(tables: &[TableSchema]) -> SqlitePool {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+
+    sqlx::any::install_default_drivers();
+    let dir = std::env::temp_dir().join(format!(
+        "vsr-it-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let url = format!("sqlite://{}?mode=rwc", dir.join("app.db").display());
+
+    let any = AnyPool::connect(&url).await.unwrap();
+    let migrator = tables.iter().fold(Migrator::new(any.clone(), dir.join("migrations")), |migrator, table| {
+        migrator.table(*table)
+    });
+    migrator.sync("init").await.unwrap();
+    any.close().await;
+
+    SqlitePoolOptions::new().max_connections(1).connect(&url).await.unwrap()
+}
+
+/// `Authorization` header value for a user with `roles`
+pub fn bearer(roles: &[&str]) -> String {
+    bearer_with(roles, &[])
+}
+
+/// `Authorization` header value for a user with `roles` and `permissions`
+pub fn bearer_with(roles: &[&str], permissions: &[&str]) -> String {
+    let roles = roles.iter().map(|r| r.to_string()).collect();
+    let permissions = permissions.iter().map(|p| p.to_string()).collect();
+    let token = issue_token(1, roles, permissions, chrono::Duration::minutes(5)).unwrap();
+    format!("Bearer {}", token)
+}
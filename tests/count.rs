@@ -0,0 +1,46 @@
+//! `GET /{table}/count` and `HEAD /{table}` count the rows `list` would return
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::http::Method;
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "task", db = "sqlite")]
+#[require_role(read = "public", create = "user", update = "user", delete = "user")]
+pub struct Task {
+    pub id: Option<i64>,
+    pub project_id: i64,
+    pub title: String,
+}
+
+#[actix_web::test]
+async fn counts_match_the_filters() {
+    let db = database(&[Task::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Task::configure(cfg, db))).await;
+    for (project_id, title) in [(1, "Write docs"), (1, "Fix bug"), (1, "Write tests"), (2, "Write release notes")] {
+        let create = test::TestRequest::post()
+            .uri("/task")
+            .insert_header(("Authorization", bearer(&["user"])))
+            .set_json(serde_json::json!({ "project_id": project_id, "title": title }))
+            .to_request();
+        assert_eq!(test::call_service(&app, create).await.status(), 201);
+    }
+
+    for (query, expected) in [("", 4), ("?project_id=1", 3), ("?project_id=1&title_contains=Write", 2), ("?project_id=3", 0)] {
+        let count = test::TestRequest::get().uri(&format!("/task/count{}", query)).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, count).await;
+        assert_eq!(body, serde_json::json!({ "count": expected }), "{}", query);
+
+        let head = test::TestRequest::default().method(Method::HEAD).uri(&format!("/task{}", query)).to_request();
+        let response = test::call_service(&app, head).await;
+        assert_eq!(response.status(), 200);
+        let total = response.headers().get("X-Total-Count").and_then(|v| v.to_str().ok());
+        assert_eq!(total, Some(expected.to_string().as_str()), "{}", query);
+    }
+
+    let unknown = test::TestRequest::get().uri("/task/count?owner=1").to_request();
+    assert_eq!(test::call_service(&app, unknown).await.status(), 400);
+}
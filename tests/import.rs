@@ -0,0 +1,38 @@
+//! `POST /{table}/import` accepts uploads above actix's default payload limit
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "reading", db = "sqlite")]
+#[require_role(read = "public", create = "user", update = "user", delete = "user")]
+pub struct Reading {
+    pub id: Option<i64>,
+    pub sensor: String,
+    pub value: f64,
+}
+
+#[actix_web::test]
+async fn imports_files_larger_than_the_default_payload_limit() {
+    let db = database(&[Reading::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Reading::configure(cfg, db))).await;
+
+    let line = format!("{{\"sensor\":\"{}\",\"value\":1.5}}\n", "s".repeat(100));
+    let rows = 300 * 1024 / line.len();
+    let body = line.repeat(rows);
+    assert!(body.len() > 256 * 1024);
+    let import = test::TestRequest::post()
+        .uri("/reading/import?format=ndjson")
+        .insert_header(("Authorization", bearer(&["user"])))
+        .set_payload(body)
+        .to_request();
+    let report: serde_json::Value = test::call_and_read_body_json(&app, import).await;
+    assert_eq!(report["inserted"], rows);
+
+    let count = test::TestRequest::get().uri("/reading/count").to_request();
+    let count: serde_json::Value = test::call_and_read_body_json(&app, count).await;
+    assert_eq!(count["count"], rows);
+}
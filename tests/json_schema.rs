@@ -0,0 +1,59 @@
+//! `/{table}/_schema` answers whoever may send at least one of the payloads it describes
+
+mod common;
+
+use common::{bearer, bearer_with, database};
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "ledger", db = "sqlite")]
+#[require_role(read = "public", create = "clerk", update = "auditor")]
+#[require_permission(patch = "ledger:amend")]
+pub struct Ledger {
+    pub id: Option<i64>,
+    pub entry: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "feedback", db = "sqlite")]
+#[require_role(read = "admin", create = "public", update = "admin")]
+pub struct Feedback {
+    pub id: Option<i64>,
+    pub message: String,
+}
+
+#[actix_web::test]
+async fn schema_needs_the_checks_of_one_payload_operation() {
+    let db = database(&[Ledger::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Ledger::configure(cfg, db))).await;
+    let schema = |authorization: Option<String>| {
+        let request = test::TestRequest::get().uri("/ledger/_schema");
+        match authorization {
+            Some(value) => request.insert_header(("Authorization", value)),
+            None => request,
+        }
+        .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, schema(None)).await.status(), 401);
+    assert_eq!(test::call_service(&app, schema(Some(bearer(&["reader"])))).await.status(), 403);
+    assert_eq!(test::call_service(&app, schema(Some(bearer(&["clerk"])))).await.status(), 200);
+    assert_eq!(test::call_service(&app, schema(Some(bearer(&["auditor"])))).await.status(), 200);
+
+    // `patch` inherits the `auditor` role, so the permission alone is not enough
+    let amend = bearer_with(&["reader"], &["ledger:amend"]);
+    assert_eq!(test::call_service(&app, schema(Some(amend))).await.status(), 403);
+    let amend = bearer_with(&["auditor"], &["ledger:amend"]);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, schema(Some(amend))).await;
+    assert!(body["$defs"]["patch"].is_object());
+}
+
+#[actix_web::test]
+async fn schema_is_public_when_a_payload_operation_is() {
+    let db = database(&[Feedback::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Feedback::configure(cfg, db))).await;
+
+    let schema = test::TestRequest::get().uri("/feedback/_schema").to_request();
+    assert_eq!(test::call_service(&app, schema).await.status(), 200);
+}
@@ -0,0 +1,86 @@
+//! `Resource::metadata()` describes what the derive generated
+
+mod common;
+
+use very_simple_rest::actix_web::{test as actix_test, App};
+use very_simple_rest::migrate::ColumnType;
+use very_simple_rest::prelude::*;
+use very_simple_rest::resource;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "reviews", db = "sqlite", exclude = "delete")]
+#[require_role(read = "public", create = "user", update(any = "editor", all = "staff"))]
+#[require_permission(update = "review:write")]
+pub struct Review {
+    pub id: Option<i64>,
+    #[relation(references = "book.id")]
+    pub book_id: i64,
+    #[rest_api(unique)]
+    pub slug: String,
+    pub rating: Option<f64>,
+    pub approved: bool,
+    #[rest_api(hidden)]
+    pub moderator_note: Option<String>,
+    #[rest_api(file(max_size = "1MB", types = "image/*"))]
+    pub photo: Option<StoredFile>,
+    pub created_at: Option<String>,
+}
+
+#[test]
+fn describes_the_struct() {
+    let meta = Review::metadata();
+    assert_eq!((meta.name, meta.table, meta.path, meta.primary_key), ("Review", "reviews", "/reviews", "id"));
+    assert_eq!(meta.schema.name, "reviews");
+
+    let columns: Vec<_> = meta
+        .columns()
+        .iter()
+        .map(|c| (c.name, c.column_type, c.primary_key, c.nullable, c.unique, c.default_now))
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("id", ColumnType::Integer, true, true, false, false),
+            ("book_id", ColumnType::Integer, false, false, false, false),
+            ("slug", ColumnType::Text, false, false, true, false),
+            ("rating", ColumnType::Real, false, true, false, false),
+            ("approved", ColumnType::Boolean, false, false, false, false),
+            ("moderator_note", ColumnType::Text, false, true, false, false),
+            ("photo", ColumnType::Text, false, true, false, false),
+            ("created_at", ColumnType::Text, false, true, false, true),
+        ]
+    );
+    let book = meta.column("book_id").and_then(|c| c.references).unwrap();
+    assert_eq!((book.table, book.column), ("book", "id"));
+
+    let relations: Vec<_> = meta.relations.iter().map(|r| (r.field, r.parent_table, r.parent_column, r.nested_path)).collect();
+    assert_eq!(relations, [("book_id", "book", "id", Some("/book/{parent_id}/reviews"))]);
+    assert_eq!(meta.hidden, ["moderator_note"]);
+    let photo = meta.file("photo").unwrap();
+    assert_eq!((photo.max_size, photo.types), (1024 * 1024, &["image/*"][..]));
+
+    let operations: Vec<_> = meta.operations.iter().map(|op| (op.name, op.public)).collect();
+    assert_eq!(
+        operations,
+        [("list", true), ("get", true), ("create", false), ("update", false), ("patch", false)]
+    );
+    // `create` and `patch` inherit each `update` requirement they do not declare themselves
+    let create = meta.operation("create").unwrap();
+    assert_eq!(create.roles.map(|r| (r.any, r.all)), Some((&["user"][..], &[][..])));
+    for op in ["update", "patch"] {
+        let operation = meta.operation(op).unwrap();
+        assert_eq!(operation.roles.map(|r| (r.any, r.all)), Some((&["editor"][..], &["staff"][..])));
+    }
+    for op in ["create", "update", "patch"] {
+        let operation = meta.operation(op).unwrap();
+        assert_eq!(operation.permissions.map(|p| (p.any, p.all)), Some((&["review:write"][..], &[][..])));
+    }
+    assert!(meta.operation("delete").is_none());
+}
+
+#[actix_web::test]
+async fn configure_registers_the_metadata() {
+    let db = common::database(&[Review::SCHEMA]).await;
+    actix_test::init_service(App::new().configure(|cfg| Review::configure(cfg, db))).await;
+    assert!(resource::registered().into_iter().any(|meta| std::ptr::eq(meta, Review::metadata())));
+}
@@ -0,0 +1,82 @@
+//! `operations`, `exclude` and `nested_route` decide which routes `configure` registers
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::http::Method;
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "stream", operations = "get")]
+pub struct Stream {
+    pub id: Option<i64>,
+    pub name: String,
+}
+
+/// Append-only: no update, patch or delete
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "event", operations = "list, get, create")]
+#[require_role(read = "user", create = "user")]
+pub struct Event {
+    pub id: Option<i64>,
+    #[relation(references = "stream.id")]
+    pub stream_id: i64,
+    pub name: String,
+}
+
+/// Everything but listing, and no nested route
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "note", exclude = "list")]
+#[require_role(get = "user", create = "user", update = "user", delete = "user")]
+pub struct Note {
+    pub id: Option<i64>,
+    #[relation(references = "stream.id", nested_route = "false")]
+    pub stream_id: i64,
+    pub body: String,
+}
+
+#[actix_web::test]
+async fn registers_enabled_operations_only() {
+    let db = database(&[Stream::SCHEMA, Event::SCHEMA, Note::SCHEMA]).await;
+    sqlx::query("INSERT INTO stream (name) VALUES ('main')").execute(&db).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .configure(|cfg| Event::configure(cfg, db.clone()))
+            .configure(|cfg| Note::configure(cfg, db)),
+    )
+    .await;
+    let call = |method: Method, uri: &str, body: serde_json::Value| {
+        test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .insert_header(("Authorization", bearer(&["user"])))
+            .set_json(body)
+            .to_request()
+    };
+    let status = |method: Method, uri: &'static str| {
+        let app = &app;
+        async move { test::call_service(app, call(method, uri, serde_json::json!({}))).await.status().as_u16() }
+    };
+
+    let event = serde_json::json!({ "stream_id": 1, "name": "opened" });
+    assert_eq!(test::call_service(&app, call(Method::POST, "/event", event.clone())).await.status(), 201);
+    assert_eq!(status(Method::GET, "/event").await, 200);
+    assert_eq!(status(Method::GET, "/event/1").await, 200);
+    assert_eq!(status(Method::GET, "/event/count").await, 200);
+    assert_eq!(test::call_service(&app, call(Method::PUT, "/event/1", event)).await.status(), 405);
+    assert_eq!(status(Method::PATCH, "/event/1").await, 405);
+    assert_eq!(status(Method::DELETE, "/event/1").await, 405);
+    // Relations get a nested route by default
+    assert_eq!(status(Method::GET, "/stream/1/event").await, 200);
+
+    let note = serde_json::json!({ "stream_id": 1, "body": "hello" });
+    assert_eq!(test::call_service(&app, call(Method::POST, "/note", note)).await.status(), 201);
+    assert_eq!(status(Method::GET, "/note/1").await, 200);
+    assert_eq!(status(Method::GET, "/note").await, 405);
+    assert_eq!(status(Method::HEAD, "/note").await, 405);
+    assert_eq!(status(Method::GET, "/note/count").await, 404);
+    assert_eq!(status(Method::GET, "/note/export").await, 404);
+    assert_eq!(status(Method::GET, "/stream/1/note").await, 404);
+    assert_eq!(status(Method::DELETE, "/note/1").await, 200);
+}
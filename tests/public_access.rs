@@ -0,0 +1,57 @@
+//! `public` operations let anonymous callers through; the others still need a token
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "article", db = "sqlite")]
+#[require_role(read = "public", create = "user", update = "user", delete = "admin")]
+pub struct Article {
+    pub id: Option<i64>,
+    pub title: String,
+}
+
+#[actix_web::test]
+async fn anonymous_callers_reach_public_operations_only() {
+    let db = database(&[Article::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Article::configure(cfg, db))).await;
+    let create = |authorization: Option<String>| {
+        let request = test::TestRequest::post().uri("/article").set_json(serde_json::json!({ "title": "Hello" }));
+        match authorization {
+            Some(value) => request.insert_header(("Authorization", value)),
+            None => request,
+        }
+        .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, create(None)).await.status(), 401);
+    assert_eq!(test::call_service(&app, create(Some(bearer(&["user"])))).await.status(), 201);
+
+    let articles: Vec<Article> = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/article").to_request()).await;
+    assert_eq!(articles.len(), 1);
+    let article = test::TestRequest::get().uri("/article/1").to_request();
+    assert_eq!(test::call_service(&app, article).await.status(), 200);
+    let count = test::TestRequest::get().uri("/article/count").to_request();
+    assert_eq!(test::call_service(&app, count).await.status(), 200);
+
+    // A token is still accepted on public routes, but only a valid one
+    let signed_in = test::TestRequest::get().uri("/article").insert_header(("Authorization", bearer(&["user"])));
+    assert_eq!(test::call_service(&app, signed_in.to_request()).await.status(), 200);
+    let forged = test::TestRequest::get().uri("/article").insert_header(("Authorization", "Bearer forged"));
+    assert_eq!(test::call_service(&app, forged.to_request()).await.status(), 401);
+
+    let delete = |authorization: Option<String>| {
+        let request = test::TestRequest::delete().uri("/article/1");
+        match authorization {
+            Some(value) => request.insert_header(("Authorization", value)),
+            None => request,
+        }
+        .to_request()
+    };
+    assert_eq!(test::call_service(&app, delete(None)).await.status(), 401);
+    assert_eq!(test::call_service(&app, delete(Some(bearer(&["user"])))).await.status(), 403);
+    assert_eq!(test::call_service(&app, delete(Some(bearer(&["admin"])))).await.status(), 200);
+}
@@ -0,0 +1,96 @@
+//! `POST /{table}?on_conflict=<field>` against a derived resource
+
+mod common;
+
+use common::{bearer, database};
+use very_simple_rest::actix_web::{test, App};
+use very_simple_rest::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "account", db = "sqlite")]
+#[require_role(read = "public", create = "user", update = "user")]
+pub struct Account {
+    pub id: Option<i64>,
+    #[rest_api(unique)]
+    pub email: String,
+    pub name: String,
+}
+
+#[actix_web::test]
+async fn upserts_on_unique_fields_only() {
+    let db = database(&[Account::SCHEMA]).await;
+    let app = test::init_service(App::new().configure(|cfg| Account::configure(cfg, db))).await;
+    let post = |uri: &str, email: &str, name: &str| {
+        test::TestRequest::post()
+            .uri(uri)
+            .insert_header(("Authorization", bearer(&["user"])))
+            .set_json(serde_json::json!({ "email": email, "name": name }))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, post("/account", "a@example.com", "A")).await.status(), 201);
+    let upsert = "/account?on_conflict=email";
+    assert_eq!(test::call_service(&app, post(upsert, "a@example.com", "B")).await.status(), 200);
+    assert_eq!(test::call_service(&app, post(upsert, "c@example.com", "C")).await.status(), 200);
+    assert_eq!(test::call_service(&app, post("/account?on_conflict=name", "a@example.com", "D")).await.status(), 400);
+    // Without `on_conflict` the unique constraint still applies
+    assert_eq!(test::call_service(&app, post("/account", "a@example.com", "E")).await.status(), 500);
+
+    let accounts: Vec<Account> = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/account").to_request()).await;
+    let rows: Vec<_> = accounts.iter().map(|a| (a.email.as_str(), a.name.as_str())).collect();
+    assert_eq!(rows, [("a@example.com", "B"), ("c@example.com", "C")]);
+}
+
+/// Anyone signed in may create, but only editors may overwrite
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "member", db = "sqlite")]
+#[require_role(read = "user", create = "public", update = "editor")]
+pub struct Member {
+    pub id: Option<i64>,
+    #[rest_api(unique)]
+    pub email: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, RestApi)]
+#[rest_api(table = "invite", db = "sqlite")]
+#[require_role(read = "user", create = "user", update = "editor")]
+pub struct Invite {
+    pub id: Option<i64>,
+    #[rest_api(unique)]
+    pub email: String,
+    pub name: String,
+}
+
+#[actix_web::test]
+async fn upserts_need_the_update_checks() {
+    let db = database(&[Member::SCHEMA, Invite::SCHEMA]).await;
+    let app = test::init_service(
+        App::new()
+            .configure(|cfg| Member::configure(cfg, db.clone()))
+            .configure(|cfg| Invite::configure(cfg, db)),
+    )
+    .await;
+    let post = |uri: &str, roles: Option<&[&str]>| {
+        let request = test::TestRequest::post()
+            .uri(uri)
+            .set_json(serde_json::json!({ "email": "a@example.com", "name": "A" }));
+        match roles {
+            Some(roles) => request.insert_header(("Authorization", bearer(roles))),
+            None => request,
+        }
+        .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, post("/invite", Some(&["user"]))).await.status(), 201);
+    let upsert = "/invite?on_conflict=email";
+    assert_eq!(test::call_service(&app, post(upsert, Some(&["user"]))).await.status(), 403);
+    assert_eq!(test::call_service(&app, post(upsert, Some(&["user", "editor"]))).await.status(), 200);
+
+    // A public create still needs a token to upsert
+    assert_eq!(test::call_service(&app, post("/member", None)).await.status(), 201);
+    let upsert = "/member?on_conflict=email";
+    assert_eq!(test::call_service(&app, post(upsert, None)).await.status(), 401);
+    assert_eq!(test::call_service(&app, post(upsert, Some(&["user"]))).await.status(), 403);
+    assert_eq!(test::call_service(&app, post(upsert, Some(&["editor"]))).await.status(), 200);
+}